@@ -0,0 +1,196 @@
+use scylla::transport::query_result::QueryResult;
+#[cfg(not(feature = "browse"))]
+use colored::*;
+use std::io::IsTerminal;
+use crate::error::CqlResult;
+use crate::formatter::{format_result, FormatOptions, OutputFormat};
+use crate::output;
+
+/// Shows `result` in the full-screen browser. Falls back to the static table
+/// when stdout is not a terminal, the result has no rows or cqlrs was built
+/// without the `browse` feature.
+pub fn browse_result(result: &QueryResult, options: &FormatOptions) -> CqlResult<()> {
+    let has_rows = result.rows.as_ref().is_some_and(|rows| !rows.is_empty());
+    if has_rows && std::io::stdout().is_terminal() {
+        #[cfg(feature = "browse")]
+        {
+            let (headers, rows) = crate::formatter::formatted_cells(result, options);
+            return tui::run(headers, rows).map_err(crate::error::CqlError::from);
+        }
+
+        #[cfg(not(feature = "browse"))]
+        eprintln!(
+            "{} cqlrs was built without the 'browse' feature, showing a static table",
+            "Note:".yellow()
+        );
+    }
+
+    output::println(&format_result(result, OutputFormat::Table, options)?);
+    Ok(())
+}
+
+#[cfg(feature = "browse")]
+mod tui {
+    use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+    use ratatui::layout::{Constraint, Layout};
+    use ratatui::style::{Style, Stylize};
+    use ratatui::text::Line;
+    use ratatui::widgets::{Cell, Row, Table, TableState};
+    use ratatui::{DefaultTerminal, Frame};
+    use std::io;
+
+    /// Wider cells are cut off; the full value is in the status line
+    const MAX_COLUMN_WIDTH: u16 = 40;
+
+    struct Browser {
+        headers: Vec<String>,
+        rows: Vec<Vec<String>>,
+        widths: Vec<u16>,
+        state: TableState,
+        /// Leading columns that stay visible while scrolling horizontally
+        frozen: usize,
+        /// Number of scrollable columns scrolled out to the left
+        column_offset: usize,
+        page_height: usize,
+    }
+
+    pub fn run(headers: Vec<String>, rows: Vec<Vec<String>>) -> io::Result<()> {
+        let mut terminal = ratatui::try_init()?;
+        let result = Browser::new(headers, rows).run(&mut terminal);
+        ratatui::restore();
+        result
+    }
+
+    impl Browser {
+        fn new(headers: Vec<String>, rows: Vec<Vec<String>>) -> Self {
+            let widths = headers.iter()
+                .enumerate()
+                .map(|(i, header)| {
+                    let widest = rows.iter()
+                        .filter_map(|row| row.get(i))
+                        .map(|cell| cell.chars().count())
+                        .chain(std::iter::once(header.chars().count()))
+                        .max()
+                        .unwrap_or(1);
+                    (widest as u16).clamp(1, MAX_COLUMN_WIDTH)
+                })
+                .collect();
+
+            Self {
+                headers,
+                rows,
+                widths,
+                state: TableState::default().with_selected(Some(0)),
+                frozen: 0,
+                column_offset: 0,
+                page_height: 1,
+            }
+        }
+
+        fn run(mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+            loop {
+                terminal.draw(|frame| self.draw(frame))?;
+
+                let Event::Key(key) = event::read()? else {
+                    continue;
+                };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+
+                let page = self.page_height as isize;
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Down | KeyCode::Char('j') => self.move_rows(1),
+                    KeyCode::Up | KeyCode::Char('k') => self.move_rows(-1),
+                    KeyCode::PageDown | KeyCode::Char(' ') => self.move_rows(page),
+                    KeyCode::PageUp => self.move_rows(-page),
+                    KeyCode::Home | KeyCode::Char('g') => self.state.select(Some(0)),
+                    KeyCode::End | KeyCode::Char('G') => self.state.select(Some(self.rows.len().saturating_sub(1))),
+                    KeyCode::Right | KeyCode::Char('l') => self.scroll_columns(1),
+                    KeyCode::Left | KeyCode::Char('h') => self.scroll_columns(-1),
+                    KeyCode::Char('f') => {
+                        self.frozen = (self.frozen + 1).min(self.headers.len().saturating_sub(1));
+                        self.scroll_columns(0);
+                    }
+                    KeyCode::Char('F') => self.frozen = self.frozen.saturating_sub(1),
+                    _ => {}
+                }
+            }
+        }
+
+        fn move_rows(&mut self, delta: isize) {
+            let current = self.state.selected().unwrap_or(0) as isize;
+            let last = self.rows.len().saturating_sub(1) as isize;
+            self.state.select(Some((current + delta).clamp(0, last) as usize));
+        }
+
+        fn scroll_columns(&mut self, delta: isize) {
+            let scrollable = self.headers.len().saturating_sub(self.frozen) as isize;
+            self.column_offset = (self.column_offset as isize + delta).clamp(0, (scrollable - 1).max(0)) as usize;
+        }
+
+        /// The frozen columns followed by as many scrollable columns as fit
+        fn visible_columns(&self, width: u16) -> Vec<usize> {
+            let mut columns: Vec<usize> = (0..self.frozen).collect();
+            let mut used: u16 = columns.iter().map(|&i| self.widths[i] + 1).sum();
+            for i in (self.frozen + self.column_offset)..self.headers.len() {
+                if used + self.widths[i] > width && columns.len() > self.frozen {
+                    break;
+                }
+                used = used.saturating_add(self.widths[i] + 1);
+                columns.push(i);
+            }
+            columns
+        }
+
+        fn draw(&mut self, frame: &mut Frame) {
+            let [table_area, value_area, status_area] = Layout::vertical([
+                Constraint::Min(2),
+                Constraint::Length(1),
+                Constraint::Length(1),
+            ])
+            .areas(frame.area());
+            self.page_height = usize::from(table_area.height.saturating_sub(1)).max(1);
+
+            let columns = self.visible_columns(table_area.width);
+            let frozen_style = Style::new().cyan();
+            let header = Row::new(columns.iter().map(|&i| {
+                let cell = Cell::from(self.headers[i].as_str());
+                if i < self.frozen { cell.style(frozen_style) } else { cell }
+            }))
+            .style(Style::new().bold());
+
+            // Multi-line values are shown on one line
+            let rows = self.rows.iter().map(|row| {
+                Row::new(columns.iter().map(|&i| {
+                    Cell::from(row.get(i).map(|cell| cell.replace('\n', " ")).unwrap_or_default())
+                }))
+            });
+
+            let table = Table::new(rows, columns.iter().map(|&i| Constraint::Length(self.widths[i])))
+                .header(header)
+                .row_highlight_style(Style::new().reversed());
+            frame.render_stateful_widget(table, table_area, &mut self.state);
+
+            // The first scrollable column of the selected row, in full
+            let selected = self.state.selected().unwrap_or(0);
+            let focused = columns.iter().copied().find(|&i| i >= self.frozen).or(columns.first().copied());
+            if let Some(column) = focused {
+                let value = self.rows.get(selected).and_then(|row| row.get(column)).cloned().unwrap_or_default();
+                frame.render_widget(Line::from(format!(" {}: {}", self.headers[column], value.replace('\n', " "))), value_area);
+            }
+
+            let status = format!(
+                " Row {}/{} | Columns {}-{} of {} | {} frozen | ↑↓ PgUp/PgDn Home/End move, ←→ scroll, f/F freeze, q quit",
+                selected + 1,
+                self.rows.len(),
+                columns.first().map(|i| i + 1).unwrap_or(0),
+                columns.last().map(|i| i + 1).unwrap_or(0),
+                self.headers.len(),
+                self.frozen
+            );
+            frame.render_widget(Line::from(status).reversed(), status_area);
+        }
+    }
+}
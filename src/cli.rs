@@ -1,9 +1,22 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
 use anyhow::Result;
-use crate::connection::ConnectionConfig;
+use colored::*;
+use crate::config;
+use crate::error::{CqlError, CqlResult};
+use crate::files;
+use crate::output;
+use crate::connection::{AuthProvider, ConnectionConfig};
+use crate::proxy::ProxyConfig;
 use crate::repl::Repl;
-use crate::executor::QueryExecutor;
-use rpassword;
+use crate::serve::serve;
+use crate::executor::{parse_consistency, QueryExecutor, DEFAULT_AUTO_PAGE_THRESHOLD};
+use crate::formatter::{json_row, DisplayTimeZone, FormatOptions, DETERMINISTIC_TABLE_WIDTH};
+use crate::schema;
+use crate::statement::{check_script, split_statements_by, table_reference, Separator};
+use crate::stats::StatsMode;
+use std::io::IsTerminal;
+use std::path::Path;
 
 #[derive(Parser, Debug)]
 #[command(name = "cqlrs")]
@@ -11,11 +24,13 @@ use rpassword;
 #[command(version = "0.1.0")]
 #[command(about = "A fully functional Cassandra CLI client", long_about = None)]
 pub struct Cli {
-    #[arg(short = 'H', long, default_value = "127.0.0.1")]
-    pub hosts: String,
+    /// Comma separated contact points [default: 127.0.0.1]
+    #[arg(short = 'H', long)]
+    pub hosts: Option<String>,
 
-    #[arg(short, long, default_value = "9042")]
-    pub port: u16,
+    /// [default: 9042]
+    #[arg(short, long)]
+    pub port: Option<u16>,
 
     #[arg(short, long)]
     pub username: Option<String>,
@@ -26,6 +41,20 @@ pub struct Cli {
     #[arg(long)]
     pub password: Option<String>,
 
+    /// Only split FILE into statements and report unterminated ones (missing ';', open quotes or brackets)
+    /// with line numbers. Does not connect; exits non-zero if problems are found.
+    #[arg(long, value_name = "FILE")]
+    pub check_syntax: Option<std::path::PathBuf>,
+
+    /// Read the password from the first line of this file (e.g. a mounted secret).
+    /// --password-prompt and --password take precedence.
+    #[arg(long, value_name = "PATH")]
+    pub password_file: Option<std::path::PathBuf>,
+
+    /// Authentication mechanism
+    #[arg(long, value_enum, default_value_t = AuthProvider::Plain)]
+    pub auth_provider: AuthProvider,
+
     #[arg(short, long)]
     pub keyspace: Option<String>,
 
@@ -35,21 +64,281 @@ pub struct Cli {
     #[arg(short, long)]
     pub file: Option<String>,
 
-    #[arg(short, long, default_value = "table")]
-    pub output_format: String,
+    /// Open the REPL even when stdin is not a terminal. Without --execute/--file the REPL
+    /// opens only on a terminal, otherwise the statements are read from stdin
+    #[arg(long, conflicts_with_all = ["execute", "file", "no_interactive"])]
+    pub interactive: bool,
+
+    /// Run the statements from stdin instead of opening the REPL, even on a terminal
+    #[arg(long)]
+    pub no_interactive: bool,
+
+    /// What ends a statement in --execute/--file: a single character or 'newline'.
+    /// Separators inside strings, quoted identifiers, comments and batches are ignored
+    #[arg(long, value_name = "CHAR|newline", default_value = ";")]
+    pub statement_separator: String,
+
+    /// Value for a `?` bind marker in --execute/--file statements, in order (repeatable).
+    /// Parsed as the marker's type, or with an explicit type: 'list<int>:[1, 2, 3]'
+    #[arg(long = "param", value_name = "VALUE")]
+    pub params: Vec<String>,
+
+    /// Re-run the --execute statements every N seconds until Ctrl-C
+    #[arg(long, value_name = "SECONDS", requires = "execute")]
+    pub watch: Option<f64>,
+
+    /// Show SELECT results in a full-screen browser (arrow keys/PgUp/PgDn to scroll, f to freeze columns, q to quit).
+    /// Needs the "browse" build feature and a terminal, otherwise the result is printed as a table.
+    #[arg(long)]
+    pub browse: bool,
+
+    /// Print the result columns (name, type, origin) of the --execute/--file statements instead of running them
+    #[arg(long)]
+    pub describe_result: bool,
+
+    /// Execute statements from --execute/--file concurrently, at most N at a time.
+    /// Statements must be independent of each other (no USE, no read-after-write).
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub parallel: usize,
 
+    /// table, json, yaml, cql-json, csv, inserts, html, sqlite (with --out) or template:<TEMPLATE> [default: table]
     #[arg(short, long)]
-    pub verbose: bool,
+    pub output_format: Option<String>,
+
+    /// SQLite database file --output-format sqlite writes the result to, as a new table per SELECT
+    /// (needs the "sqlite" build feature)
+    #[arg(long, value_name = "FILE")]
+    pub out: Option<String>,
+
+    /// Print one line per row from a template like '{id} -> {name}', where {column} is replaced
+    /// by the formatted value ({{ and }} for literal braces)
+    #[arg(long, value_name = "TEMPLATE", conflicts_with = "output_format")]
+    pub template: Option<String>,
+
+    /// Print rows as INSERT statements into KEYSPACE.TABLE instead of the table they were read from
+    #[arg(long, value_name = "KEYSPACE.TABLE", conflicts_with_all = ["output_format", "template"])]
+    pub insert_table: Option<String>,
+
+    /// Encode results for terminals that are not UTF-8, e.g. latin1, windows-1252 or shift_jis.
+    /// Unsupported characters become '?', table borders are drawn with ASCII instead.
+    #[arg(long, value_name = "ENCODING")]
+    pub output_encoding: Option<String>,
+
+    /// Stop writing results after this many bytes, ending with an "(output truncated at N bytes)" notice
+    #[arg(long, value_name = "N")]
+    pub limit_output_bytes: Option<u64>,
+
+    /// Only show rows where any cell contains this substring
+    #[arg(long)]
+    pub grep: Option<String>,
+
+    /// Stream SELECT results page by page once they exceed this many rows (0 disables paging)
+    #[arg(long, default_value_t = DEFAULT_AUTO_PAGE_THRESHOLD)]
+    pub auto_page_threshold: usize,
+
+    /// Time limit in seconds for each page of a SELECT, separate from the time the whole result takes
+    #[arg(long, value_name = "SECONDS")]
+    pub page_timeout: Option<f64>,
+
+    /// Warn about statements that take longer than this many milliseconds (string literals redacted)
+    #[arg(long, value_name = "MS")]
+    pub slow_query_threshold: Option<u64>,
+
+    /// Request a page again this many times after it hit --page-timeout
+    #[arg(long, value_name = "N", default_value_t = 0, requires = "page_timeout")]
+    pub page_retries: usize,
+
+    /// Remove the trailing ';' from statements before sending them (false keeps exactly one)
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    pub strip_trailing_semicolon: bool,
+
+    /// Rewrite unqualified table names after FROM/INTO/UPDATE to keyspace.table using the current keyspace.
+    /// Based on a simple token scan, so DDL and unusual statements are sent unchanged.
+    #[arg(long)]
+    pub qualify_tables: bool,
+
+    /// Color table cells by value type (NULL, booleans, numbers, UUIDs and timestamps)
+    #[arg(long)]
+    pub colorize_values: bool,
+
+    /// Print float and double values with N decimal places (JSON keeps full precision)
+    #[arg(long, value_name = "N")]
+    pub float_precision: Option<usize>,
+
+    /// Table width to fit instead of the terminal width (0 = unlimited) [default: terminal width, 120 if unknown]
+    #[arg(long, value_name = "N")]
+    pub max_width: Option<usize>,
+
+    /// Wrap table columns wider than N characters (0 = never wrap) [default: 50]
+    #[arg(long, value_name = "N")]
+    pub max_col_width: Option<usize>,
+
+    /// Repeat the table header on every page of results streamed page by page
+    #[arg(long)]
+    pub repeat_headers: bool,
+
+    /// Prefix table rows with a 1-based row number
+    #[arg(long)]
+    pub row_numbers: bool,
+
+    /// Show table rows as blocks of 'column | value' lines under '@ Row N', like cqlsh's EXPAND ON.
+    /// Values are not cut, which helps with wide tables
+    #[arg(long)]
+    pub expand: bool,
+
+    /// Append the size of blob and text values to their cells (table output only), to spot oversized values
+    #[arg(long)]
+    pub show_sizes: bool,
+
+    /// Show timestamps in this time zone, an IANA name like Europe/Berlin or 'local' [default: UTC]
+    #[arg(long, value_name = "TZ")]
+    pub timezone: Option<String>,
+
+    /// Show timestamps as milliseconds since the epoch instead of a date and time, for scripts
+    #[arg(long, conflicts_with = "timezone")]
+    pub timestamps_as_epoch: bool,
 
+    /// Show these columns first and in this order, e.g. 'name,id', whatever order the query selects them in
+    #[arg(long, value_name = "COLUMNS", value_delimiter = ',')]
+    pub order_columns: Vec<String>,
+
+    /// With --order-columns, leave out the columns it does not list instead of appending them
+    #[arg(long, requires = "order_columns")]
+    pub drop_unlisted_columns: bool,
+
+    /// Sort table output by this column and show a header row before each run of equal values.
+    /// Only changes the display; results streamed page by page are grouped per page.
+    #[arg(long, value_name = "COLUMN")]
+    pub group_by: Option<String>,
+
+    /// Print nulls per column and min/max/avg/sum of numeric columns after the result.
+    /// Table output only unless 'always' is given, other formats then get it on stderr.
+    #[arg(long, value_enum, value_name = "WHEN", num_args = 0..=1, default_missing_value = "table", default_value_t = StatsMode::Off)]
+    pub stats: StatsMode,
+
+    /// Rows per unlogged batch sent by the REPL's import command
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub copy_batch_size: usize,
+
+    /// Batches the REPL's import command sends concurrently
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub copy_max_batches_inflight: usize,
+
+    /// Seed REPL completion from a file written by \dump-completion-cache instead of reading the schema at startup
+    #[arg(long, value_name = "FILE")]
+    pub completion_cache: Option<std::path::PathBuf>,
+
+    /// Seconds the REPL may spend loading the schema for completion before it falls back to
+    /// keywords only; Ctrl-C skips it as well. 0 waits as long as it takes
+    #[arg(long, value_name = "SECONDS", default_value_t = 30.0)]
+    pub schema_refresh_timeout: f64,
+
+    /// Complete keyspaces, tables and columns also by substring or by their letters in order
+    /// (e.g. usr -> user_accounts); prefix matches are still listed first
+    #[arg(long)]
+    pub fuzzy_completion: bool,
+
+    /// In the REPL, warn before SELECT * on tables with more than N columns
+    #[arg(long, value_name = "N")]
+    pub warn_wide_tables: Option<usize>,
+
+    /// Disable colored output
     #[arg(long)]
+    pub no_color: bool,
+
+    /// Stable output for golden files: no colors, sorted maps and sets, no row count/timing
+    /// footers and a fixed table width of 120 (unless --max-width is given)
+    #[arg(long)]
+    pub deterministic_output: bool,
+
+    /// Write CSV with a UTF-8 BOM and CRLF line endings for Microsoft Excel
+    #[arg(long)]
+    pub csv_excel: bool,
+
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    #[arg(long, overrides_with = "no_ssl")]
     pub ssl: bool,
 
+    /// Connect without SSL/TLS even if the profile enables it
+    #[arg(long, overrides_with = "ssl", conflicts_with = "insecure")]
+    pub no_ssl: bool,
+
+    /// PEM file with the CA certificate(s) to trust [env: CQLRS_SSL_CA holds PEM content]
     #[arg(long)]
     pub ssl_ca_cert: Option<String>,
 
-    #[arg(long, default_value = "false")]
+    /// PEM file with the client certificate [env: CQLRS_SSL_CERT holds PEM content]
+    #[arg(long)]
+    pub ssl_client_cert: Option<String>,
+
+    /// PEM file with the client private key [env: CQLRS_SSL_KEY holds PEM content]
+    #[arg(long)]
+    pub ssl_client_key: Option<String>,
+
+    #[arg(long, default_value = "false", overrides_with = "no_ssl_verify")]
     pub ssl_verify: bool,
 
+    /// Do not verify the server certificate even if the profile sets ssl_verify
+    #[arg(long, overrides_with = "ssl_verify")]
+    pub no_ssl_verify: bool,
+
+    /// Enable SSL/TLS without certificate verification (development only)
+    #[arg(long, conflicts_with = "ssl_verify")]
+    pub insecure: bool,
+
+    /// Refuse statements with ALLOW FILTERING, e.g. on shared production clusters
+    #[arg(long)]
+    pub no_allow_filtering: bool,
+
+    /// Trace every statement and print the coordinator node and consistency level after the result.
+    /// Tracing writes to system_traces and adds a round trip, so use it for debugging only
+    #[arg(long)]
+    pub show_coordinator: bool,
+
+    /// In INSERT/UPDATE statements, replace a :now given as this column's value with the current
+    /// time: toTimestamp(now()) for timestamp, now() for timeuuid columns
+    #[arg(long, value_name = "COLUMN")]
+    pub now_column: Option<String>,
+
+    /// Follow well-known server errors (ALLOW FILTERING needed, batch too large, ...) with a hint on how to fix them
+    #[arg(long)]
+    pub explain_errors: bool,
+
+    /// After UPDATE ... SET c = c + n on a counter, read the counter back and print its new value
+    #[arg(long)]
+    pub echo_counter: bool,
+
+    /// Consistency level for schema queries (completion, describe), e.g. ONE or LOCAL_ONE,
+    /// independent of the consistency used for data [default: same as data]
+    #[arg(long, value_name = "LEVEL")]
+    pub schema_consistency: Option<String>,
+
+    /// Retry the initial connection this many times, e.g. while a container is still starting
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub connect_retries: usize,
+
+    /// Seconds to wait before the first connection retry, doubled after each further attempt (at most 30)
+    #[arg(long, value_name = "SECONDS", default_value_t = 1.0, requires = "connect_retries")]
+    pub connect_retry_interval: f64,
+
+    /// Connect to the cluster through a proxy: socks5://[user:password@]host:port or http://[user:password@]host:port (HTTP CONNECT)
+    #[arg(long, value_name = "URL")]
+    pub proxy: Option<String>,
+
+    /// Exit right after connecting if system.local reports an older release_version, e.g. 4.1
+    #[arg(long, value_name = "X.Y")]
+    pub min_server_version: Option<String>,
+
+    /// Suppress informational notices and warnings
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Use the [profile.<name>] section of ~/.cqlrs.toml
+    #[arg(long)]
+    pub profile: Option<String>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -60,6 +349,28 @@ pub enum Commands {
     Describe {
         #[arg(required = true)]
         target: Vec<String>,
+        /// Print the schema as a JSON document (replication, columns with types and kinds,
+        /// primary key, indexes, table options) instead of the system table rows
+        #[arg(long)]
+        json: bool,
+    },
+    /// Run a query repeatedly and report latency statistics
+    Bench {
+        query: String,
+        /// Number of measured executions
+        #[arg(short = 'n', long, default_value_t = 100)]
+        iterations: usize,
+        /// Unmeasured executions before the measurement starts
+        #[arg(long, default_value_t = 10)]
+        warmup: usize,
+    },
+    /// Keep one session open and answer newline-delimited JSON requests like
+    /// {"id": 1, "query": "SELECT ...", "format": "json"} from stdin with one JSON line each on stdout
+    Serve,
+    /// Print a shell completion script to stdout
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
     },
 }
 
@@ -68,9 +379,38 @@ impl Cli {
         if self.verbose {
             std::env::set_var("RUST_LOG", "debug");
         }
+
+        if self.no_color || self.deterministic_output {
+            colored::control::set_override(false);
+        }
+
+        if let Some(encoding) = &self.output_encoding {
+            output::set_encoding(encoding)?;
+        }
+        if let Some(bytes) = self.limit_output_bytes {
+            output::set_limit(bytes);
+        }
+
+        if let Some(Commands::Completions { shell }) = &self.command {
+            Self::print_completions(*shell);
+            return Ok(());
+        }
+
+        if let Some(path) = &self.check_syntax {
+            return Self::check_syntax(path);
+        }
         
+        let profile = config::load_profile(self.profile.as_deref())?;
+        let username = self.username.clone().or(profile.username);
+        let output_format = self.template.as_ref()
+            .map(|template| format!("template:{}", template))
+            .or_else(|| self.insert_table.as_ref().map(|table| format!("inserts:{}", table)))
+            .or_else(|| self.output_format.clone())
+            .or(profile.output_format)
+            .unwrap_or_else(|| "table".to_string());
+
         let password = if self.password_prompt {
-            if self.username.is_none() {
+            if username.is_none() {
                 eprintln!("Warning: Password prompt specified but no username provided");
                 None
             } else {
@@ -80,41 +420,166 @@ impl Cli {
                 let pwd = rpassword::read_password()?;
                 Some(pwd)
             }
+        } else if let Some(password) = &self.password {
+            Some(password.clone())
+        } else if let Some(path) = &self.password_file {
+            Some(read_password_file(path, self.quiet)?)
         } else {
-            self.password.clone()
+            profile.password
         };
 
+        if self.insecure && !self.quiet {
+            eprintln!(
+                "{} {}",
+                "WARNING:".red().bold(),
+                "--insecure is set: SSL/TLS is enabled but server certificates are NOT verified.".yellow().bold()
+            );
+            eprintln!(
+                "{}",
+                "         The connection is open to man-in-the-middle attacks. Do not use this in production.".yellow()
+            );
+        }
+
+        let hosts = self.hosts.clone()
+            .or(profile.hosts)
+            .unwrap_or_else(|| "127.0.0.1".to_string());
+
         let config = ConnectionConfig {
-            hosts: self.hosts.split(',').map(|s| s.trim().to_string()).collect(),
-            port: self.port,
-            username: self.username.clone(),
+            hosts: hosts.split(',').map(|s| s.trim().to_string()).collect(),
+            port: self.port.or(profile.port).unwrap_or(9042),
+            username,
             password,
-            keyspace: self.keyspace.clone(),
-            ssl_enabled: self.ssl,
-            ssl_ca_cert: self.ssl_ca_cert.clone(),
-            ssl_verify: self.ssl_verify,
+            auth_provider: self.auth_provider,
+            keyspace: self.keyspace.clone().or(profile.keyspace),
+            ssl_enabled: switch(self.ssl, self.no_ssl).or(profile.ssl).unwrap_or(false) || self.insecure,
+            ssl_ca_cert: self.ssl_ca_cert.clone().or(profile.ssl_ca_cert),
+            ssl_client_cert: self.ssl_client_cert.clone().or(profile.ssl_client_cert),
+            ssl_client_key: self.ssl_client_key.clone().or(profile.ssl_client_key),
+            ssl_verify: switch(self.ssl_verify, self.no_ssl_verify).or(profile.ssl_verify).unwrap_or(false) && !self.insecure,
+            connect_retries: self.connect_retries,
+            connect_retry_interval: std::time::Duration::try_from_secs_f64(self.connect_retry_interval)
+                .map_err(|_| anyhow::anyhow!("--connect-retry-interval needs a non-negative number of seconds"))?,
+            proxy: self.proxy.as_deref().map(ProxyConfig::parse).transpose()?,
+        };
+
+        let format_options = self.format_options()?;
+        let separator = Separator::parse(&self.statement_separator).ok_or_else(|| {
+            CqlError::ConfigError(format!(
+                "--statement-separator '{}' must be 'newline' or a single character other than quotes, $, - and /",
+                self.statement_separator
+            ))
+        })?;
+        let sqlite_out = match (output_format.eq_ignore_ascii_case("sqlite"), &self.out) {
+            (true, Some(path)) => Some(Path::new(path)),
+            (true, None) => return Err(CqlError::ConfigError("--output-format sqlite needs --out <FILE>".to_string()).into()),
+            (false, Some(_)) => return Err(CqlError::ConfigError("--out is only used with --output-format sqlite".to_string()).into()),
+            (false, None) => None,
+        };
+        let min_server_version = match &self.min_server_version {
+            Some(version) => Some(parse_version(version).ok_or_else(|| {
+                CqlError::ConfigError(format!("--min-server-version '{}' is not a version like 4.1 or 4.0.11", version))
+            })?),
+            None => None,
         };
 
         let mut executor = QueryExecutor::new(config).await?;
+        if let Some(warning) = executor.connection().degraded_warning().filter(|_| !self.quiet) {
+            eprintln!("{} {}", "Warning:".yellow().bold(), warning);
+        }
+        if let Some(required) = &min_server_version {
+            let detected = executor.connection().server_version().await?;
+            if parse_version(&detected).is_none_or(|version| version_less(&version, required)) {
+                return Err(CqlError::ConnectionError(format!(
+                    "Server version {} is older than the required {}",
+                    detected,
+                    self.min_server_version.as_deref().unwrap_or_default()
+                )).into());
+            }
+        }
+        executor.set_auto_page_threshold(self.auto_page_threshold);
+        executor.set_strip_trailing_semicolon(self.strip_trailing_semicolon);
+        executor.set_params(self.params.clone());
+        executor.set_qualify_tables(self.qualify_tables);
+        if let Some(seconds) = self.page_timeout {
+            let timeout = std::time::Duration::try_from_secs_f64(seconds)
+                .ok()
+                .filter(|d| !d.is_zero())
+                .ok_or_else(|| anyhow::anyhow!("--page-timeout needs a positive number of seconds"))?;
+            executor.set_page_timeout(Some(timeout));
+            executor.set_page_retries(self.page_retries);
+        }
+        if let Some(level) = &self.schema_consistency {
+            let consistency = parse_consistency(level)
+                .ok_or_else(|| CqlError::ConfigError(format!("Unknown consistency level '{}'", level)))?;
+            executor.set_schema_consistency(Some(consistency));
+        }
+        executor.set_echo_counter(self.echo_counter);
+        executor.set_explain_errors(self.explain_errors);
+        executor.set_now_column(self.now_column.clone());
+        executor.set_show_coordinator(self.show_coordinator);
+        executor.set_allow_filtering(!self.no_allow_filtering);
+        executor.set_slow_query_threshold(self.slow_query_threshold.map(std::time::Duration::from_millis));
+
+        // Piped input runs as a script unless the REPL is asked for explicitly
+        let interactive = self.execute.is_none() && self.file.is_none() && match &self.command {
+            Some(Commands::Repl) => !self.no_interactive,
+            None => self.interactive || (!self.no_interactive && std::io::stdin().is_terminal()),
+            _ => false,
+        };
 
         match &self.command {
-            Some(Commands::Repl) | None if self.execute.is_none() && self.file.is_none() => {
-                let mut repl = Repl::new(executor, self.output_format.clone());
+            Some(Commands::Repl) | None if interactive => {
+                let mut repl = Repl::new(executor, output_format, format_options);
+                repl.set_completion_cache(self.completion_cache.clone());
+                repl.set_import_batching(self.copy_batch_size, self.copy_max_batches_inflight);
+                repl.set_fuzzy_completion(self.fuzzy_completion);
+                let schema_refresh_timeout = std::time::Duration::try_from_secs_f64(self.schema_refresh_timeout)
+                    .map_err(|_| anyhow::anyhow!("--schema-refresh-timeout needs a non-negative number of seconds"))?;
+                repl.set_schema_refresh_timeout(Some(schema_refresh_timeout).filter(|timeout| !timeout.is_zero()));
+                if !self.quiet {
+                    repl.set_wide_table_threshold(self.warn_wide_tables);
+                }
                 repl.run().await?;
             }
-            Some(Commands::Describe { target }) => {
-                self.handle_describe(&mut executor, target).await?;
+            Some(Commands::Describe { target, json: true }) => {
+                self.handle_describe_json(&executor, target).await?;
+            }
+            Some(Commands::Describe { target, json: false }) => {
+                self.handle_describe(&mut executor, target, &output_format, &format_options).await?;
+            }
+            Some(Commands::Serve) => {
+                let format = self.output_format.as_deref().unwrap_or("json");
+                serve(&executor, format, &format_options).await?;
+            }
+            Some(Commands::Bench { query, iterations, warmup }) => {
+                let stats = executor.benchmark(query, *iterations, *warmup).await?;
+                output::print(&stats.render());
             }
             _ => {
-                if let Some(query) = &self.execute {
-                    executor.execute_and_print(query, &self.output_format).await?;
-                } else if let Some(file_path) = &self.file {
-                    let content = std::fs::read_to_string(file_path)?;
-                    for query in content.split(';') {
-                        let query = query.trim();
-                        if !query.is_empty() {
-                            executor.execute_and_print(query, &self.output_format).await?;
-                        }
+                let statements = self.script_statements(separator)?;
+                if self.describe_result {
+                    for query in statements {
+                        executor.describe_result(&query, &output_format).await?;
+                    }
+                } else if let Some(seconds) = self.watch {
+                    let interval = std::time::Duration::try_from_secs_f64(seconds)
+                        .ok()
+                        .filter(|d| !d.is_zero())
+                        .ok_or_else(|| anyhow::anyhow!("--watch needs a positive number of seconds"))?;
+                    executor.watch(&statements, interval, &output_format, &format_options).await?;
+                } else if self.browse {
+                    for query in statements {
+                        executor.browse(&query, &output_format, &format_options).await?;
+                    }
+                } else if let Some(path) = sqlite_out {
+                    for query in statements {
+                        executor.export_sqlite(&query, path, &format_options).await?;
+                    }
+                } else if self.parallel > 1 {
+                    executor.execute_parallel(&statements, self.parallel, &format_options).await?;
+                } else {
+                    for query in statements {
+                        executor.execute_and_print(&query, &output_format, &format_options).await?;
                     }
                 }
             }
@@ -123,7 +588,86 @@ impl Cli {
         Ok(())
     }
 
-    async fn handle_describe(&self, executor: &mut QueryExecutor, target: &[String]) -> Result<()> {
+    /// The statements of `--execute`, `--file` or piped input, in order
+    fn script_statements(&self, separator: Separator) -> Result<Vec<String>> {
+        let content = if let Some(query) = &self.execute {
+            query.clone()
+        } else if let Some(file_path) = &self.file {
+            files::read_script(file_path)?
+        } else if matches!(self.command, Some(Commands::Repl) | None) {
+            // Piped input, e.g. `cqlrs < script.cql`
+            files::normalize_line_endings(std::io::read_to_string(std::io::stdin())?)
+        } else {
+            String::new()
+        };
+        Ok(split_statements_by(&content, separator))
+    }
+
+    /// `--check-syntax`: splits the script offline and lists problems as
+    /// `file:line: message`, failing if there are any
+    fn check_syntax(path: &Path) -> Result<()> {
+        let content = files::read_script(path)?;
+        let (statements, problems) = check_script(&content);
+
+        for problem in &problems {
+            println!("{}:{}: {}", path.display(), problem.line, problem.message.red());
+        }
+        println!(
+            "{}: {} statement(s), {} problem(s)",
+            path.display(),
+            statements.to_string().cyan(),
+            if problems.is_empty() { "0".green() } else { problems.len().to_string().red() }
+        );
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(CqlError::InvalidQuery(format!("{} has {} syntax problem(s)", path.display(), problems.len())).into())
+        }
+    }
+
+    fn print_completions(shell: Shell) {
+        let mut command = Cli::command();
+        let name = command.get_name().to_string();
+        clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+    }
+
+    fn format_options(&self) -> Result<FormatOptions> {
+        let grep = match &self.grep {
+            Some(needle) => Some(regex::Regex::new(&regex::escape(needle))?),
+            None => None,
+        };
+
+        Ok(FormatOptions {
+            grep,
+            csv_excel: self.csv_excel,
+            colorize_values: self.colorize_values,
+            row_numbers: self.row_numbers,
+            float_precision: self.float_precision,
+            max_width: self.max_width.or(self.deterministic_output.then_some(DETERMINISTIC_TABLE_WIDTH)),
+            max_col_width: self.max_col_width,
+            repeat_headers: self.repeat_headers,
+            sort_collections: self.deterministic_output,
+            no_footer: self.deterministic_output,
+            stats: self.stats,
+            column_order: self.order_columns.iter().map(|name| name.trim().to_string()).collect(),
+            drop_unlisted_columns: self.drop_unlisted_columns,
+            timezone: self.timezone.as_deref().map(DisplayTimeZone::parse).transpose()?,
+            epoch_timestamps: self.timestamps_as_epoch,
+            group_by: self.group_by.clone(),
+            expanded: self.expand,
+            show_sizes: self.show_sizes,
+            quiet: self.quiet,
+        })
+    }
+
+    async fn handle_describe(
+        &self,
+        executor: &mut QueryExecutor,
+        target: &[String],
+        output_format: &str,
+        format_options: &FormatOptions,
+    ) -> Result<()> {
         let query = match target.first().map(|s| s.as_str()) {
             Some("cluster") => {
                 "SELECT * FROM system.local".to_string()
@@ -135,7 +679,16 @@ impl Cli {
                 format!("SELECT * FROM system_schema.keyspaces WHERE keyspace_name = '{}'", target[1])
             }
             Some("table") if target.len() > 1 => {
-                format!("SELECT * FROM system_schema.columns WHERE table_name = '{}'", target[1])
+                let (keyspace, table) = table_reference(&target[1])
+                    .ok_or_else(|| CqlError::InvalidQuery(format!("Invalid table name '{}'", target[1])))?;
+                // Unqualified names are looked up in the session's keyspace, if there is one
+                match keyspace.or_else(|| executor.current_keyspace()) {
+                    Some(keyspace) => format!(
+                        "SELECT * FROM system_schema.columns WHERE keyspace_name = '{}' AND table_name = '{}'",
+                        keyspace, table
+                    ),
+                    None => format!("SELECT * FROM system_schema.columns WHERE table_name = '{}'", table),
+                }
             }
             Some("tables") if target.len() > 1 => {
                 format!("SELECT table_name FROM system_schema.tables WHERE keyspace_name = '{}'", target[1])
@@ -146,7 +699,158 @@ impl Cli {
             }
         };
 
-        executor.execute_and_print(&query, &self.output_format).await?;
+        executor.execute_and_print(&query, output_format, format_options).await?;
+        Ok(())
+    }
+
+    /// `describe ... --json`: the schema as structured documents, built from
+    /// `system_schema` rather than printing its rows
+    async fn handle_describe_json(&self, executor: &QueryExecutor, target: &[String]) -> Result<()> {
+        let document = match (target.first().map(|s| s.as_str()), target.get(1)) {
+            (Some("cluster"), _) => {
+                let result = executor.query_schema(
+                    "SELECT cluster_name, partitioner, release_version FROM system.local",
+                    (),
+                ).await?;
+                let row = result.rows.iter().flatten().next()
+                    .ok_or_else(|| CqlError::QueryError("system.local returned no row".to_string()))?;
+                json_row(&result.col_specs, row, &FormatOptions::default())
+            }
+            (Some("keyspaces"), _) => {
+                let result = executor.query_schema("SELECT keyspace_name FROM system_schema.keyspaces", ()).await?;
+                let mut names: Vec<String> = result.rows_typed::<(String,)>()
+                    .map_err(|e| CqlError::QueryError(format!("Failed to read the keyspaces: {}", e)))?
+                    .filter_map(|row| row.ok().map(|(name,)| name))
+                    .collect();
+                names.sort();
+                let mut keyspaces = Vec::with_capacity(names.len());
+                for name in names {
+                    keyspaces.push(schema::load_keyspace(executor, &name).await?);
+                }
+                serde_json::to_value(keyspaces)?
+            }
+            (Some("keyspace"), Some(keyspace)) => serde_json::to_value(schema::load_keyspace(executor, keyspace).await?)?,
+            (Some("tables"), Some(keyspace)) => serde_json::to_value(schema::load_keyspace(executor, keyspace).await?.tables)?,
+            (Some("table"), Some(name)) => {
+                let (keyspace, table) = table_reference(name)
+                    .ok_or_else(|| CqlError::InvalidQuery(format!("Invalid table name '{}'", name)))?;
+                let keyspace = keyspace.or_else(|| executor.current_keyspace()).ok_or_else(|| {
+                    CqlError::ConfigError(format!("describe table {} --json needs KEYSPACE.TABLE or --keyspace", name))
+                })?;
+                serde_json::to_value(schema::load_table(executor, &keyspace, &table).await?)?
+            }
+            _ => {
+                println!("Usage: describe [cluster|keyspaces|keyspace NAME|table [KEYSPACE.]NAME|tables KEYSPACE] --json");
+                return Ok(());
+            }
+        };
+
+        output::println(&serde_json::to_string_pretty(&document)?);
         Ok(())
     }
 }
+
+/// The leading numeric components of a release version: `4.0.11` -> [4, 0, 11],
+/// `5.0-beta1` -> [5, 0]. `None` if it does not start with a number.
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    let numeric = version.trim().split(|c: char| !c.is_ascii_digit() && c != '.').next()?;
+    let parts: Vec<u64> = numeric.split('.')
+        .take_while(|part| !part.is_empty())
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    (!parts.is_empty()).then_some(parts)
+}
+
+/// Compares component by component, missing components count as 0, so 4.1 == 4.1.0
+fn version_less(version: &[u64], required: &[u64]) -> bool {
+    let len = version.len().max(required.len());
+    let component = |parts: &[u64], i: usize| parts.get(i).copied().unwrap_or(0);
+    (0..len)
+        .map(|i| (component(version, i), component(required, i)))
+        .find(|(a, b)| a != b)
+        .is_some_and(|(a, b)| a < b)
+}
+
+/// The setting a `--x`/`--no-x` flag pair gives, `None` if neither was used
+/// so the profile decides
+fn switch(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+/// The first line of `path`, without its line ending. Warns when other users
+/// may read the file.
+fn read_password_file(path: &Path, quiet: bool) -> CqlResult<String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| CqlError::ConfigError(format!("Failed to read password file {}: {}", path.display(), e)))?;
+
+    #[cfg(unix)]
+    if !quiet {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path)?.permissions().mode();
+        if mode & 0o004 != 0 {
+            eprintln!(
+                "{} password file {} is readable by all users (mode {:o}), consider chmod 600",
+                "Warning:".yellow().bold(),
+                path.display(),
+                mode & 0o777
+            );
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = quiet;
+
+    let line = content.split('\n').next().unwrap_or("");
+    let password = line.strip_suffix('\r').unwrap_or(line);
+    if password.is_empty() {
+        return Err(CqlError::ConfigError(format!("Password file {} is empty", path.display())));
+    }
+    Ok(password.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn execute_statements(args: &[&str]) -> Vec<String> {
+        let cli = Cli::try_parse_from([&["cqlrs"], args].concat()).unwrap();
+        let separator = Separator::parse(&cli.statement_separator).unwrap();
+        cli.script_statements(separator).unwrap()
+    }
+
+    #[test]
+    fn execute_runs_use_and_select_as_separate_statements() {
+        assert_eq!(execute_statements(&["-e", "USE ks; SELECT * FROM t;"]), ["USE ks", "SELECT * FROM t"]);
+        assert_eq!(execute_statements(&["-e", "USE ks; SELECT * FROM t"]), ["USE ks", "SELECT * FROM t"]);
+        assert_eq!(
+            execute_statements(&["--execute", "USE ks;\nSELECT * FROM t WHERE name = 'a;b'; -- done;"]),
+            ["USE ks", "SELECT * FROM t WHERE name = 'a;b'"],
+        );
+        assert_eq!(execute_statements(&["-e", "SELECT * FROM t"]), ["SELECT * FROM t"]);
+    }
+
+    #[test]
+    fn execute_uses_the_statement_separator() {
+        assert_eq!(
+            execute_statements(&["--statement-separator", "newline", "-e", "USE ks\nSELECT * FROM t"]),
+            ["USE ks", "SELECT * FROM t"],
+        );
+    }
+
+    #[test]
+    fn no_ssl_flags_override_the_profile() {
+        let ssl = |args: &[&str]| {
+            let cli = Cli::try_parse_from([&["cqlrs"], args].concat()).unwrap();
+            (switch(cli.ssl, cli.no_ssl), switch(cli.ssl_verify, cli.no_ssl_verify))
+        };
+        assert_eq!(ssl(&[]), (None, None));
+        assert_eq!(ssl(&["--ssl", "--ssl-verify"]), (Some(true), Some(true)));
+        assert_eq!(ssl(&["--no-ssl", "--no-ssl-verify"]), (Some(false), Some(false)));
+        assert_eq!(ssl(&["--ssl", "--no-ssl"]), (Some(false), None));
+        assert!(Cli::try_parse_from(["cqlrs", "--no-ssl", "--insecure"]).is_err());
+        assert_eq!(switch(false, true).or(Some(true)), Some(false));
+    }
+}
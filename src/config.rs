@@ -0,0 +1,98 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use crate::error::{CqlError, CqlResult};
+
+/// Connection settings from `~/.cqlrs.toml`. Every field is optional so
+/// that command line flags can override whatever the file provides.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Profile {
+    pub hosts: Option<String>,
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub keyspace: Option<String>,
+    pub ssl: Option<bool>,
+    pub ssl_ca_cert: Option<String>,
+    pub ssl_client_cert: Option<String>,
+    pub ssl_client_key: Option<String>,
+    pub ssl_verify: Option<bool>,
+    pub output_format: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ConfigFile {
+    #[serde(flatten)]
+    defaults: Profile,
+    #[serde(default)]
+    profile: HashMap<String, Profile>,
+}
+
+impl Profile {
+    fn or(self, base: Profile) -> Profile {
+        Profile {
+            hosts: self.hosts.or(base.hosts),
+            port: self.port.or(base.port),
+            username: self.username.or(base.username),
+            password: self.password.or(base.password),
+            keyspace: self.keyspace.or(base.keyspace),
+            ssl: self.ssl.or(base.ssl),
+            ssl_ca_cert: self.ssl_ca_cert.or(base.ssl_ca_cert),
+            ssl_client_cert: self.ssl_client_cert.or(base.ssl_client_cert),
+            ssl_client_key: self.ssl_client_key.or(base.ssl_client_key),
+            ssl_verify: self.ssl_verify.or(base.ssl_verify),
+            output_format: self.output_format.or(base.output_format),
+        }
+    }
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|mut p| {
+        p.push(".cqlrs.toml");
+        p
+    })
+}
+
+/// Loads the top-level settings of the config file, overlaid with the
+/// `[profile.<name>]` section when a profile is requested.
+pub fn load_profile(name: Option<&str>) -> CqlResult<Profile> {
+    let path = match config_path() {
+        Some(path) if path.exists() => path,
+        _ => {
+            return match name {
+                Some(name) => Err(CqlError::ConfigError(format!(
+                    "Profile '{}' not found: no config file at ~/.cqlrs.toml",
+                    name
+                ))),
+                None => Ok(Profile::default()),
+            };
+        }
+    };
+
+    let content = std::fs::read_to_string(&path)?;
+    let file: ConfigFile = toml::from_str(&content)
+        .map_err(|e| CqlError::ConfigError(format!("Failed to parse {}: {}", path.display(), e)))?;
+
+    let name = match name {
+        Some(name) => name,
+        None => return Ok(file.defaults),
+    };
+
+    match file.profile.get(name) {
+        Some(profile) => Ok(profile.clone().or(file.defaults)),
+        None => {
+            let mut available: Vec<&String> = file.profile.keys().collect();
+            available.sort();
+            Err(CqlError::ConfigError(format!(
+                "Profile '{}' not found in {} (available: {})",
+                name,
+                path.display(),
+                if available.is_empty() {
+                    "none".to_string()
+                } else {
+                    available.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", ")
+                }
+            )))
+        }
+    }
+}
@@ -1,7 +1,40 @@
 use scylla::{Session, SessionBuilder};
-use crate::error::{CqlError, CqlResult};
-use tracing::info;
+use crate::proxy::{open_tunnel, ProxyConfig, ProxyTranslator};
+use crate::error::{is_session_authentication_failure, is_unreachable, CqlError, CqlResult};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use openssl::pkey::PKey;
 use openssl::ssl::{SslContext, SslMethod, SslVerifyMode};
+use openssl::x509::X509;
+
+/// Environment variables holding PEM content, used when the matching file
+/// option is not given (e.g. secrets injected into a container)
+pub const SSL_CA_ENV: &str = "CQLRS_SSL_CA";
+pub const SSL_CERT_ENV: &str = "CQLRS_SSL_CERT";
+pub const SSL_KEY_ENV: &str = "CQLRS_SSL_KEY";
+
+/// The wait between connection attempts doubles up to this
+const MAX_CONNECT_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How the client authenticates against the cluster
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum AuthProvider {
+    /// PasswordAuthenticator with --username/--password
+    #[default]
+    Plain,
+    /// No authentication, credentials are ignored
+    None,
+}
+
+impl std::fmt::Display for AuthProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthProvider::Plain => write!(f, "plain"),
+            AuthProvider::None => write!(f, "none"),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct ConnectionConfig {
@@ -9,10 +42,55 @@ pub struct ConnectionConfig {
     pub port: u16,
     pub username: Option<String>,
     pub password: Option<String>,
+    pub auth_provider: AuthProvider,
     pub keyspace: Option<String>,
     pub ssl_enabled: bool,
     pub ssl_ca_cert: Option<String>,
+    pub ssl_client_cert: Option<String>,
+    pub ssl_client_key: Option<String>,
     pub ssl_verify: bool,
+    /// Further attempts when the initial connection fails, e.g. because the
+    /// cluster is still starting
+    pub connect_retries: usize,
+    /// Wait before the first retry, doubled after every further attempt
+    pub connect_retry_interval: Duration,
+    /// SOCKS5 or HTTP CONNECT proxy all connections to the cluster go through
+    pub proxy: Option<ProxyConfig>,
+}
+
+/// PEM encoded TLS material for the SSL context
+struct TlsMaterials {
+    ca_cert: Option<Vec<u8>>,
+    client_cert: Option<Vec<u8>>,
+    client_key: Option<Vec<u8>>,
+}
+
+impl TlsMaterials {
+    fn load(config: &ConnectionConfig) -> CqlResult<Self> {
+        Ok(Self {
+            ca_cert: read_pem(config.ssl_ca_cert.as_deref(), SSL_CA_ENV)?,
+            client_cert: read_pem(config.ssl_client_cert.as_deref(), SSL_CERT_ENV)?,
+            client_key: read_pem(config.ssl_client_key.as_deref(), SSL_KEY_ENV)?,
+        })
+    }
+}
+
+/// Reads PEM data from `path`, or from the `env_var` environment variable
+/// (which holds the PEM text itself) when no path is given
+fn read_pem(path: Option<&str>, env_var: &str) -> CqlResult<Option<Vec<u8>>> {
+    if let Some(path) = path {
+        return std::fs::read(path)
+            .map(Some)
+            .map_err(|e| CqlError::ConfigError(format!("Failed to read {}: {}", path, e)));
+    }
+
+    match std::env::var(env_var) {
+        Ok(pem) if !pem.trim().is_empty() => {
+            info!("Using PEM data from {}", env_var);
+            Ok(Some(pem.into_bytes()))
+        }
+        _ => Ok(None),
+    }
 }
 
 pub struct ConnectionManager {
@@ -21,9 +99,54 @@ pub struct ConnectionManager {
 }
 
 impl ConnectionManager {
-    fn create_ssl_context(verify_cert: bool) -> CqlResult<SslContext> {
+    fn create_ssl_context(verify_cert: bool, materials: &TlsMaterials) -> CqlResult<SslContext> {
+        let ssl_error = |what: &str, e: openssl::error::ErrorStack| {
+            CqlError::ConnectionError(format!("Failed to {}: {}", what, e))
+        };
+
         let mut ssl_builder = SslContext::builder(SslMethod::tls())
-            .map_err(|e| CqlError::ConnectionError(format!("Failed to create SSL context: {}", e)))?;
+            .map_err(|e| ssl_error("create SSL context", e))?;
+
+        if let Some(ca_cert) = &materials.ca_cert {
+            for cert in X509::stack_from_pem(ca_cert).map_err(|e| ssl_error("parse CA certificate", e))? {
+                ssl_builder.cert_store_mut()
+                    .add_cert(cert)
+                    .map_err(|e| ssl_error("add CA certificate", e))?;
+            }
+            info!("Loaded custom CA certificate(s)");
+        }
+
+        match (&materials.client_cert, &materials.client_key) {
+            (Some(cert), Some(key)) => {
+                // The first certificate is the client's own, any further
+                // ones are intermediates sent along with it
+                let mut chain = X509::stack_from_pem(cert)
+                    .map_err(|e| ssl_error("parse client certificate", e))?
+                    .into_iter();
+                let leaf = chain.next().ok_or_else(|| {
+                    CqlError::ConfigError("Client certificate contains no PEM certificate".to_string())
+                })?;
+                ssl_builder.set_certificate(&leaf).map_err(|e| ssl_error("use client certificate", e))?;
+                for intermediate in chain {
+                    ssl_builder.add_extra_chain_cert(intermediate)
+                        .map_err(|e| ssl_error("add client certificate chain", e))?;
+                }
+
+                let key = PKey::private_key_from_pem(key).map_err(|e| ssl_error("parse client key", e))?;
+                ssl_builder.set_private_key(&key).map_err(|e| ssl_error("use client key", e))?;
+                ssl_builder.check_private_key()
+                    .map_err(|e| ssl_error("match client key to certificate", e))?;
+                info!("Using client certificate authentication");
+            }
+            (None, None) => {}
+            _ => {
+                return Err(CqlError::ConfigError(format!(
+                    "A client certificate needs both a certificate and a key \
+                    (--ssl-client-cert/{} and --ssl-client-key/{})",
+                    SSL_CERT_ENV, SSL_KEY_ENV
+                )));
+            }
+        }
 
         if verify_cert {
             info!("SSL certificate verification enabled (SslVerifyMode::PEER)");
@@ -36,10 +159,46 @@ impl ConnectionManager {
         Ok(ssl_builder.build())
     }
 
+    fn configure_auth(builder: SessionBuilder, config: &ConnectionConfig) -> SessionBuilder {
+        match config.auth_provider {
+            AuthProvider::None => {
+                if config.username.is_some() {
+                    info!("Authentication disabled, ignoring provided credentials");
+                }
+                builder
+            }
+            AuthProvider::Plain => match (&config.username, &config.password) {
+                (Some(username), Some(password)) => {
+                    info!("Using authentication with username: {}", username);
+                    builder.user(username, password)
+                }
+                _ => builder,
+            },
+        }
+    }
+
+    /// Replaces every contact point with the local end of a tunnel through
+    /// the proxy. Hostnames go to the proxy as they are for `socks5h://` and
+    /// `http://`, and are resolved locally for `socks5://`.
+    async fn tunnel_contact_points(proxy: &Arc<ProxyConfig>, contact_points: &[String], default_port: u16) -> CqlResult<Vec<String>> {
+        let mut tunneled = Vec::with_capacity(contact_points.len());
+        for contact_point in contact_points {
+            let (host, port) = match contact_point.rsplit_once(':') {
+                Some((host, port)) => (host, port.parse().unwrap_or(default_port)),
+                None => (contact_point.as_str(), default_port),
+            };
+            let host = host.trim_start_matches('[').trim_end_matches(']').to_string();
+            let local = open_tunnel(proxy.clone(), host, port).await
+                .map_err(|e| CqlError::ConnectionError(format!("Could not open a tunnel through the proxy: {}", e)))?;
+            tunneled.push(local.to_string());
+        }
+        Ok(tunneled)
+    }
+
     pub async fn connect(config: ConnectionConfig) -> CqlResult<Self> {
         info!("Connecting to Cassandra cluster at {:?}:{}", config.hosts, config.port);
         
-        let contact_points: Vec<String> = config.hosts.iter()
+        let mut contact_points: Vec<String> = config.hosts.iter()
             .map(|host| {
                 if host.contains(':') {
                     host.clone()
@@ -51,53 +210,137 @@ impl ConnectionManager {
         
         info!("Contact points: {:?}", contact_points);
         
-        let mut builder = SessionBuilder::new()
-            .known_nodes(&contact_points);
-
-        if let (Some(username), Some(password)) = (&config.username, &config.password) {
-            info!("Using authentication with username: {}", username);
-            builder = builder.user(username, password);
+        let mut builder = SessionBuilder::new();
+        if let Some(proxy) = &config.proxy {
+            let proxy = Arc::new(proxy.clone());
+            contact_points = Self::tunnel_contact_points(&proxy, &contact_points, config.port).await?;
+            builder = builder.address_translator(Arc::new(ProxyTranslator::new(proxy)));
         }
+        let builder = builder.known_nodes(&contact_points);
+
+        let mut builder = Self::configure_auth(builder, &config);
 
         if config.ssl_enabled {
             info!("SSL/TLS enabled with verification: {}", config.ssl_verify);
             
-            let ssl_context = Self::create_ssl_context(config.ssl_verify)?;
+            let materials = TlsMaterials::load(&config)?;
+            let ssl_context = Self::create_ssl_context(config.ssl_verify, &materials)?;
             
             builder = builder.ssl_context(Some(ssl_context));
-            
-            if let Some(ref ca_cert) = config.ssl_ca_cert {
-                info!("CA certificate path specified: {}", ca_cert);
-                eprintln!("Info: Custom CA certificate loading can be added to create_ssl_context()");
+        }
+
+        // Build session, retrying while the cluster cannot be reached yet.
+        // Authentication and TLS setup failures are final.
+        let mut interval = config.connect_retry_interval;
+        let mut attempt = 0;
+        let session = loop {
+            match Self::build_session(&builder, &contact_points, &config).await {
+                Ok(session) => break session,
+                Err(e @ (CqlError::Unreachable(_) | CqlError::ConnectionError(_))) if attempt < config.connect_retries => {
+                    attempt += 1;
+                    warn!(
+                        "Connection attempt {} of {} failed, retrying in {:.1}s: {}",
+                        attempt,
+                        config.connect_retries + 1,
+                        interval.as_secs_f64(),
+                        e.to_string().lines().next().unwrap_or_default()
+                    );
+                    tokio::time::sleep(interval).await;
+                    interval = (interval * 2).min(MAX_CONNECT_RETRY_INTERVAL);
+                }
+                Err(e) => return Err(e),
             }
+        };
+
+        if let Some(keyspace) = &config.keyspace {
+            info!("Using keyspace: {}", keyspace);
+            session.use_keyspace(keyspace, false).await
+                .map_err(|e| CqlError::KeyspaceError(format!(
+                    "Connected, but keyspace '{}' could not be used: {}",
+                    keyspace, e
+                )))?;
         }
 
-        // Build session
+        info!("Successfully connected to Cassandra");
+        
+        Ok(Self { session, config })
+    }
+
+    async fn build_session(
+        builder: &SessionBuilder,
+        contact_points: &[String],
+        config: &ConnectionConfig,
+    ) -> CqlResult<Session> {
         info!("Building session...");
-        let session = builder.build().await
+        builder.build().await
             .map_err(|e| {
+                if is_session_authentication_failure(&e) {
+                    return CqlError::AuthenticationError(format!(
+                        "Could not authenticate against {:?}: {}",
+                        contact_points, e
+                    ));
+                }
+
+                if is_unreachable(&e) {
+                    return CqlError::Unreachable(format!(
+                        "Could not connect to any of {:?}\n\nPossible causes:\n\
+                        1. Cassandra is not running\n\
+                        2. Wrong host/port\n\
+                        3. Firewall blocking connection\n\
+                        4. SSL/TLS mismatch (SSL enabled: {})\n\n\
+                        Original error: {}",
+                        contact_points, config.ssl_enabled, e
+                    ));
+                }
+
+                // Reached a node, but it refused or broke off the session
                 let error_msg = format!(
-                    "Failed to connect to Cassandra at {:?}\n\nPossible causes:\n\
-                    1. Cassandra is not running\n\
-                    2. Wrong host/port (current: {:?})\n\
-                    3. SSL/TLS mismatch (SSL enabled: {})\n\
-                    4. Firewall blocking connection\n\
-                    5. Authentication required but not provided\n\n\
+                    "Connected to {:?}, but the session could not be established\n\nPossible causes:\n\
+                    1. SSL/TLS mismatch (SSL enabled: {})\n\
+                    2. Authentication required but not provided\n\
+                    3. Unsupported protocol version\n\n\
                     Original error: {}",
-                    contact_points, contact_points, config.ssl_enabled, e
+                    contact_points, config.ssl_enabled, e
                 );
                 CqlError::ConnectionError(error_msg)
-            })?;
+            })
+    }
 
-        if let Some(keyspace) = &config.keyspace {
-            info!("Using keyspace: {}", keyspace);
-            session.use_keyspace(keyspace, false).await
-                .map_err(|e| CqlError::ConnectionError(format!("Failed to use keyspace: {}", e)))?;
+    /// Describes known nodes that are marked down, or `None` when all are up.
+    /// A session can be built through one reachable contact point while the
+    /// rest of the cluster is down, so queries may still fail.
+    pub fn degraded_warning(&self) -> Option<String> {
+        let cluster = self.session.get_cluster_data();
+        let nodes = cluster.get_nodes_info();
+        let down: Vec<String> = nodes.iter()
+            .filter(|node| node.is_down())
+            .map(|node| node.address.to_string())
+            .collect();
+
+        if down.is_empty() {
+            None
+        } else if down.len() == nodes.len() {
+            Some(format!(
+                "Connected, but none of the {} known node(s) is up; queries will fail until nodes are back",
+                nodes.len()
+            ))
+        } else {
+            Some(format!(
+                "{} of {} node(s) are down ({}); queries may fail depending on consistency level",
+                down.len(),
+                nodes.len(),
+                down.join(", ")
+            ))
         }
+    }
 
-        info!("Successfully connected to Cassandra");
-        
-        Ok(Self { session, config })
+    /// `release_version` of the node answering the query. Scylla reports the
+    /// Cassandra version it is compatible with here, not its own.
+    pub async fn server_version(&self) -> CqlResult<String> {
+        let result = self.session.query("SELECT release_version FROM system.local", &[]).await?;
+        let (version,) = result.single_row_typed::<(Option<String>,)>()
+            .map_err(|e| CqlError::QueryError(format!("Failed to read the server version: {}", e)))?;
+        version.ok_or_else(|| CqlError::QueryError("system.local has no release_version".to_string()))
     }
 
     pub fn session(&self) -> &Session {
@@ -110,7 +353,7 @@ impl ConnectionManager {
 
     pub async fn use_keyspace(&self, keyspace: &str) -> CqlResult<()> {
         self.session.use_keyspace(keyspace, false).await
-            .map_err(|e| CqlError::ConnectionError(format!("Failed to use keyspace: {}", e)))?;
+            .map_err(|e| CqlError::KeyspaceError(format!("Failed to use keyspace '{}': {}", keyspace, e)))?;
         Ok(())
     }
 }
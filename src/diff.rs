@@ -0,0 +1,109 @@
+use colored::*;
+use prettytable::{format, Cell, Row, Table};
+use std::collections::HashMap;
+use crate::error::{CqlError, CqlResult};
+
+/// The formatted rows of one `\diff` run, kept to compare the next run against
+pub struct ResultSnapshot {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    /// Indices of the primary key columns in `headers`
+    key: Vec<usize>,
+}
+
+impl ResultSnapshot {
+    /// Fails if one of the `primary_key` columns is missing from the result,
+    /// since rows could not be matched between runs without it
+    pub fn new(headers: Vec<String>, rows: Vec<Vec<String>>, primary_key: &[String]) -> CqlResult<Self> {
+        let key = primary_key.iter()
+            .map(|column| headers.iter().position(|header| header == column))
+            .collect::<Option<Vec<_>>>()
+            .filter(|key| !key.is_empty())
+            .ok_or_else(|| CqlError::InvalidQuery(format!(
+                "\\diff needs the primary key columns ({}) in the result",
+                primary_key.join(", ")
+            )))?;
+
+        Ok(Self { headers, rows, key })
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    fn keyed(&self) -> HashMap<Vec<&str>, &Vec<String>> {
+        self.rows.iter()
+            .map(|row| (self.key.iter().map(|&i| row[i].as_str()).collect(), row))
+            .collect()
+    }
+
+    /// Renders the rows added (`+`), removed (`-`) and changed (`~`, with
+    /// changed cells shown as `old → new`) since `previous`, followed by a
+    /// summary line
+    pub fn diff(&self, previous: &ResultSnapshot) -> String {
+        if self.headers != previous.headers {
+            return format!(
+                "{}\n",
+                "The result columns changed since the last run, nothing to compare".yellow()
+            );
+        }
+
+        let before = previous.keyed();
+        let after = self.keyed();
+        let mut lines: Vec<(ColoredString, Vec<String>)> = Vec::new();
+        let mut unchanged = 0;
+        let (mut added, mut removed, mut changed) = (0, 0, 0);
+
+        for row in &self.rows {
+            let key: Vec<&str> = self.key.iter().map(|&i| row[i].as_str()).collect();
+            match before.get(&key) {
+                None => {
+                    added += 1;
+                    lines.push(("+".green().bold(), row.clone()));
+                }
+                Some(old) if *old == row => unchanged += 1,
+                Some(old) => {
+                    changed += 1;
+                    let cells = old.iter()
+                        .zip(row)
+                        .map(|(old, new)| if old == new { new.clone() } else { format!("{} → {}", old, new) })
+                        .collect();
+                    lines.push(("~".yellow().bold(), cells));
+                }
+            }
+        }
+
+        for row in &previous.rows {
+            let key: Vec<&str> = previous.key.iter().map(|&i| row[i].as_str()).collect();
+            if !after.contains_key(&key) {
+                removed += 1;
+                lines.push(("-".red().bold(), row.clone()));
+            }
+        }
+
+        let mut output = String::new();
+        if !lines.is_empty() {
+            let mut table = Table::new();
+            table.set_format(*format::consts::FORMAT_BOX_CHARS);
+            let mut titles = vec![Cell::new("")];
+            titles.extend(self.headers.iter().map(|header| Cell::new(header).style_spec("b")));
+            table.set_titles(Row::new(titles));
+
+            for (marker, cells) in lines {
+                let mut row = vec![Cell::new(&marker.to_string())];
+                row.extend(cells.iter().map(|cell| Cell::new(cell)));
+                table.add_row(Row::new(row));
+            }
+            output.push_str(&table.to_string());
+        }
+
+        output.push_str(&format!(
+            "{} added, {} removed, {} changed, {} unchanged\n",
+            added.to_string().green(),
+            removed.to_string().red(),
+            changed.to_string().yellow(),
+            unchanged
+        ));
+        output
+    }
+}
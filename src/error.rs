@@ -1,12 +1,35 @@
 use thiserror::Error;
+use scylla::transport::errors::{DbError, NewSessionError, QueryError};
+
+/// Process exit codes, one per failure class, so scripts can branch on them
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_GENERIC: i32 = 1;
+pub const EXIT_CONNECTION: i32 = 2;
+pub const EXIT_AUTHENTICATION: i32 = 3;
+pub const EXIT_QUERY: i32 = 4;
+pub const EXIT_TIMEOUT: i32 = 5;
 
 #[derive(Error, Debug)]
 pub enum CqlError {
     #[error("Connection error: {0}")]
     ConnectionError(String),
+
+    /// None of the contact points accepted a connection
+    #[error("No contact point reachable: {0}")]
+    Unreachable(String),
+
+    /// Connected, but the requested keyspace could not be used
+    #[error("Keyspace error: {0}")]
+    KeyspaceError(String),
+
+    #[error("Authentication failed: {0}")]
+    AuthenticationError(String),
     
     #[error("Query execution error: {0}")]
     QueryError(String),
+
+    #[error("Timeout: {0}")]
+    Timeout(String),
     
     #[error("Invalid query: {0}")]
     InvalidQuery(String),
@@ -18,10 +41,163 @@ pub enum CqlError {
     IoError(#[from] std::io::Error),
     
     #[error("Scylla error: {0}")]
-    ScyllaError(#[from] scylla::transport::errors::QueryError),
+    ScyllaError(#[from] QueryError),
     
     #[error("New session error: {0}")]
-    NewSessionError(#[from] scylla::transport::errors::NewSessionError),
+    NewSessionError(#[from] NewSessionError),
+
+    /// A failed statement with a `--explain-errors` hint on how to fix it
+    #[error("{error}\nHint: {hint}")]
+    Explained { error: Box<CqlError>, hint: &'static str },
+}
+
+impl CqlError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CqlError::ConnectionError(_) | CqlError::Unreachable(_) | CqlError::KeyspaceError(_) => EXIT_CONNECTION,
+            CqlError::AuthenticationError(_) => EXIT_AUTHENTICATION,
+            CqlError::QueryError(_) | CqlError::InvalidQuery(_) => EXIT_QUERY,
+            CqlError::Timeout(_) => EXIT_TIMEOUT,
+            CqlError::ConfigError(_) | CqlError::IoError(_) => EXIT_GENERIC,
+            CqlError::ScyllaError(e) if is_timeout(e) => EXIT_TIMEOUT,
+            CqlError::ScyllaError(e) if is_authentication_failure(e) => EXIT_AUTHENTICATION,
+            CqlError::ScyllaError(_) => EXIT_QUERY,
+            CqlError::NewSessionError(e) if is_session_authentication_failure(e) => EXIT_AUTHENTICATION,
+            CqlError::NewSessionError(_) => EXIT_CONNECTION,
+            CqlError::Explained { error, .. } => error.exit_code(),
+        }
+    }
+}
+
+/// Exit code for an error that reached `main`; errors that did not come
+/// from cqlrs itself (e.g. a failed password prompt) count as generic
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    error.downcast_ref::<CqlError>()
+        .map(CqlError::exit_code)
+        .unwrap_or(EXIT_GENERIC)
+}
+
+pub fn is_timeout(error: &QueryError) -> bool {
+    matches!(
+        error,
+        QueryError::TimeoutError
+            | QueryError::RequestTimeout(_)
+            | QueryError::DbError(DbError::ReadTimeout { .. } | DbError::WriteTimeout { .. }, _)
+    )
+}
+
+pub fn is_authentication_failure(error: &QueryError) -> bool {
+    matches!(error, QueryError::DbError(DbError::AuthenticationError, _))
+}
+
+/// Whether the driver rejected a response because a text/ascii cell is not
+/// valid UTF-8. It reports this as an unparsable message carrying the text
+/// of its `Utf8Error` conversion; server errors never match, whatever
+/// their message says.
+pub fn is_invalid_utf8(error: &QueryError) -> bool {
+    matches!(error, QueryError::InvalidMessage(message) if message.contains("UTF8 serialization failed"))
+}
+
+/// Authentication failures during connection setup are not always reported
+/// as `DbError::AuthenticationError` (e.g. when credentials are missing but
+/// the server requires them), so the message is checked as well
+pub fn is_session_authentication_failure(error: &NewSessionError) -> bool {
+    matches!(error, NewSessionError::DbError(DbError::AuthenticationError, _))
+        || error.to_string().to_lowercase().contains("authenticat")
+}
+
+/// Whether building a session failed because no contact point could be
+/// connected to at all (refused, unresolvable, timed out), as opposed to the
+/// server rejecting the connection
+pub fn is_unreachable(error: &NewSessionError) -> bool {
+    matches!(
+        error,
+        NewSessionError::IoError(_)
+            | NewSessionError::FailedToResolveAnyHostname(_)
+            | NewSessionError::EmptyKnownNodesList
+            | NewSessionError::TimeoutError
+            | NewSessionError::RequestTimeout(_)
+    )
+}
+
+/// Parts of well-known server error messages (lower case) and what usually fixes them
+const KNOWN_ERRORS: &[(&str, &str)] = &[
+    (
+        "might involve data filtering",
+        "the WHERE clause restricts columns outside the primary key or skips part of it. Restrict the whole \
+         partition key, query a secondary index or materialized view, or add ALLOW FILTERING if scanning is acceptable",
+    ),
+    (
+        "cannot be restricted as preceding column",
+        "clustering columns can only be restricted in their declared order: restrict every preceding clustering \
+         column with = first",
+    ),
+    (
+        "partition key parts are missing",
+        "restrict every partition key column with = or IN, or scan with token(...) ranges",
+    ),
+    (
+        "only eq and in relation are supported on the partition key",
+        "partition key columns only support = and IN; range scans go through token(...)",
+    ),
+    (
+        "order by is only supported when the partition key is restricted",
+        "ORDER BY needs the partition key restricted by = or IN and can only order by clustering columns",
+    ),
+    (
+        "batch too large",
+        "the batch is larger than batch_size_fail_threshold; split it into smaller batches and keep each batch \
+         to rows of one partition",
+    ),
+    (
+        "unconfigured table",
+        "the table does not exist in that keyspace: check the name and the keyspace (USE or keyspace.table)",
+    ),
+    (
+        "undefined column name",
+        "there is no such column; names are case-sensitive when they were created in double quotes",
+    ),
+    (
+        "cannot achieve consistency level",
+        "too few replicas are up for the consistency level; try CONSISTENCY LOCAL_ONE or check the nodes",
+    ),
+    (
+        "operation timed out",
+        "the replicas did not answer in time; narrow the query, lower --auto-page-threshold or the consistency level",
+    ),
+    (
+        "cannot set the value of counter column",
+        "counters can only be changed relatively, e.g. SET c = c + 1",
+    ),
+];
+
+/// A remediation hint for `error` if its message matches a known server error
+pub fn remediation_hint(error: &CqlError) -> Option<&'static str> {
+    let message = error.to_string().to_lowercase();
+    KNOWN_ERRORS.iter()
+        .find(|(pattern, _)| message.contains(pattern))
+        .map(|(_, hint)| *hint)
 }
 
 pub type CqlResult<T> = Result<T, CqlError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scylla::frame::frame_errors::ParseError;
+
+    #[test]
+    fn invalid_utf8_is_recognized_by_the_drivers_error() {
+        let cell = vec![0x61, 0xff];
+        let utf8_error = std::str::from_utf8(&cell).unwrap_err();
+        let driver_error = QueryError::from(ParseError::from(utf8_error));
+        assert!(is_invalid_utf8(&driver_error));
+
+        let server_error = QueryError::DbError(
+            DbError::Invalid,
+            "Table comment 'stores UTF-8 names' is too long".to_string(),
+        );
+        assert!(!is_invalid_utf8(&server_error));
+        assert!(!is_invalid_utf8(&QueryError::InvalidMessage("Frame error: utf-8".to_string())));
+    }
+}
@@ -1,37 +1,616 @@
+use bytes::Bytes;
+use scylla::frame::response::result::{ColumnSpec, CqlValue, Row as CqlRow};
+use scylla::prepared_statement::PreparedStatement;
 use scylla::query::Query;
+use scylla::serialize::row::SerializeRow;
+use scylla::statement::{Consistency, SerialConsistency};
+use scylla::transport::errors::{DbError, QueryError};
 use scylla::transport::query_result::QueryResult;
 use crate::connection::{ConnectionConfig, ConnectionManager};
-use crate::error::{CqlError, CqlResult};
-use crate::formatter::{format_result, OutputFormat};
-use tracing::{info, error};
+use crate::browse::browse_result;
+use crate::sqlite::SqliteWriter;
+use crate::diff::ResultSnapshot;
+use crate::result::StructuredResult;
+use crate::stats::{ResultStats, StatsMode};
+use crate::params::parse_param;
+use crate::output;
+use crate::statement::{counter_readback, has_allow_filtering, now_placeholder, qualify_tables, redact_literals};
+use crate::error::{is_authentication_failure, is_invalid_utf8, is_timeout, remediation_hint, CqlError, CqlResult};
+use crate::formatter::{
+    filter_rows, format_column_specs, format_result, speculative_ddl, formatted_cells, reorder_columns, row_matches, sort_collections, FormatOptions,
+    OutputFormat, PagedFormatter,
+};
+use colored::*;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use futures::stream::{self, StreamExt};
+use prettytable::{format, Cell, Row, Table};
+use std::io::IsTerminal;
+use std::time::{Duration, Instant};
+use tracing::{info, error, warn};
+
+/// SELECTs returning more rows than this are printed page by page
+pub const DEFAULT_AUTO_PAGE_THRESHOLD: usize = 5000;
+
+/// Slow query warnings show at most this much of the statement
+const SLOW_QUERY_MAX_CHARS: usize = 200;
+
+/// Consistency levels by their CQL names, as accepted by cqlsh's CONSISTENCY
+const CONSISTENCY_LEVELS: &[(&str, Consistency)] = &[
+    ("ANY", Consistency::Any),
+    ("ONE", Consistency::One),
+    ("TWO", Consistency::Two),
+    ("THREE", Consistency::Three),
+    ("QUORUM", Consistency::Quorum),
+    ("ALL", Consistency::All),
+    ("LOCAL_QUORUM", Consistency::LocalQuorum),
+    ("EACH_QUORUM", Consistency::EachQuorum),
+    ("LOCAL_ONE", Consistency::LocalOne),
+    ("SERIAL", Consistency::Serial),
+    ("LOCAL_SERIAL", Consistency::LocalSerial),
+];
+
+pub fn parse_consistency(name: &str) -> Option<Consistency> {
+    CONSISTENCY_LEVELS.iter()
+        .find(|(level, _)| level.eq_ignore_ascii_case(name))
+        .map(|(_, consistency)| *consistency)
+}
+
+pub fn consistency_name(consistency: Consistency) -> &'static str {
+    CONSISTENCY_LEVELS.iter()
+        .find(|(_, level)| *level == consistency)
+        .map(|(name, _)| *name)
+        .unwrap_or("UNKNOWN")
+}
+
+/// Whether `statement` reads the schema rather than data: a query of a
+/// `system_schema` table or a server-side DESCRIBE
+fn is_schema_query(statement: &str) -> bool {
+    let lowercase = statement.trim_start().to_lowercase();
+    lowercase.contains("system_schema.")
+        || lowercase.split_whitespace().next().is_some_and(|word| word == "describe" || word == "desc")
+}
+
+pub fn consistency_names() -> Vec<&'static str> {
+    CONSISTENCY_LEVELS.iter().map(|(name, _)| *name).collect()
+}
+
+pub fn parse_serial_consistency(name: &str) -> Option<SerialConsistency> {
+    parse_consistency(name).and_then(|consistency| SerialConsistency::try_from(consistency).ok())
+}
+
+pub fn serial_consistency_name(consistency: SerialConsistency) -> &'static str {
+    match consistency {
+        SerialConsistency::Serial => "SERIAL",
+        SerialConsistency::LocalSerial => "LOCAL_SERIAL",
+    }
+}
+
+/// Latencies of a `benchmark` run
+pub struct BenchmarkStats {
+    latencies: Vec<Duration>,
+    elapsed: Duration,
+}
+
+impl BenchmarkStats {
+    /// Nearest-rank percentile of the sorted latencies
+    fn percentile(&self, p: f64) -> Duration {
+        let rank = ((p / 100.0) * self.latencies.len() as f64).ceil() as usize;
+        self.latencies[rank.clamp(1, self.latencies.len()) - 1]
+    }
+
+    pub fn render(&self) -> String {
+        let ms = |d: Duration| format!("{:.3} ms", d.as_secs_f64() * 1000.0);
+        let total: Duration = self.latencies.iter().sum();
+        let mean = total / self.latencies.len() as u32;
+
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_BOX_CHARS);
+        table.set_titles(Row::new(vec![
+            Cell::new("Metric").style_spec("b"),
+            Cell::new("Value").style_spec("b"),
+        ]));
+        for (name, value) in [
+            ("Iterations", self.latencies.len().to_string()),
+            ("Min", ms(self.latencies[0])),
+            ("Max", ms(self.latencies[self.latencies.len() - 1])),
+            ("Mean", ms(mean)),
+            ("p50", ms(self.percentile(50.0))),
+            ("p95", ms(self.percentile(95.0))),
+            ("p99", ms(self.percentile(99.0))),
+            ("Throughput", format!(
+                "{:.1} queries/s",
+                self.latencies.len() as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+            )),
+        ] {
+            table.add_row(Row::new(vec![Cell::new(name), Cell::new(&value)]));
+        }
+        table.to_string()
+    }
+}
 
 pub struct QueryExecutor {
     connection: ConnectionManager,
+    auto_page_threshold: usize,
+    strip_trailing_semicolon: bool,
+    last_output: Option<String>,
+    consistency: Consistency,
+    serial_consistency: SerialConsistency,
+    schema_consistency: Option<Consistency>,
+    params: Vec<String>,
+    qualify_tables: bool,
+    page_timeout: Option<Duration>,
+    page_retries: usize,
+    slow_query_threshold: Option<Duration>,
+    echo_counter: bool,
+    allow_filtering: bool,
+    explain_errors: bool,
+    now_column: Option<String>,
+    show_coordinator: bool,
+    /// Statements prepared for `--param` values and benchmarks, by keyspace
+    /// and text, so they are prepared once per session
+    prepared: std::sync::Mutex<HashMap<(Option<String>, String), PreparedStatement>>,
+}
+
+/// Runs `operation` with `prepared`. The driver re-prepares statements the
+/// server has evicted by itself, but if the statement still comes back as
+/// unprepared (e.g. because a schema change gave it a new id), `prepare`
+/// prepares it again, the result replaces `prepared` and `operation` is
+/// retried once before the error is reported.
+async fn retry_unprepared<P, T, Op, OpFuture, Prepare, PrepareFuture>(
+    prepared: &mut P,
+    mut operation: Op,
+    prepare: Prepare,
+) -> Result<T, QueryError>
+where
+    P: Clone,
+    Op: FnMut(P) -> OpFuture,
+    OpFuture: Future<Output = Result<T, QueryError>>,
+    Prepare: FnOnce(P) -> PrepareFuture,
+    PrepareFuture: Future<Output = Result<P, QueryError>>,
+{
+    match operation(prepared.clone()).await {
+        Err(QueryError::DbError(DbError::Unprepared { .. }, _)) => {
+            info!("Prepared statement was invalidated, preparing it again");
+            *prepared = prepare(prepared.clone()).await?;
+            operation(prepared.clone()).await
+        }
+        outcome => outcome,
+    }
+}
+
+/// Brings a statement into the form it is sent to the server in, so the REPL
+/// (which keeps the terminating `;`) and file/`-e` execution (which split on
+/// it) behave the same: whitespace and trailing semicolons are removed and,
+/// unless `strip_trailing_semicolon` is set, exactly one `;` is appended.
+pub fn normalize_statement(query: &str, strip_trailing_semicolon: bool) -> String {
+    let statement = query.trim().trim_end_matches(|c: char| c == ';' || c.is_whitespace());
+    if strip_trailing_semicolon || statement.is_empty() {
+        statement.to_string()
+    } else {
+        format!("{};", statement)
+    }
 }
 
 impl QueryExecutor {
     pub async fn new(config: ConnectionConfig) -> CqlResult<Self> {
         let connection = ConnectionManager::connect(config).await?;
-        Ok(Self { connection })
+        Ok(Self {
+            connection,
+            auto_page_threshold: DEFAULT_AUTO_PAGE_THRESHOLD,
+            strip_trailing_semicolon: true,
+            last_output: None,
+            consistency: Consistency::LocalQuorum,
+            serial_consistency: SerialConsistency::LocalSerial,
+            schema_consistency: None,
+            params: Vec::new(),
+            qualify_tables: false,
+            page_timeout: None,
+            page_retries: 0,
+            slow_query_threshold: None,
+            echo_counter: false,
+            allow_filtering: true,
+            explain_errors: false,
+            now_column: None,
+            show_coordinator: false,
+            prepared: std::sync::Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn set_strip_trailing_semicolon(&mut self, strip: bool) {
+        self.strip_trailing_semicolon = strip;
+    }
+
+    pub fn auto_page_threshold(&self) -> usize {
+        self.auto_page_threshold
+    }
+
+    pub fn strip_trailing_semicolon(&self) -> bool {
+        self.strip_trailing_semicolon
+    }
+
+    pub fn consistency(&self) -> Consistency {
+        self.consistency
+    }
+
+    /// Consistency level for all following statements
+    pub fn set_consistency(&mut self, consistency: Consistency) {
+        self.consistency = consistency;
+    }
+
+    pub fn serial_consistency(&self) -> SerialConsistency {
+        self.serial_consistency
+    }
+
+    /// Serial consistency level for the Paxos phase of conditional updates
+    pub fn set_serial_consistency(&mut self, consistency: SerialConsistency) {
+        self.serial_consistency = consistency;
+    }
+
+    pub fn schema_consistency(&self) -> Option<Consistency> {
+        self.schema_consistency
+    }
+
+    /// Consistency level for reads of `system_schema` (completion, describe),
+    /// so they keep working when the data consistency cannot be met. `None`
+    /// uses the data consistency.
+    pub fn set_schema_consistency(&mut self, consistency: Option<Consistency>) {
+        self.schema_consistency = consistency;
+    }
+
+    /// The consistency `statement` runs at: the schema consistency for
+    /// schema introspection, the session's consistency for everything else
+    fn consistency_for(&self, statement: &str) -> Consistency {
+        match self.schema_consistency {
+            Some(consistency) if is_schema_query(statement) => consistency,
+            _ => self.consistency,
+        }
+    }
+
+    /// Values for `?` bind markers, applied to every statement that has them
+    pub fn set_params(&mut self, params: Vec<String>) {
+        self.params = params;
+    }
+
+    /// Prefix unqualified table names with the current keyspace before sending
+    pub fn set_qualify_tables(&mut self, qualify: bool) {
+        self.qualify_tables = qualify;
+    }
+
+    pub fn page_timeout(&self) -> Option<Duration> {
+        self.page_timeout
+    }
+
+    /// Time limit for each page of a SELECT, independent of how long the
+    /// whole result takes; `None` uses the driver's default
+    pub fn set_page_timeout(&mut self, timeout: Option<Duration>) {
+        self.page_timeout = timeout;
+    }
+
+    pub fn page_retries(&self) -> usize {
+        self.page_retries
+    }
+
+    /// How often a page that hit the page timeout is requested again
+    pub fn set_page_retries(&mut self, retries: usize) {
+        self.page_retries = retries;
+    }
+
+    pub fn slow_query_threshold(&self) -> Option<Duration> {
+        self.slow_query_threshold
+    }
+
+    /// Warn about every statement whose round trips to the server took
+    /// longer than this; `None` disables the warning
+    pub fn set_slow_query_threshold(&mut self, threshold: Option<Duration>) {
+        self.slow_query_threshold = threshold;
+    }
+
+    pub fn allow_filtering(&self) -> bool {
+        self.allow_filtering
+    }
+
+    /// Whether statements with `ALLOW FILTERING` may run; when off they are
+    /// refused before anything is sent
+    pub fn set_allow_filtering(&mut self, allow: bool) {
+        self.allow_filtering = allow;
+    }
+
+    /// Refuses statements the session's guardrails forbid. Only applied to
+    /// statements the user entered, not to cqlrs' own schema lookups.
+    fn check_allowed(&self, statement: &str) -> CqlResult<()> {
+        if !self.allow_filtering && has_allow_filtering(statement) {
+            return Err(CqlError::InvalidQuery(
+                "ALLOW FILTERING is disabled in this session (--no-allow-filtering, \\allow-filtering on to enable)"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Follow counter updates with a read of the updated counters
+    pub fn set_echo_counter(&mut self, echo: bool) {
+        self.echo_counter = echo;
+    }
+
+    /// Trace statements and name the coordinator that served them
+    pub fn set_show_coordinator(&mut self, show: bool) {
+        self.show_coordinator = show;
+    }
+
+    /// `--show-coordinator`: the node that coordinated a traced statement and
+    /// the consistency level it ran with, read from its trace session, as one
+    /// line on stderr
+    async fn report_coordinator(&self, tracing_id: uuid::Uuid, requested: Option<Consistency>) {
+        let line = match self.connection.session().get_tracing_info(&tracing_id).await {
+            Ok(trace) => {
+                let coordinator = trace.coordinator.map(|ip| ip.to_string()).unwrap_or_else(|| "unknown".to_string());
+                let consistency = trace.parameters.as_ref()
+                    .and_then(|parameters| parameters.get("consistency_level").cloned())
+                    .or_else(|| requested.map(|c| consistency_name(c).to_string()))
+                    .unwrap_or_else(|| "unknown".to_string());
+                let duration = trace.duration
+                    .map(|micros| format!(", {:.1} ms on the server", micros as f64 / 1000.0))
+                    .unwrap_or_default();
+                format!("Coordinator: {}, consistency {}{} (trace {})", coordinator, consistency, duration, tracing_id)
+            }
+            Err(e) => format!("Coordinator: unknown, trace {} could not be read: {}", tracing_id, e),
+        };
+        eprintln!("{}", line.bright_black());
+    }
+
+    /// Column whose `:now` placeholder in INSERT/UPDATE is replaced by the current time
+    pub fn set_now_column(&mut self, column: Option<String>) {
+        self.now_column = column;
+    }
+
+    /// `--now-column`: `statement` with the `:now` given for the column
+    /// replaced by `toTimestamp(now())` or `now()`, depending on whether the
+    /// column is a timestamp or a timeuuid; `None` if there is nothing to fill
+    async fn fill_now_column(&self, statement: &str) -> CqlResult<Option<String>> {
+        let Some(column) = &self.now_column else {
+            return Ok(None);
+        };
+        let Some(placeholder) = now_placeholder(statement, column) else {
+            return Ok(None);
+        };
+
+        let unquote = |name: &str| match name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+            Some(quoted) => quoted.replace("\"\"", "\""),
+            None => name.to_lowercase(),
+        };
+        let (keyspace, table) = match placeholder.table.split_once('.') {
+            Some((keyspace, table)) => (unquote(keyspace), unquote(table)),
+            None => {
+                let keyspace = self.current_keyspace().ok_or_else(|| {
+                    CqlError::InvalidQuery("--now-column: no keyspace selected to look up the column type in".to_string())
+                })?;
+                (keyspace, unquote(&placeholder.table))
+            }
+        };
+
+        let query = format!(
+            "SELECT type FROM system_schema.columns WHERE keyspace_name = '{}' AND table_name = '{}' AND column_name = '{}'",
+            keyspace.replace('\'', "''"),
+            table.replace('\'', "''"),
+            column.replace('\'', "''")
+        );
+        let result = self.execute(&query).await?;
+        let column_type = result.maybe_first_row_typed::<(String,)>()
+            .map_err(|e| CqlError::QueryError(format!("--now-column: failed to read the column type: {}", e)))?
+            .map(|(column_type,)| column_type)
+            .ok_or_else(|| CqlError::InvalidQuery(format!("--now-column: {}.{} has no column '{}'", keyspace, table, column)))?;
+
+        let value = match column_type.as_str() {
+            "timestamp" => "toTimestamp(now())",
+            "timeuuid" => "now()",
+            other => {
+                return Err(CqlError::InvalidQuery(format!(
+                    "--now-column: '{}' is a {} column, :now only fills timestamp and timeuuid columns",
+                    column, other
+                )));
+            }
+        };
+
+        let mut filled = statement.to_string();
+        filled.replace_range(placeholder.range, value);
+        info!("Filled {} with {}: {}", column, value, filled);
+        Ok(Some(filled))
+    }
+
+    /// Add a remediation hint to errors of statements that match a known server error
+    pub fn set_explain_errors(&mut self, explain: bool) {
+        self.explain_errors = explain;
+    }
+
+    /// Reports a statement that took longer than `--slow-query-threshold`,
+    /// with its string literals redacted. Interactive sessions get a warning
+    /// on stderr, scripts only a log entry so their output stays clean.
+    fn report_slow_query(&self, statement: &str, elapsed: Duration) {
+        let Some(threshold) = self.slow_query_threshold else {
+            return;
+        };
+        if elapsed <= threshold {
+            return;
+        }
+
+        let statement = redact_literals(statement.trim());
+        let statement = match statement.char_indices().nth(SLOW_QUERY_MAX_CHARS) {
+            Some((end, _)) => format!("{}...", &statement[..end]),
+            None => statement,
+        };
+        let message = format!(
+            "Slow query ({:.1} ms, threshold {} ms): {}",
+            elapsed.as_secs_f64() * 1000.0,
+            threshold.as_millis(),
+            statement
+        );
+        if std::io::stdin().is_terminal() {
+            eprintln!("{}", message.yellow());
+        } else {
+            warn!("{}", message);
+        }
+    }
+
+    /// Prepares `query` and parses the `--param` values for its bind markers
+    /// using the types the server reports for them. Returns `None` when no
+    /// values are given or the statement has no markers.
+    async fn bind_params(&self, query: &Query) -> CqlResult<Option<(PreparedStatement, Vec<Option<CqlValue>>)>> {
+        if self.params.is_empty() {
+            return Ok(None);
+        }
+
+        let prepared = self.prepare_cached(query).await?;
+        let markers = prepared.get_variable_col_specs();
+        if markers.is_empty() {
+            return Ok(None);
+        }
+        if markers.len() != self.params.len() {
+            return Err(CqlError::InvalidQuery(format!(
+                "Statement has {} bind marker(s) but {} --param value(s) were given",
+                markers.len(),
+                self.params.len()
+            )));
+        }
+
+        let values = self.params.iter()
+            .zip(markers)
+            .enumerate()
+            .map(|(index, (raw, spec))| parse_param(raw, &spec.typ, index))
+            .collect::<CqlResult<Vec<_>>>()?;
+        Ok(Some((prepared, values)))
+    }
+
+    fn prepared_cache(&self) -> std::sync::MutexGuard<'_, HashMap<(Option<String>, String), PreparedStatement>> {
+        self.prepared.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// `query` prepared, from the cache if it was prepared before in the
+    /// current keyspace, with the settings (consistency, paging, timeout,
+    /// tracing) of `query`
+    async fn prepare_cached(&self, query: &Query) -> CqlResult<PreparedStatement> {
+        let key = (self.current_keyspace(), query.contents.clone());
+        let cached = self.prepared_cache().get(&key).cloned();
+        let Some(mut prepared) = cached else {
+            let prepared = self.connection.session()
+                .prepare(query.clone())
+                .await
+                .map_err(Self::query_failed)?;
+            self.prepared_cache().insert(key, prepared.clone());
+            return Ok(prepared);
+        };
+
+        if let Some(consistency) = query.get_consistency() {
+            prepared.set_consistency(consistency);
+        }
+        prepared.set_serial_consistency(query.get_serial_consistency());
+        match query.get_page_size() {
+            Some(page_size) => prepared.set_page_size(page_size),
+            None => prepared.disable_paging(),
+        }
+        prepared.set_request_timeout(query.get_request_timeout());
+        prepared.set_tracing(query.get_tracing());
+        Ok(prepared)
+    }
+
+    /// Prepares the text of `stale` again, keeping its settings, and puts
+    /// the new statement in the cache in its place
+    async fn reprepare(&self, stale: PreparedStatement) -> Result<PreparedStatement, QueryError> {
+        let mut fresh = self.connection.session().prepare(stale.get_statement()).await?;
+        if let Some(consistency) = stale.get_consistency() {
+            fresh.set_consistency(consistency);
+        }
+        fresh.set_serial_consistency(stale.get_serial_consistency());
+        match stale.get_page_size() {
+            Some(page_size) => fresh.set_page_size(page_size),
+            None => fresh.disable_paging(),
+        }
+        fresh.set_request_timeout(stale.get_request_timeout());
+        fresh.set_tracing(stale.get_tracing());
+
+        let key = (self.current_keyspace(), stale.get_statement().to_string());
+        self.prepared_cache().insert(key, fresh.clone());
+        Ok(fresh)
+    }
+
+    /// A statement as sent to the server, with the session's consistency levels
+    fn new_query(&self, query_str: &str) -> Query {
+        let mut statement = normalize_statement(query_str, self.strip_trailing_semicolon);
+        if self.qualify_tables {
+            if let Some(keyspace) = self.current_keyspace() {
+                statement = qualify_tables(&statement, &keyspace);
+                info!("Qualified statement: {}", statement);
+            }
+        }
+
+        let consistency = self.consistency_for(&statement);
+        let mut query = Query::new(statement);
+        query.set_consistency(consistency);
+        query.set_serial_consistency(Some(self.serial_consistency));
+        query
+    }
+
+    /// The formatted output of the last non-paged result, if any
+    pub fn last_output(&self) -> Option<&str> {
+        self.last_output.as_deref()
+    }
+
+    pub fn current_keyspace(&self) -> Option<String> {
+        self.connection.session().get_keyspace().map(|ks| ks.to_string())
+    }
+
+    /// Sets the row count above which results are streamed page by page
+    /// instead of being fetched at once. `0` disables automatic paging.
+    pub fn set_auto_page_threshold(&mut self, threshold: usize) {
+        self.auto_page_threshold = threshold;
+    }
+
+    fn query_failed(e: scylla::transport::errors::QueryError) -> CqlError {
+        error!("Query execution failed: {}", e);
+        let message = e.to_string();
+        if is_timeout(&e) {
+            return CqlError::Timeout(message);
+        }
+        if is_authentication_failure(&e) {
+            return CqlError::AuthenticationError(message);
+        }
+        // The driver rejects the whole response if a text/ascii cell is not
+        // valid UTF-8, so the rows never reach the formatter
+        if is_invalid_utf8(&e) {
+            return CqlError::QueryError(format!(
+                "{}\nA text or ascii column contains bytes that are not valid UTF-8. \
+                Select it as textAsBlob(<column>) to see the raw bytes.",
+                message
+            ));
+        }
+        CqlError::QueryError(message)
     }
 
     pub async fn execute(&self, query_str: &str) -> CqlResult<QueryResult> {
-        info!("Executing query: {}", query_str.trim());
-        
-        let query = Query::new(query_str);
+        let query = self.new_query(query_str);
+        info!("Executing query: {}", query.contents);
         
         let result = self.connection.session()
             .query(query, &[])
             .await
-            .map_err(|e| {
-                error!("Query execution failed: {}", e);
-                CqlError::QueryError(format!("{}", e))
-            })?;
+            .map_err(Self::query_failed)?;
 
         Ok(result)
     }
 
-    pub async fn execute_and_print(&mut self, query_str: &str, format: &str) -> CqlResult<()> {
+    pub async fn execute_and_print(&mut self, query_str: &str, format: &str, options: &FormatOptions) -> CqlResult<()> {
+        let result = self.print_statement(query_str, format, options).await;
+        match result {
+            Err(error) if self.explain_errors => match remediation_hint(&error) {
+                Some(hint) => Err(CqlError::Explained { error: Box::new(error), hint }),
+                None => Err(error),
+            },
+            result => result,
+        }
+    }
+
+    async fn print_statement(&mut self, query_str: &str, format: &str, options: &FormatOptions) -> CqlResult<()> {
         let query_trimmed = query_str.trim();
         
         if query_trimmed.to_lowercase().starts_with("use ") {
@@ -44,22 +623,633 @@ impl QueryExecutor {
         if query_trimmed.is_empty() {
             return Ok(());
         }
+        self.check_allowed(query_trimmed)?;
+        let filled = self.fill_now_column(query_trimmed).await?;
+        let query_str = filled.as_deref().unwrap_or(query_str);
 
-        let result = self.execute(query_str).await?;
-        
-        let output_format = match format.to_lowercase().as_str() {
-            "json" => OutputFormat::Json,
-            "csv" => OutputFormat::Csv,
-            _ => OutputFormat::Table,
+        let output_format = OutputFormat::from_name(format);
+
+        info!("Executing query: {}", query_trimmed);
+
+        // SELECTs are fetched with a page size of `auto_page_threshold`. If the
+        // first page already holds the complete result (e.g. because of a
+        // LIMIT) it is printed as usual, otherwise the remaining pages are
+        // streamed so large results never have to be held in memory at once.
+        let mut query = self.new_query(query_str);
+        let is_select = query_trimmed.split_whitespace()
+            .next()
+            .is_some_and(|word| word.eq_ignore_ascii_case("select"));
+        if self.auto_page_threshold > 0 && is_select {
+            query.set_page_size(i32::try_from(self.auto_page_threshold).unwrap_or(i32::MAX));
+        }
+
+        // Only SELECTs are safe to retry after a page timed out
+        let page_timeout = self.page_timeout.filter(|_| is_select);
+        query.set_request_timeout(page_timeout);
+        query.set_tracing(self.show_coordinator);
+
+        // Only the time spent waiting for the server counts towards
+        // --slow-query-threshold, not formatting or writing the output
+        let started = Instant::now();
+        let mut bound = self.bind_params(&query).await?;
+        let mut result = self.fetch_page(&query, bound.as_mut(), None, page_timeout).await?;
+        let mut elapsed = started.elapsed();
+        let tracing_id = result.tracing_id;
+        reorder_columns(&mut result, options)?;
+
+        let mut filtered = 0;
+        self.last_output = None;
+
+        // Statistics go below tables; machine formats only get them with
+        // `--stats always`, on stderr so stdout stays parseable
+        let stats_on_stdout = matches!(output_format, OutputFormat::Table);
+        let mut stats = match options.stats {
+            StatsMode::Always => Some(ResultStats::new(&result.col_specs)),
+            StatsMode::Table if stats_on_stdout => Some(ResultStats::new(&result.col_specs)),
+            _ => None,
+        };
+
+        if result.paging_state.is_none() {
+            if options.sort_collections {
+                sort_collections(&mut result);
+            }
+            if let Some(pattern) = &options.grep {
+                filtered += filter_rows(&mut result, pattern, options);
+            }
+            if let Some(stats) = &mut stats {
+                stats.add(&result);
+            }
+
+            let formatted = format_result(&result, output_format, options)?;
+            output::println(&formatted);
+            self.last_output = Some(formatted);
+        } else {
+            info!("Result exceeds {} rows, fetching page by page", self.auto_page_threshold);
+            let mut paged = PagedFormatter::new(output_format);
+
+            loop {
+                let paging_state = result.paging_state.take();
+                if options.sort_collections {
+                    sort_collections(&mut result);
+                }
+                if let Some(pattern) = &options.grep {
+                    filtered += filter_rows(&mut result, pattern, options);
+                }
+                if let Some(stats) = &mut stats {
+                    stats.add(&result);
+                }
+                output::print(&paged.page(&result, options)?);
+
+                let Some(paging_state) = paging_state else {
+                    break;
+                };
+                let started = Instant::now();
+                result = match self.fetch_page(&query, bound.as_mut(), Some(paging_state.clone()), page_timeout).await {
+                    Ok(result) => result,
+                    Err(e) => {
+                        // Keep what was already printed usable (closed table,
+                        // complete JSON) and say where the result stops
+                        let (pages, rows) = (paged.pages(), paged.rows());
+                        output::println(&paged.finish(options));
+                        eprintln!(
+                            "{}",
+                            format!("Partial result: fetching page {} failed after {} row(s)", pages + 1, rows)
+                                .yellow()
+                                .bold()
+                        );
+                        eprintln!(
+                            "Paging state of the missing page: 0x{}",
+                            paging_state.iter().map(|byte| format!("{:02x}", byte)).collect::<String>()
+                        );
+                        return Err(e);
+                    }
+                };
+                elapsed += started.elapsed();
+                reorder_columns(&mut result, options)?;
+            }
+
+            output::println(&paged.finish(options));
+        }
+
+        if let Some(stats) = stats.map(|stats| stats.render()).filter(|stats| !stats.is_empty()) {
+            if stats_on_stdout {
+                output::print(&stats);
+            } else {
+                eprint!("{}", stats);
+            }
+        }
+        if filtered > 0 {
+            eprintln!("{}", format!("{} row(s) filtered out by grep", filtered).bright_black());
+        }
+        self.report_slow_query(query_trimmed, elapsed);
+        if let Some(tracing_id) = tracing_id {
+            self.report_coordinator(tracing_id, query.get_consistency()).await;
+        }
+
+        if let Some(select) = counter_readback(query_trimmed).filter(|_| self.echo_counter) {
+            // Only feedback, a failed read does not fail the update
+            match self.execute(&select).await {
+                Ok(result) => output::println(&format_result(&result, OutputFormat::from_name(format), options)?),
+                Err(e) => info!("Could not read back the updated counter: {}", e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fetches one page of `query`. With a `page_timeout` (set as the
+    /// statement's request timeout) a page that does not arrive in time is
+    /// requested again up to `page_retries` times before giving up, so one
+    /// stalled page does not abort a long export.
+    async fn fetch_page(
+        &self,
+        query: &Query,
+        mut bound: Option<&mut (PreparedStatement, Vec<Option<CqlValue>>)>,
+        paging_state: Option<Bytes>,
+        page_timeout: Option<Duration>,
+    ) -> CqlResult<QueryResult> {
+        let mut attempt = 0;
+        loop {
+            let session = self.connection.session();
+            let outcome = match bound.as_deref_mut() {
+                Some((prepared, values)) => {
+                    let values = &*values;
+                    retry_unprepared(
+                        prepared,
+                        |prepared| {
+                            let paging_state = paging_state.clone();
+                            async move { session.execute_paged(&prepared, values, paging_state).await }
+                        },
+                        |stale| self.reprepare(stale),
+                    ).await
+                }
+                None => session.query_paged(query.clone(), &[], paging_state.clone()).await,
+            };
+
+            match (outcome, page_timeout) {
+                (Err(e), Some(timeout)) if is_timeout(&e) && attempt < self.page_retries => {
+                    attempt += 1;
+                    eprintln!(
+                        "{} page did not arrive within {:.1}s, retrying ({}/{})",
+                        "Warning:".yellow().bold(),
+                        timeout.as_secs_f64(),
+                        attempt,
+                        self.page_retries
+                    );
+                }
+                (outcome, _) => return outcome.map_err(Self::query_failed),
+            }
+        }
+    }
+
+    /// Re-runs `statements` every `interval` like the Unix `watch` command,
+    /// clearing the screen before each run, until Ctrl-C is pressed.
+    pub async fn watch(
+        &mut self,
+        statements: &[String],
+        interval: Duration,
+        format: &str,
+        options: &FormatOptions,
+    ) -> CqlResult<()> {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        let runs = async {
+            loop {
+                ticker.tick().await;
+                print!("\x1B[2J\x1B[1;1H");
+                println!(
+                    "{} {}",
+                    format!("Every {:.1}s: {}", interval.as_secs_f64(), statements.join("; ")).bright_black(),
+                    chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string().cyan()
+                );
+                println!();
+
+                for statement in statements {
+                    if let Err(e) = self.execute_and_print(statement, format, options).await {
+                        eprintln!("{} {}", "Error:".red().bold(), e);
+                    }
+                }
+            }
+        };
+
+        tokio::select! {
+            _ = runs => {}
+            _ = tokio::signal::ctrl_c() => {
+                println!("{}", "Watch stopped".yellow());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prepares `query` without executing it and prints the columns it
+    /// would return
+    pub async fn describe_result(&self, query: &str, format: &str) -> CqlResult<()> {
+        let statement = normalize_statement(query, self.strip_trailing_semicolon);
+        let prepared = self.connection.session()
+            .prepare(statement)
+            .await
+            .map_err(Self::query_failed)?;
+
+        let formatted = format_column_specs(prepared.get_result_set_col_specs(), OutputFormat::from_name(format))?;
+        output::println(&formatted);
+        Ok(())
+    }
+
+    /// Prepares `query` without executing it and prints a speculative
+    /// `CREATE TABLE table` for its result columns
+    pub async fn generate_ddl(&self, table: &str, query: &str) -> CqlResult<()> {
+        let statement = normalize_statement(query, self.strip_trailing_semicolon);
+        let prepared = self.connection.session()
+            .prepare(statement)
+            .await
+            .map_err(Self::query_failed)?;
+
+        let col_specs = prepared.get_result_set_col_specs();
+        if col_specs.is_empty() {
+            return Err(CqlError::InvalidQuery("The query returns no columns to build a table from".to_string()));
+        }
+        output::println(&speculative_ddl(table, col_specs, query));
+        Ok(())
+    }
+
+    /// Runs a single statement with the session's settings and `--param`
+    /// values and returns its complete result, unpaged and unformatted
+    pub async fn run(&self, query: &str) -> CqlResult<StructuredResult> {
+        self.check_allowed(query)?;
+        let statement = self.new_query(query);
+        let bound = self.bind_params(&statement).await?;
+        let session = self.connection.session();
+
+        let started = Instant::now();
+        let result = match bound {
+            Some((mut prepared, values)) => self.execute_prepared(&mut prepared, &values).await?,
+            None => session.query(statement, &[]).await.map_err(Self::query_failed)?,
+        };
+
+        Ok(StructuredResult::from_query_result(result, started.elapsed()))
+    }
+
+    /// Runs `query` and shows the complete result in the full-screen browser.
+    /// Statements other than SELECT are executed and printed as usual.
+    pub async fn browse(&mut self, query: &str, format: &str, options: &FormatOptions) -> CqlResult<()> {
+        let is_select = query.split_whitespace()
+            .next()
+            .is_some_and(|word| word.eq_ignore_ascii_case("select"));
+        if !is_select {
+            return self.execute_and_print(query, format, options).await;
+        }
+
+        // Unpaged, the browser needs every row up front
+        let structured = self.run(query).await?;
+        structured.print_warnings();
+        let mut result = structured.into_query_result();
+
+        if options.sort_collections {
+            sort_collections(&mut result);
+        }
+        if let Some(pattern) = &options.grep {
+            filter_rows(&mut result, pattern, options);
+        }
+
+        browse_result(&result, options)
+    }
+
+    /// Runs `query` with the session's settings and `--param` values and
+    /// calls `on_row` for every row as the pages arrive, so results of any
+    /// size are processed in constant memory. The column specs are passed
+    /// along with each row. An error from `on_row` stops fetching and is
+    /// returned. Returns the number of rows.
+    pub async fn stream<F>(&self, query: &str, mut on_row: F) -> CqlResult<usize>
+    where
+        F: FnMut(&[ColumnSpec], CqlRow) -> CqlResult<()>,
+    {
+        self.check_allowed(query)?;
+        let mut statement = self.new_query(query);
+        if self.auto_page_threshold > 0 {
+            statement.set_page_size(i32::try_from(self.auto_page_threshold).unwrap_or(i32::MAX));
+        }
+        statement.set_request_timeout(self.page_timeout);
+        let bound = self.bind_params(&statement).await?;
+        let session = self.connection.session();
+
+        let mut rows = match bound {
+            Some((mut prepared, values)) => {
+                retry_unprepared(
+                    &mut prepared,
+                    |prepared| session.execute_iter(prepared, values.clone()),
+                    |stale| self.reprepare(stale),
+                ).await
+            }
+            None => session.query_iter(statement, ()).await,
+        }
+        .map_err(Self::query_failed)?;
+
+        let mut count = 0;
+        while let Some(row) = rows.next().await {
+            let row = row.map_err(Self::query_failed)?;
+            on_row(rows.get_column_specs(), row)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// `--output-format sqlite`: streams the result of `query` into a new
+    /// table of the SQLite database at `path`. Statements other than SELECT
+    /// are executed and printed as usual.
+    pub async fn export_sqlite(&mut self, query: &str, path: &Path, options: &FormatOptions) -> CqlResult<()> {
+        let is_select = query.split_whitespace()
+            .next()
+            .is_some_and(|word| word.eq_ignore_ascii_case("select"));
+        if !is_select {
+            return self.execute_and_print(query, "table", options).await;
+        }
+
+        let mut writer: Option<SqliteWriter> = None;
+        self.stream(query, |col_specs, row| {
+            if options.grep.as_ref().is_some_and(|pattern| !row_matches(&row, pattern, options)) {
+                return Ok(());
+            }
+            let writer = match &mut writer {
+                Some(writer) => writer,
+                None => writer.insert(SqliteWriter::create(path, col_specs)?),
+            };
+            writer.insert(&row, options)
+        }).await?;
+
+        let Some(writer) = writer else {
+            eprintln!("{}", "The query returned no rows, no table created".bright_black());
+            return Ok(());
         };
+        let export = writer.finish()?;
+        println!(
+            "{} {} in {} ({})",
+            "Created table".green(),
+            export.table.cyan(),
+            path.display(),
+            format!("{} row(s)", export.rows).bright_black()
+        );
+        Ok(())
+    }
 
-        let formatted = format_result(&result, output_format)?;
-        println!("{}", formatted);
+    /// Runs `query` unpaged and captures its rows for `\diff`, keyed by the
+    /// primary key of the table the result comes from
+    pub async fn diff_snapshot(&self, query: &str, options: &FormatOptions) -> CqlResult<ResultSnapshot> {
+        let structured = self.run(query).await?;
+        structured.print_warnings();
+        info!("Fetched diff snapshot in {:.1} ms", structured.elapsed.as_secs_f64() * 1000.0);
+        let mut result = structured.into_query_result();
+
+        let Some(table) = result.col_specs.first().map(|spec| spec.table_spec.clone()) else {
+            return Err(CqlError::InvalidQuery("\\diff needs a query that returns rows".to_string()));
+        };
+        let primary_key = self.primary_key_columns(table.ks_name(), table.table_name()).await?;
+
+        // Sets and maps are compared by their text, so their order must be stable
+        sort_collections(&mut result);
+        let (headers, rows) = formatted_cells(&result, options);
+        ResultSnapshot::new(headers, rows, &primary_key)
+    }
+
+    /// Runs a read of `system_schema` at the schema consistency level
+    pub async fn query_schema(&self, statement: &str, values: impl SerializeRow) -> CqlResult<QueryResult> {
+        let mut query = Query::new(statement);
+        query.set_consistency(self.consistency_for(statement));
+        self.connection.session()
+            .query(query, values)
+            .await
+            .map_err(Self::query_failed)
+    }
+
+    /// Partition key columns followed by clustering columns, in key order
+    async fn primary_key_columns(&self, keyspace: &str, table: &str) -> CqlResult<Vec<String>> {
+        let mut query = Query::new(
+            "SELECT column_name, kind, position FROM system_schema.columns WHERE keyspace_name = ? AND table_name = ?",
+        );
+        query.set_consistency(self.consistency_for(&query.contents));
+        let result = self.connection.session()
+            .query(query, (keyspace, table))
+            .await
+            .map_err(Self::query_failed)?;
+
+        let mut columns: Vec<(u8, i32, String)> = result.rows_typed::<(String, String, i32)>()
+            .map_err(|e| CqlError::QueryError(format!("Failed to read schema of {}.{}: {}", keyspace, table, e)))?
+            .filter_map(|row| row.ok())
+            .filter_map(|(name, kind, position)| match kind.as_str() {
+                "partition_key" => Some((0, position, name)),
+                "clustering" => Some((1, position, name)),
+                _ => None,
+            })
+            .collect();
+        if columns.is_empty() {
+            return Err(CqlError::QueryError(format!("No primary key found for {}.{}", keyspace, table)));
+        }
+
+        columns.sort();
+        Ok(columns.into_iter().map(|(_, _, name)| name).collect())
+    }
+
+    /// Prepares `query` once and executes it `warmup` times unmeasured, then
+    /// `iterations` times sequentially, recording the latency of each run
+    pub async fn benchmark(&self, query: &str, iterations: usize, warmup: usize) -> CqlResult<BenchmarkStats> {
+        if iterations == 0 {
+            return Err(CqlError::InvalidQuery("Benchmark needs at least one iteration".to_string()));
+        }
+
+        self.check_allowed(query)?;
+        let statement = normalize_statement(query, self.strip_trailing_semicolon);
+        let session = self.connection.session();
+        let mut prepared = session.prepare(statement).await.map_err(Self::query_failed)?;
+        prepared.set_consistency(self.consistency);
+        prepared.set_serial_consistency(Some(self.serial_consistency));
+
+        for _ in 0..warmup {
+            self.execute_prepared(&mut prepared, &[]).await?;
+        }
+
+        let mut latencies = Vec::with_capacity(iterations);
+        let started = Instant::now();
+        for _ in 0..iterations {
+            let run = Instant::now();
+            self.execute_prepared(&mut prepared, &[]).await?;
+            latencies.push(run.elapsed());
+        }
+        let elapsed = started.elapsed();
+
+        latencies.sort();
+        Ok(BenchmarkStats { latencies, elapsed })
+    }
+
+    /// Executes a prepared statement that is reused across many executions,
+    /// preparing it again if the server no longer knows it
+    async fn execute_prepared(
+        &self,
+        prepared: &mut PreparedStatement,
+        values: &impl SerializeRow,
+    ) -> CqlResult<QueryResult> {
+        let session = self.connection.session();
+        retry_unprepared(
+            prepared,
+            |prepared| async move { session.execute(&prepared, values).await },
+            |stale| self.reprepare(stale),
+        )
+        .await
+        .map_err(Self::query_failed)
+    }
+
+    /// Runs independent statements concurrently with at most `concurrency`
+    /// in flight. Results are not printed; failures are reported as they
+    /// happen and summarized at the end together with the total runtime.
+    /// Statements may complete in any order, so callers must not rely on
+    /// one statement seeing the effects of another.
+    pub async fn execute_parallel(&self, statements: &[String], concurrency: usize, options: &FormatOptions) -> CqlResult<()> {
+        let started = Instant::now();
+
+        let failures = stream::iter(statements.iter().enumerate())
+            .map(|(index, statement)| async move {
+                self.check_allowed(statement).map_err(|e| (index, e))?;
+                self.execute(statement).await.map_err(|e| (index, e))
+            })
+            .buffer_unordered(concurrency.max(1))
+            .fold(0usize, |failures, outcome| async move {
+                match outcome {
+                    Ok(_) => failures,
+                    Err((index, e)) => {
+                        eprintln!("{} statement {}: {}", "Error:".red().bold(), index + 1, e);
+                        failures + 1
+                    }
+                }
+            })
+            .await;
+
+        let elapsed = started.elapsed();
+        if !options.no_footer {
+            println!(
+                "Executed {} statement(s) with concurrency {} in {:.3}s ({:.1} statements/s)",
+                statements.len().to_string().cyan(),
+                concurrency,
+                elapsed.as_secs_f64(),
+                statements.len() as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+            );
+        }
+
+        if failures > 0 {
+            return Err(CqlError::QueryError(format!(
+                "{} of {} statement(s) failed",
+                failures,
+                statements.len()
+            )));
+        }
 
         Ok(())
     }
 
+    /// Connects with `config` and switches to the new session. The current
+    /// session stays in use if the new connection cannot be established.
+    pub async fn reconnect(&mut self, config: ConnectionConfig) -> CqlResult<()> {
+        self.connection = ConnectionManager::connect(config).await?;
+        self.last_output = None;
+        self.prepared_cache().clear();
+        Ok(())
+    }
+
     pub fn connection(&self) -> &ConnectionManager {
         &self.connection
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    fn unprepared() -> QueryError {
+        QueryError::DbError(DbError::Unprepared { statement_id: Bytes::from_static(b"stale") }, "Unprepared".to_string())
+    }
+
+    /// Statement ids the server knows; executing another one fails as unprepared
+    async fn run(known: &RefCell<Vec<u32>>, executed: &RefCell<Vec<u32>>, id: u32) -> Result<u32, QueryError> {
+        executed.borrow_mut().push(id);
+        if known.borrow().contains(&id) { Ok(id) } else { Err(unprepared()) }
+    }
+
+    #[tokio::test]
+    async fn unprepared_statement_is_prepared_again_and_retried_once() {
+        let known = RefCell::new(vec![2]);
+        let executed = RefCell::new(Vec::new());
+        let prepares = Cell::new(0);
+        let mut prepared = 1;
+
+        let outcome = retry_unprepared(
+            &mut prepared,
+            |id| run(&known, &executed, id),
+            |stale| {
+                prepares.set(prepares.get() + 1);
+                async move { Ok(stale + 1) }
+            },
+        ).await;
+
+        assert_eq!(outcome.unwrap(), 2);
+        assert_eq!(prepared, 2, "the re-prepared statement replaces the stale one");
+        assert_eq!(*executed.borrow(), [1, 2]);
+        assert_eq!(prepares.get(), 1);
+
+        // Executed again, the replacement is used without preparing
+        let outcome = retry_unprepared(&mut prepared, |id| run(&known, &executed, id), |_| async { unreachable!() }).await;
+        assert_eq!(outcome.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn unprepared_error_after_the_retry_is_reported() {
+        let known = RefCell::new(Vec::new());
+        let executed = RefCell::new(Vec::new());
+        let mut prepared = 1;
+
+        let outcome = retry_unprepared(&mut prepared, |id| run(&known, &executed, id), |stale| async move { Ok(stale + 1) }).await;
+
+        assert!(matches!(outcome, Err(QueryError::DbError(DbError::Unprepared { .. }, _))));
+        assert_eq!(*executed.borrow(), [1, 2]);
+    }
+
+    #[tokio::test]
+    async fn other_errors_are_not_retried() {
+        let mut prepared = 1;
+        let attempts = Cell::new(0);
+
+        let outcome: Result<(), QueryError> = retry_unprepared(
+            &mut prepared,
+            |_| async {
+                attempts.set(attempts.get() + 1);
+                Err(QueryError::DbError(DbError::Overloaded, "Overloaded".to_string()))
+            },
+            |_| async { unreachable!() },
+        ).await;
+
+        assert!(outcome.is_err());
+        assert_eq!(attempts.get(), 1);
+        assert_eq!(prepared, 1);
+    }
+
+    #[test]
+    fn repl_and_file_statements_are_sent_the_same() {
+        let script = "SELECT * FROM users WHERE id = 1;\nINSERT INTO users (id, name) VALUES (2, 'a;b') ;\nTRUNCATE users;;\n";
+        // The REPL sends the lines of a statement joined by spaces, `;` included
+        let repl = [
+            "SELECT * FROM users WHERE id = 1;",
+            "INSERT INTO users (id, name) VALUES (2, 'a;b') ;",
+            "TRUNCATE users;;",
+        ];
+        let file = crate::statement::split_statements(script);
+        assert_eq!(file.len(), repl.len());
+
+        for (typed, split) in repl.iter().zip(&file) {
+            for strip in [true, false] {
+                assert_eq!(normalize_statement(typed, strip), normalize_statement(split, strip));
+            }
+        }
+        assert_eq!(normalize_statement(repl[1], true), "INSERT INTO users (id, name) VALUES (2, 'a;b')");
+        assert_eq!(normalize_statement(repl[2], false), "TRUNCATE users;");
+        assert_eq!(normalize_statement(&file[2], false), "TRUNCATE users;");
+    }
+}
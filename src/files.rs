@@ -0,0 +1,143 @@
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+/// Files ending in `.gz` are gzip-compressed on write and decompressed on read
+fn is_gzip(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gz"))
+}
+
+pub fn open_reader(path: impl AsRef<Path>) -> io::Result<Box<dyn Read>> {
+    let path = path.as_ref();
+    let file = BufReader::new(File::open(path)?);
+    if is_gzip(path) {
+        Ok(Box::new(MultiGzDecoder::new(file)))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+pub fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    let mut content = String::new();
+    open_reader(path)?.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+/// Reads a CQL script with CRLF and lone CR line endings turned into `\n`,
+/// so statement splitting, comments and line numbers in messages work the
+/// same for scripts saved on Windows
+pub fn read_script(path: impl AsRef<Path>) -> io::Result<String> {
+    read_to_string(path).map(normalize_line_endings)
+}
+
+pub fn normalize_line_endings(text: String) -> String {
+    if !text.contains('\r') {
+        return text;
+    }
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Writes `contents` to `path`, gzip-compressed for `.gz` paths. The file
+/// only appears once it is complete, see `write_atomic`.
+pub fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let path = path.as_ref();
+    write_atomic(path, |file| {
+        if is_gzip(path) {
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            encoder.write_all(contents.as_ref())?;
+            encoder.finish()?;
+        } else {
+            file.write_all(contents.as_ref())?;
+        }
+        Ok(())
+    })
+}
+
+/// Lets `write_contents` fill a temporary file next to `path` and renames it
+/// to `path` only when that succeeded, so anything watching `path` never sees
+/// a truncated file. On failure the temporary file is removed and an
+/// existing file at `path` is left untouched.
+pub fn write_atomic(path: &Path, write_contents: impl FnOnce(&mut File) -> io::Result<()>) -> io::Result<()> {
+    let file_name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a file path", path.display())))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = File::create(&temp_path).and_then(|mut file| {
+        write_contents(&mut file)?;
+        file.sync_all()
+    });
+    match result.and_then(|()| std::fs::rename(&temp_path, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = std::fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::statement::{check_script, split_statements};
+    use std::path::PathBuf;
+
+    /// A path in the system temp directory, unique per test and process
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("cqlrs-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn crlf_and_mixed_line_endings_split_like_unix_scripts() {
+        let unix = "-- create the table\nCREATE TABLE t (id int PRIMARY KEY);\n\nINSERT INTO t (id) VALUES (1); -- first\n-- done\nSELECT * FROM t;\n";
+        let crlf = unix.replace('\n', "\r\n");
+        let mixed = "-- create the table\r\nCREATE TABLE t (id int PRIMARY KEY);\r\n\rINSERT INTO t (id) VALUES (1); -- first\n-- done\rSELECT * FROM t;\r\n";
+
+        for (name, content) in [("crlf.cql", crlf.as_str()), ("mixed.cql", mixed)] {
+            let path = temp_path(name);
+            std::fs::write(&path, content).unwrap();
+            let script = read_script(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert!(!script.contains('\r'), "{}", name);
+            assert_eq!(split_statements(&script), split_statements(unix), "{}", name);
+            assert_eq!(split_statements(&script), [
+                "CREATE TABLE t (id int PRIMARY KEY)",
+                "INSERT INTO t (id) VALUES (1)",
+                "SELECT * FROM t",
+            ]);
+        }
+    }
+
+    #[test]
+    fn check_script_counts_crlf_lines_once() {
+        let script = normalize_line_endings("-- setup\r\nSELECT * FROM t;\r\n-- comment\r\nSELECT 'open FROM t;\r\n".to_string());
+        let (statements, problems) = check_script(&script);
+        assert_eq!(statements, 2);
+        assert!(!problems.is_empty());
+        assert!(problems.iter().all(|problem| problem.line == 4), "{:?}", problems);
+
+        let script = normalize_line_endings("SELECT * FROM t;\r-- comment\rSELECT * FROM t\r".to_string());
+        let (_, problems) = check_script(&script);
+        assert_eq!(problems.iter().map(|problem| problem.line).collect::<Vec<_>>(), [3]);
+    }
+
+    #[test]
+    fn gzipped_script_round_trips() {
+        let path = temp_path("x.cql.gz");
+        let script = "USE shop;\nSELECT * FROM users WHERE name = 'Zoë';\n";
+
+        write(&path, script).unwrap();
+        let raw = std::fs::read(&path).unwrap();
+        let read = read_script(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(&raw[..2], [0x1f, 0x8b], "written gzip-compressed");
+        assert_eq!(read.unwrap(), script);
+    }
+}
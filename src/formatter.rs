@@ -1,16 +1,232 @@
 use scylla::transport::query_result::QueryResult;
-use scylla::frame::response::result::CqlValue;
+use scylla::frame::response::result::{ColumnSpec, ColumnType, CqlValue, Row as CqlRow};
+use scylla::frame::value::{CqlDate, CqlDecimal, CqlDuration, CqlTime, CqlTimestamp, CqlVarint};
 use prettytable::{Table, Row, Cell, format};
 use colored::*;
 use crate::error::{CqlError, CqlResult};
+use crate::stats::StatsMode;
+use crate::statement::quote_identifier;
 use serde_json::{json, Value as JsonValue};
 use terminal_size::{Width, terminal_size};
+use regex::Regex;
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+use tracing::warn;
 
 #[derive(Debug, Clone)]
 pub enum OutputFormat {
     Table,
     Json,
+    /// `rows:` with one mapping per row and `count:`, like `Json`
+    Yaml,
+    /// One object per row, encoded like Cassandra's `SELECT JSON`
+    CqlJson,
     Csv,
+    /// One line per row, `{column}` placeholders replaced by the cell values
+    Template(String),
+    /// One `INSERT` statement per row, into the given `keyspace.table` or
+    /// the table the result comes from
+    Inserts(Option<String>),
+    /// A complete HTML document with the result as a styled table
+    Html,
+    /// The driver's own `{:#?}` of the column specs and every row, for
+    /// tracking down values the other formats render wrongly. Not listed in
+    /// `\help formats`.
+    Debug,
+}
+
+impl OutputFormat {
+    /// Maps a `--output-format`/`\format` name to a format; unknown names fall back to a table
+    pub fn from_name(name: &str) -> Self {
+        if let Some(template) = name.strip_prefix("template:") {
+            return OutputFormat::Template(template.to_string());
+        }
+        if let Some(table) = name.strip_prefix("inserts:") {
+            return OutputFormat::Inserts(Some(table.to_string()));
+        }
+        match name.to_lowercase().as_str() {
+            "json" => OutputFormat::Json,
+            "yaml" | "yml" => OutputFormat::Yaml,
+            "cql-json" | "cqljson" => OutputFormat::CqlJson,
+            "csv" => OutputFormat::Csv,
+            "inserts" => OutputFormat::Inserts(None),
+            "html" => OutputFormat::Html,
+            "debug" => OutputFormat::Debug,
+            _ => OutputFormat::Table,
+        }
+    }
+}
+
+/// Display options applied on top of the selected output format
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    pub grep: Option<Regex>,
+    pub csv_excel: bool,
+    pub colorize_values: bool,
+    pub row_numbers: bool,
+    /// Decimal places for float/double cells; `None` prints full precision
+    pub float_precision: Option<usize>,
+    /// Table width instead of the terminal width; `Some(0)` means unlimited
+    pub max_width: Option<usize>,
+    /// Per-column width cap instead of `DEFAULT_MAX_COL_WIDTH`; `Some(0)` means unlimited
+    pub max_col_width: Option<usize>,
+    /// Start every page of a paged table result with the column header
+    pub repeat_headers: bool,
+    /// Sort map entries by key and set elements by value before formatting
+    pub sort_collections: bool,
+    /// Leave out "N row(s) returned" and similar summary lines
+    pub no_footer: bool,
+    /// Which results get a footer with per-column statistics
+    pub stats: StatsMode,
+    /// Columns to show first, in this order, whatever the query selected
+    pub column_order: Vec<String>,
+    /// Leave out the columns `column_order` does not list instead of appending them
+    pub drop_unlisted_columns: bool,
+    /// Zone timestamps are shown in; `None` shows them in UTC
+    pub timezone: Option<DisplayTimeZone>,
+    /// Show timestamps as milliseconds since the epoch (a number in JSON)
+    pub epoch_timestamps: bool,
+    /// Sort table rows by this column and start each run of equal values with a header row
+    pub group_by: Option<String>,
+    /// Show table rows as blocks of `column | value` lines instead of a grid
+    pub expanded: bool,
+    /// Append the size of blob and text values to their cells in table and
+    /// expanded output
+    pub show_sizes: bool,
+    /// Leave out notices such as the hint that table cells were cut short
+    pub quiet: bool,
+}
+
+/// A `--timezone` other than UTC
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisplayTimeZone {
+    /// The zone of the machine cqlrs runs on
+    Local,
+    Named(chrono_tz::Tz),
+}
+
+impl DisplayTimeZone {
+    /// Parses an IANA zone name (`Europe/Berlin`) or `local`
+    pub fn parse(name: &str) -> CqlResult<Self> {
+        if name.eq_ignore_ascii_case("local") {
+            return Ok(DisplayTimeZone::Local);
+        }
+        name.parse::<chrono_tz::Tz>()
+            .map(DisplayTimeZone::Named)
+            .map_err(|_| CqlError::ConfigError(format!("Unknown time zone '{}', expected an IANA name like Europe/Berlin or 'local'", name)))
+    }
+
+    fn format(&self, timestamp: chrono::DateTime<chrono::Utc>, format: &str) -> String {
+        match self {
+            DisplayTimeZone::Local => timestamp.with_timezone(&chrono::Local).format(format).to_string(),
+            DisplayTimeZone::Named(zone) => timestamp.with_timezone(zone).format(format).to_string(),
+        }
+    }
+}
+
+/// Table width used by `--deterministic-output` unless `--max-width` is given
+pub const DETERMINISTIC_TABLE_WIDTH: usize = 120;
+
+/// Table columns wider than this are wrapped unless `--max-col-width` says otherwise
+pub const DEFAULT_MAX_COL_WIDTH: usize = 50;
+
+/// Orders the entries of every map and set (including nested ones) by their
+/// formatted text, so output does not depend on the order the server sent.
+/// Lists keep their order since it is part of the value.
+pub fn sort_collections(result: &mut QueryResult) {
+    fn sort_value(value: &mut CqlValue) {
+        let key = |v: &CqlValue| format_cql_value(&Some(v.clone()), &FormatOptions::default());
+        match value {
+            CqlValue::Set(elements) => {
+                elements.iter_mut().for_each(sort_value);
+                elements.sort_by_cached_key(key);
+            }
+            CqlValue::Map(entries) => {
+                for (k, v) in entries.iter_mut() {
+                    sort_value(k);
+                    sort_value(v);
+                }
+                entries.sort_by_cached_key(|(k, _)| key(k));
+            }
+            CqlValue::List(elements) => elements.iter_mut().for_each(sort_value),
+            CqlValue::Tuple(elements) => elements.iter_mut().flatten().for_each(sort_value),
+            CqlValue::UserDefinedType { fields, .. } => {
+                fields.iter_mut().filter_map(|(_, v)| v.as_mut()).for_each(sort_value)
+            }
+            _ => {}
+        }
+    }
+
+    for row in result.rows.iter_mut().flatten() {
+        row.columns.iter_mut().flatten().for_each(sort_value);
+    }
+}
+
+/// Moves the columns named in `options.column_order` to the front in that
+/// order, followed by the other columns in their original order (or without
+/// them with `drop_unlisted_columns`). Fails on names the result lacks.
+pub fn reorder_columns(result: &mut QueryResult, options: &FormatOptions) -> CqlResult<()> {
+    if options.column_order.is_empty() || result.col_specs.is_empty() {
+        return Ok(());
+    }
+
+    let mut order = Vec::with_capacity(result.col_specs.len());
+    for name in &options.column_order {
+        let index = result.col_specs.iter()
+            .position(|spec| spec.name == *name)
+            .ok_or_else(|| CqlError::InvalidQuery(format!(
+                "--order-columns: the result has no column '{}' (columns: {})",
+                name,
+                result.col_specs.iter().map(|spec| spec.name.as_str()).collect::<Vec<_>>().join(", ")
+            )))?;
+        if !order.contains(&index) {
+            order.push(index);
+        }
+    }
+    if !options.drop_unlisted_columns {
+        let unlisted: Vec<usize> = (0..result.col_specs.len()).filter(|index| !order.contains(index)).collect();
+        order.extend(unlisted);
+    }
+
+    result.col_specs = order.iter().map(|&index| result.col_specs[index].clone()).collect();
+    for row in result.rows.iter_mut().flatten() {
+        let mut columns: Vec<Option<Option<CqlValue>>> = std::mem::take(&mut row.columns).into_iter().map(Some).collect();
+        row.columns = order.iter().filter_map(|&index| columns.get_mut(index).and_then(Option::take)).collect();
+    }
+    Ok(())
+}
+
+/// Index of the `--group-by` column, or an error naming the columns there are
+fn group_column(col_specs: &[ColumnSpec], options: &FormatOptions) -> CqlResult<Option<usize>> {
+    let Some(name) = &options.group_by else {
+        return Ok(None);
+    };
+    col_specs.iter()
+        .position(|spec| spec.name == *name)
+        .map(Some)
+        .ok_or_else(|| CqlError::InvalidQuery(format!(
+            "--group-by: the result has no column '{}' (columns: {})",
+            name,
+            col_specs.iter().map(|spec| spec.name.as_str()).collect::<Vec<_>>().join(", ")
+        )))
+}
+
+/// Keeps only rows where at least one formatted cell matches `pattern`.
+/// Returns the number of rows that were removed.
+pub fn filter_rows(result: &mut QueryResult, pattern: &Regex, options: &FormatOptions) -> usize {
+    let rows = match result.rows {
+        Some(ref mut rows) => rows,
+        None => return 0,
+    };
+
+    let before = rows.len();
+    rows.retain(|row| row_matches(row, pattern, options));
+    before - rows.len()
+}
+
+/// Whether any cell of `row` matches `pattern` as it is displayed
+pub fn row_matches(row: &CqlRow, pattern: &Regex, options: &FormatOptions) -> bool {
+    row.columns.iter().any(|col| pattern.is_match(&format_cql_value(col, options)))
 }
 
 fn get_terminal_width() -> usize {
@@ -47,15 +263,363 @@ fn wrap_str(s: &str, max_width: usize) -> String {
     wrapped
 }
 
-pub fn format_result(result: &QueryResult, format: OutputFormat) -> CqlResult<String> {
+pub fn format_result(result: &QueryResult, format: OutputFormat, options: &FormatOptions) -> CqlResult<String> {
+    match format {
+        OutputFormat::Table => format_as_table(result, options),
+        OutputFormat::Json => format_as_json(result, options),
+        OutputFormat::Yaml => format_as_yaml(result, options),
+        OutputFormat::CqlJson => Ok(format_as_cql_json(result)),
+        OutputFormat::Csv => format_as_csv(result, options),
+        OutputFormat::Template(template) => format_as_template(result, &template, options),
+        OutputFormat::Html => Ok(format_as_html(result, options)),
+        OutputFormat::Inserts(table) => {
+            let rows: &[CqlRow] = result.rows.as_deref().unwrap_or(&[]);
+            let output = insert_statements(&result.col_specs, rows, table.as_deref())?;
+            Ok(output.strip_suffix('\n').unwrap_or(&output).to_string())
+        }
+        OutputFormat::Debug => {
+            let rows: &[CqlRow] = result.rows.as_deref().unwrap_or(&[]);
+            Ok(format!("col_specs: {:#?}\n{}", result.col_specs, debug_rows(rows, 0)).trim_end().to_string())
+        }
+    }
+}
+
+/// `{:#?}` of every row's values, numbered from `first`
+fn debug_rows(rows: &[CqlRow], first: usize) -> String {
+    rows.iter()
+        .enumerate()
+        .map(|(index, row)| format!("row {}: {:#?}\n", first + index, row.columns))
+        .collect()
+}
+
+/// Renders `rows` as replayable `INSERT` statements, one per line. NULL
+/// cells are left out rather than inserted, which would write tombstones.
+fn insert_statements(col_specs: &[ColumnSpec], rows: &[CqlRow], table: Option<&str>) -> CqlResult<String> {
+    let Some(first) = col_specs.first() else {
+        return Ok(String::new());
+    };
+    let table = match table {
+        Some(table) => table.to_string(),
+        None => format!(
+            "{}.{}",
+            cql_json_column_name(first.table_spec.ks_name()),
+            cql_json_column_name(first.table_spec.table_name())
+        ),
+    };
+
+    let mut output = String::new();
+    for row in rows {
+        let (columns, values): (Vec<String>, Vec<String>) = col_specs.iter()
+            .zip(&row.columns)
+            .filter_map(|(spec, value)| {
+                let value = value.as_ref().filter(|value| !matches!(value, CqlValue::Empty))?;
+                Some((cql_json_column_name(&spec.name), cql_literal(value)))
+            })
+            .unzip();
+        if columns.is_empty() {
+            continue;
+        }
+        output.push_str(&format!(
+            "INSERT INTO {} ({}) VALUES ({});\n",
+            table,
+            columns.join(", "),
+            values.join(", ")
+        ));
+    }
+    Ok(output)
+}
+
+/// CQL spells the non-finite floats `NaN`, `Infinity` and `-Infinity`
+fn float_literal(value: f64, display: String) -> String {
+    if value.is_infinite() {
+        if value > 0.0 { "Infinity".to_string() } else { "-Infinity".to_string() }
+    } else {
+        display
+    }
+}
+
+/// `value` as a CQL literal that can be used in a statement
+pub fn cql_literal(value: &CqlValue) -> String {
+    let quote = |s: &str| format!("'{}'", s.replace('\'', "''"));
+    let join = |values: Vec<String>| values.join(", ");
+    let literal_or_null = |value: &Option<CqlValue>| value.as_ref().map(cql_literal).unwrap_or_else(|| "null".to_string());
+
+    match value {
+        CqlValue::Ascii(s) | CqlValue::Text(s) => quote(s),
+        CqlValue::Boolean(b) => b.to_string(),
+        CqlValue::TinyInt(i) => i.to_string(),
+        CqlValue::SmallInt(i) => i.to_string(),
+        CqlValue::Int(i) => i.to_string(),
+        CqlValue::BigInt(i) => i.to_string(),
+        CqlValue::Counter(c) => c.0.to_string(),
+        CqlValue::Varint(v) => format_varint(v),
+        CqlValue::Decimal(d) => format_decimal(d),
+        CqlValue::Float(f) => float_literal(*f as f64, f.to_string()),
+        CqlValue::Double(f) => float_literal(*f, f.to_string()),
+        CqlValue::Uuid(u) => u.to_string(),
+        CqlValue::Timeuuid(u) => u.to_string(),
+        CqlValue::Inet(ip) => quote(&ip.to_string()),
+        CqlValue::Blob(bytes) => format_blob(bytes),
+        CqlValue::Date(d) => {
+            let date: Result<chrono::NaiveDate, _> = (*d).try_into();
+            match date {
+                Ok(date) => quote(&date.to_string()),
+                Err(_) => d.0.to_string(),
+            }
+        }
+        CqlValue::Time(t) => t.0.to_string(),
+        CqlValue::Timestamp(ts) => ts.0.to_string(),
+        CqlValue::Duration(d) => format_duration(d),
+        CqlValue::Empty => "null".to_string(),
+        CqlValue::List(values) => format!("[{}]", join(values.iter().map(cql_literal).collect())),
+        CqlValue::Set(values) => format!("{{{}}}", join(values.iter().map(cql_literal).collect())),
+        CqlValue::Map(entries) => format!(
+            "{{{}}}",
+            join(entries.iter().map(|(k, v)| format!("{}: {}", cql_literal(k), cql_literal(v))).collect())
+        ),
+        CqlValue::Tuple(values) => format!("({})", join(values.iter().map(literal_or_null).collect())),
+        CqlValue::UserDefinedType { fields, .. } => format!(
+            "{{{}}}",
+            join(fields.iter()
+                .map(|(name, v)| format!("{}: {}", cql_json_column_name(name), literal_or_null(v)))
+                .collect())
+        ),
+    }
+}
+
+enum TemplatePart {
+    Literal(String),
+    Column(usize),
+}
+
+/// Splits a `--template` into literal text and column references. `{{` and
+/// `}}` stand for literal braces; a name that is not a result column is an error.
+fn parse_template(template: &str, col_specs: &[ColumnSpec]) -> CqlResult<Vec<TemplatePart>> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => {
+                            return Err(CqlError::InvalidQuery(format!("Unclosed '{{' in template: {}", template)));
+                        }
+                    }
+                }
+
+                let name = name.trim();
+                let index = col_specs.iter().position(|spec| spec.name == name).ok_or_else(|| {
+                    let columns: Vec<&str> = col_specs.iter().map(|spec| spec.name.as_str()).collect();
+                    CqlError::InvalidQuery(format!(
+                        "Unknown column '{}' in template (result columns: {})",
+                        name,
+                        columns.join(", ")
+                    ))
+                })?;
+                if !literal.is_empty() {
+                    parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(TemplatePart::Column(index));
+            }
+            c => literal.push(c),
+        }
+    }
+
+    if !literal.is_empty() {
+        parts.push(TemplatePart::Literal(literal));
+    }
+    Ok(parts)
+}
+
+fn template_rows(col_specs: &[ColumnSpec], rows: &[CqlRow], template: &str, options: &FormatOptions) -> CqlResult<String> {
+    let parts = parse_template(template, col_specs)?;
+    let mut output = String::new();
+
+    for row in rows {
+        for part in &parts {
+            match part {
+                TemplatePart::Literal(text) => output.push_str(text),
+                TemplatePart::Column(index) => {
+                    let value = row.columns.get(*index).unwrap_or(&None);
+                    output.push_str(&format_cql_value(value, options));
+                }
+            }
+        }
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+fn format_as_template(result: &QueryResult, template: &str, options: &FormatOptions) -> CqlResult<String> {
+    let rows: &[CqlRow] = result.rows.as_deref().unwrap_or(&[]);
+    let output = template_rows(&result.col_specs, rows, template, options)?;
+    // Printed with println!, which adds the last newline back
+    Ok(output.strip_suffix('\n').unwrap_or(&output).to_string())
+}
+
+/// The CQL spelling of a column type, e.g. `map<text, frozen<list<int>>>`.
+/// Result metadata does not say whether a collection is frozen, so only
+/// nested collections are shown as frozen.
+pub fn cql_type_name(typ: &ColumnType) -> String {
+    fn nested(typ: &ColumnType) -> String {
+        match typ {
+            ColumnType::List(_) | ColumnType::Set(_) | ColumnType::Map(_, _) | ColumnType::UserDefinedType { .. } => {
+                format!("frozen<{}>", cql_type_name(typ))
+            }
+            _ => cql_type_name(typ),
+        }
+    }
+
+    match typ {
+        ColumnType::Custom(class) => format!("'{}'", class),
+        ColumnType::Ascii => "ascii".to_string(),
+        ColumnType::Boolean => "boolean".to_string(),
+        ColumnType::Blob => "blob".to_string(),
+        ColumnType::Counter => "counter".to_string(),
+        ColumnType::Date => "date".to_string(),
+        ColumnType::Decimal => "decimal".to_string(),
+        ColumnType::Double => "double".to_string(),
+        ColumnType::Duration => "duration".to_string(),
+        ColumnType::Float => "float".to_string(),
+        ColumnType::Int => "int".to_string(),
+        ColumnType::BigInt => "bigint".to_string(),
+        ColumnType::Text => "text".to_string(),
+        ColumnType::Timestamp => "timestamp".to_string(),
+        ColumnType::Inet => "inet".to_string(),
+        ColumnType::List(element) => format!("list<{}>", nested(element)),
+        ColumnType::Set(element) => format!("set<{}>", nested(element)),
+        ColumnType::Map(key, value) => format!("map<{}, {}>", nested(key), nested(value)),
+        ColumnType::UserDefinedType { type_name, .. } => type_name.clone(),
+        ColumnType::SmallInt => "smallint".to_string(),
+        ColumnType::TinyInt => "tinyint".to_string(),
+        ColumnType::Time => "time".to_string(),
+        ColumnType::Timeuuid => "timeuuid".to_string(),
+        ColumnType::Tuple(elements) => format!(
+            "tuple<{}>",
+            elements.iter().map(nested).collect::<Vec<_>>().join(", ")
+        ),
+        ColumnType::Uuid => "uuid".to_string(),
+        ColumnType::Varint => "varint".to_string(),
+    }
+}
+
+/// A `CREATE TABLE` statement for a table that could hold the rows of a
+/// result with these columns, for `\generate-ddl`. Only the column types
+/// come from the result metadata: the primary key is just the first column,
+/// counters become bigint and repeated column names get a numeric suffix, so
+/// the statement is a starting point and says so in a leading comment.
+pub fn speculative_ddl(table: &str, col_specs: &[ColumnSpec], query: &str) -> String {
+    let mut seen = HashSet::new();
+    let columns: Vec<(String, String)> = col_specs.iter()
+        .map(|spec| {
+            let mut name = spec.name.clone();
+            let mut suffix = 1;
+            while !seen.insert(name.clone()) {
+                suffix += 1;
+                name = format!("{}_{}", spec.name, suffix);
+            }
+            let cql_type = match spec.typ {
+                ColumnType::Counter => "bigint".to_string(),
+                _ => cql_type_name(&spec.typ),
+            };
+            (quote_identifier(&name), cql_type)
+        })
+        .collect();
+
+    let mut ddl = String::new();
+    ddl.push_str("-- Speculative DDL, review before use: column types are taken from the result of\n");
+    ddl.push_str(&format!("--   {}\n", query.split_whitespace().collect::<Vec<_>>().join(" ")));
+    ddl.push_str("-- and the primary key is a guess (the first column).\n");
+    ddl.push_str(&format!("CREATE TABLE {} (\n", table));
+    for (name, cql_type) in &columns {
+        ddl.push_str(&format!("    {} {},\n", name, cql_type));
+    }
+    ddl.push_str(&format!("    PRIMARY KEY ({})\n);", columns.first().map(|(name, _)| name.as_str()).unwrap_or("")));
+    ddl
+}
+
+/// Lists result columns (name, CQL type and the table they come from) in
+/// the given output format, for `--describe-result`
+pub fn format_column_specs(col_specs: &[ColumnSpec], format: OutputFormat) -> CqlResult<String> {
     match format {
-        OutputFormat::Table => format_as_table(result),
-        OutputFormat::Json => format_as_json(result),
-        OutputFormat::Csv => format_as_csv(result),
+        OutputFormat::Json | OutputFormat::CqlJson => {
+            let columns: Vec<JsonValue> = col_specs.iter()
+                .map(|spec| json!({
+                    "name": spec.name,
+                    "type": cql_type_name(&spec.typ),
+                    "keyspace": spec.table_spec.ks_name(),
+                    "table": spec.table_spec.table_name(),
+                }))
+                .collect();
+            serde_json::to_string_pretty(&json!({ "columns": columns }))
+                .map_err(|e| CqlError::QueryError(format!("JSON serialization error: {}", e)))
+        }
+        OutputFormat::Yaml => {
+            let columns: Vec<JsonValue> = col_specs.iter()
+                .map(|spec| json!({
+                    "name": spec.name,
+                    "type": cql_type_name(&spec.typ),
+                    "keyspace": spec.table_spec.ks_name(),
+                    "table": spec.table_spec.table_name(),
+                }))
+                .collect();
+            to_yaml(&json!({ "columns": columns }))
+        }
+        OutputFormat::Csv => {
+            let mut output = "name,type,keyspace,table\n".to_string();
+            for spec in col_specs {
+                output.push_str(&format!(
+                    "{},{},{},{}\n",
+                    escape_csv_value(&spec.name),
+                    escape_csv_value(&cql_type_name(&spec.typ)),
+                    escape_csv_value(spec.table_spec.ks_name()),
+                    escape_csv_value(spec.table_spec.table_name())
+                ));
+            }
+            Ok(output)
+        }
+        OutputFormat::Debug => Ok(format!("{:#?}", col_specs)),
+        // The template refers to result columns, not to this listing
+        OutputFormat::Table | OutputFormat::Template(_) | OutputFormat::Inserts(_) | OutputFormat::Html => {
+            if col_specs.is_empty() {
+                return Ok(format!("{}", "Statement returns no columns".yellow()));
+            }
+
+            let mut table = Table::new();
+            table.set_format(*format::consts::FORMAT_BOX_CHARS);
+            table.set_titles(Row::new(
+                ["Column", "Type", "Keyspace", "Table"].iter()
+                    .map(|title| Cell::new(title).style_spec("b"))
+                    .collect(),
+            ));
+            for spec in col_specs {
+                table.add_row(Row::new(vec![
+                    Cell::new(&spec.name),
+                    Cell::new(&cql_type_name(&spec.typ)),
+                    Cell::new(spec.table_spec.ks_name()),
+                    Cell::new(spec.table_spec.table_name()),
+                ]));
+            }
+            Ok(table.to_string())
+        }
     }
 }
 
-fn format_as_table(result: &QueryResult) -> CqlResult<String> {
+fn format_as_table(result: &QueryResult, options: &FormatOptions) -> CqlResult<String> {
     let rows = match result.rows {
         Some(ref rows) => rows,
         None => {
@@ -67,80 +631,306 @@ fn format_as_table(result: &QueryResult) -> CqlResult<String> {
         return Ok(format!("{}", "Empty result set".yellow()));
     }
 
+    if result.col_specs.is_empty() {
+        return Ok(format!("{}", "No columns in result".yellow()));
+    }
+
+    let mut output = if options.expanded {
+        render_expanded(&result.col_specs, rows, 0, options)
+    } else {
+        let group_by = group_column(&result.col_specs, options)?;
+        render_table(&result.col_specs, rows, 0, true, group_by, options)
+    };
+    if !options.no_footer {
+        output.push_str(&format!("\n{} row(s) returned\n", rows.len().to_string().cyan()));
+    }
+    
+    Ok(output)
+}
+
+/// Column names and the cell texts of every row, formatted as in a table
+/// but without wrapping, for consumers that lay the data out themselves
+pub fn formatted_cells(result: &QueryResult, options: &FormatOptions) -> (Vec<String>, Vec<Vec<String>>) {
+    let headers = result.col_specs.iter().map(|spec| spec.name.clone()).collect();
+    let rows = result.rows.as_deref()
+        .unwrap_or(&[])
+        .iter()
+        .map(|row| row.columns.iter().map(|col| format_cql_value(col, options)).collect())
+        .collect();
+    (headers, rows)
+}
+
+/// A cell of table or expanded output, with the value's size appended for
+/// blobs and text if `show_sizes` is set
+fn display_cell(value: &Option<CqlValue>, options: &FormatOptions) -> String {
+    let formatted = format_cql_value(value, options);
+    let size = match value {
+        Some(CqlValue::Blob(bytes)) if options.show_sizes => bytes.len(),
+        Some(CqlValue::Text(text) | CqlValue::Ascii(text)) if options.show_sizes => text.len(),
+        _ => return formatted,
+    };
+    format!("{} ({})", formatted, format_size(size))
+}
+
+fn format_size(bytes: usize) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+const TRUNCATION_NOTICE: &str =
+    "Some values were truncated; use \\expand or --expand to see full content.";
+
+/// `0x… (N bytes)` for a blob cell wider than `width`: wrapped hex of a
+/// large blob is not readable anyway
+fn elided_blob(cell: &str, value: &Option<CqlValue>, width: usize) -> Option<String> {
+    match value {
+        Some(CqlValue::Blob(bytes)) if cell.chars().count() > width => Some(format!("0x\u{2026} ({} bytes)", bytes.len())),
+        _ => None,
+    }
+}
+
+/// Renders `rows` as a box table. `row_offset` is the number of rows printed
+/// before (for paged results) so row numbers continue across pages. With
+/// `group_by`, rows are sorted by that column's formatted value and every
+/// group starts with a row spanning the table that shows the value.
+fn render_table(
+    col_specs: &[ColumnSpec],
+    rows: &[CqlRow],
+    row_offset: usize,
+    show_header: bool,
+    group_by: Option<usize>,
+    options: &FormatOptions,
+) -> String {
     let mut table = Table::new();
     table.set_format(*format::consts::FORMAT_BOX_CHARS);
 
-    let col_specs = &result.col_specs;
     let num_cols = col_specs.len();
-    
-    if num_cols == 0 {
-        return Ok(format!("{}", "No columns in result".yellow()));
-    }
 
-    let terminal_width = get_terminal_width();
+    let row_number_width = if options.row_numbers {
+        (row_offset + rows.len()).to_string().len()
+    } else {
+        0
+    };
+
+    let unlimited = |width: usize| if width == 0 { usize::MAX } else { width };
+    let mut terminal_width = options.max_width.map(unlimited).unwrap_or_else(get_terminal_width);
+    let col_width_cap = options.max_col_width.map(unlimited).unwrap_or(DEFAULT_MAX_COL_WIDTH);
+    if options.row_numbers {
+        terminal_width = terminal_width.saturating_sub(row_number_width + 3);
+    }
     let border_overhead = (num_cols * 3) + 1;
     let available_width = terminal_width.saturating_sub(border_overhead).max(num_cols);
-    
-    let max_col_width = available_width / num_cols;
-    let min_col_width = 3;
-    let col_width = max_col_width.max(min_col_width).min(50); 
 
     let mut data_rows: Vec<Vec<String>> = Vec::new();
-    let mut col_max_widths: Vec<usize> = vec![0; num_cols];
-    
-    for (i, spec) in col_specs.iter().enumerate() {
-        col_max_widths[i] = spec.name.len().min(col_width);
-    }
-    
+    let mut natural_widths: Vec<usize> = col_specs.iter()
+        .map(|spec| spec.name.chars().count().min(col_width_cap))
+        .collect();
+
     for row in rows {
         let row_data: Vec<String> = row.columns.iter()
-            .map(|col| format_cql_value(col))
+            .map(|col| display_cell(col, options))
             .collect();
         
         for (i, cell) in row_data.iter().enumerate() {
-            if i < col_max_widths.len() {
-                col_max_widths[i] = col_max_widths[i].max(cell.len().min(col_width));
+            if i < natural_widths.len() {
+                let shown = elided_blob(cell, &row.columns[i], col_width_cap);
+                let width = shown.as_deref().unwrap_or(cell).chars().count();
+                natural_widths[i] = natural_widths[i].max(width.min(col_width_cap));
             }
         }
         
         data_rows.push(row_data);
     }
-    
-    let total_width: usize = col_max_widths.iter().sum();
-    if total_width > available_width {
-        let scale = available_width as f64 / total_width as f64;
-        for width in &mut col_max_widths {
-            *width = ((*width as f64 * scale) as usize).max(min_col_width);
+
+    let col_max_widths = fit_column_widths(&natural_widths, available_width, MIN_COLUMN_WIDTH);
+
+    if show_header {
+        let mut header_cells: Vec<Cell> = Vec::with_capacity(num_cols + 1);
+        if options.row_numbers {
+            header_cells.push(Cell::new("#").style_spec("Fb"));
         }
+        header_cells.extend(col_specs.iter()
+            .enumerate()
+            .map(|(i, spec)| {
+                let wrapped = wrap_str(&spec.name, col_max_widths[i]);
+                Cell::new(&wrapped).style_spec("Fb")
+            }));
+        table.add_row(Row::new(header_cells));
     }
 
-    let header_cells: Vec<Cell> = col_specs.iter()
-        .enumerate()
-        .map(|(i, spec)| {
-            let wrapped = wrap_str(&spec.name, col_max_widths[i]);
-            Cell::new(&wrapped).style_spec("Fb")
-        })
-        .collect();
-    table.add_row(Row::new(header_cells));
+    let mut order: Vec<usize> = (0..rows.len()).collect();
+    if let Some(group) = group_by {
+        order.sort_by(|&a, &b| data_rows[a][group].cmp(&data_rows[b][group]));
+    }
+    let table_width = col_max_widths.iter().sum::<usize>()
+        + (num_cols - 1) * 3
+        + if options.row_numbers { row_number_width + 3 } else { 0 };
+    let mut current_group: Option<&str> = None;
+    let mut elided = false;
+
+    for (index, &row_index) in order.iter().enumerate() {
+        let (row, row_data) = (&rows[row_index], &data_rows[row_index]);
+        if let Some(group) = group_by {
+            let value = row_data[group].as_str();
+            if current_group != Some(value) {
+                current_group = Some(value);
+                let label = wrap_str(&format!("{} = {}", col_specs[group].name, value), table_width);
+                let span = num_cols + usize::from(options.row_numbers);
+                table.add_row(Row::new(vec![Cell::new(&label).style_spec("Fyb").with_hspan(span)]));
+            }
+        }
 
-    for row_data in data_rows {
-        let cells: Vec<Cell> = row_data.iter()
+        let mut cells: Vec<Cell> = Vec::with_capacity(num_cols + 1);
+        if options.row_numbers {
+            cells.push(Cell::new(&(row_offset + index + 1).to_string()).style_spec("r"));
+        }
+        cells.extend(row_data.iter()
             .enumerate()
             .map(|(i, cell)| {
-                let wrapped = wrap_str(cell, col_max_widths[i]);
-                Cell::new(&wrapped)
-            })
-            .collect();
+                let shown = elided_blob(cell, row.columns.get(i).unwrap_or(&None), col_max_widths[i]);
+                elided |= shown.is_some();
+                let wrapped = wrap_str(shown.as_deref().unwrap_or(cell), col_max_widths[i]);
+                if options.colorize_values {
+                    Cell::new(&colorize_value(&wrapped, row.columns.get(i).unwrap_or(&None)))
+                } else {
+                    Cell::new(&wrapped)
+                }
+            }));
         table.add_row(Row::new(cells));
     }
 
     let mut output = table.to_string();
-    output.push_str(&format!("\n{} row(s) returned\n", rows.len().to_string().cyan()));
-    
-    Ok(output)
+    if elided && !options.quiet {
+        output.push_str(&format!("{}\n", TRUNCATION_NOTICE.yellow()));
+    }
+    output
 }
 
-fn format_as_json(result: &QueryResult) -> CqlResult<String> {
+/// Renders `rows` like psql's `\x` or cqlsh's `EXPAND ON`: a `@ Row N`
+/// line per row, then one `column | value` line per column. Values are
+/// neither wrapped nor cut; further lines of multi-line values line up
+/// under the first.
+fn render_expanded(col_specs: &[ColumnSpec], rows: &[CqlRow], row_offset: usize, options: &FormatOptions) -> String {
+    let name_width = col_specs.iter().map(|spec| spec.name.chars().count()).max().unwrap_or(0);
+    let mut output = String::new();
+
+    for (index, row) in rows.iter().enumerate() {
+        let values: Vec<String> = row.columns.iter()
+            .map(|col| display_cell(col, options))
+            .collect();
+        let value_width = values.iter()
+            .flat_map(|value| value.lines())
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(get_terminal_width().saturating_sub(name_width + 3));
+
+        if row_offset + index > 0 {
+            output.push('\n');
+        }
+        output.push_str(&format!("{}\n", format!("@ Row {}", row_offset + index + 1).bold()));
+        output.push_str(&format!("-{}-+-{}\n", "-".repeat(name_width), "-".repeat(value_width)));
+
+        for ((spec, value), column) in col_specs.iter().zip(&values).zip(&row.columns) {
+            let mut lines = value.lines().map(|line| {
+                if options.colorize_values {
+                    colorize_value(line, column)
+                } else {
+                    line.to_string()
+                }
+            });
+            let name = format!("{:<width$}", spec.name, width = name_width);
+            output.push_str(&format!(" {} | {}\n", name.bold(), lines.next().unwrap_or_default()));
+            for line in lines {
+                output.push_str(&format!(" {:<width$} | {}\n", "", line, width = name_width));
+            }
+        }
+    }
+
+    output
+}
+
+/// Narrowest a column is shrunk to when the table does not fit
+const MIN_COLUMN_WIDTH: usize = 3;
+
+/// Fits columns of the given natural (content) widths into `available`
+/// characters. Columns keep their natural width if everything fits;
+/// otherwise the widest columns are cut down first, all to the same width,
+/// so narrow columns stay intact. No column gets narrower than `min` (the
+/// table may then exceed `available`).
+fn fit_column_widths(natural: &[usize], available: usize, min: usize) -> Vec<usize> {
+    if natural.iter().sum::<usize>() <= available {
+        return natural.to_vec();
+    }
+
+    // The largest width limit under which the columns fit
+    let fits = |limit: usize| natural.iter().map(|&w| w.min(limit)).sum::<usize>() <= available;
+    let mut low = min;
+    let mut high = natural.iter().copied().max().unwrap_or(min);
+    if !fits(low) {
+        return natural.iter().map(|&w| w.min(min)).collect();
+    }
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        if fits(mid) {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    // Hand out what is left below the limit to the cut columns, left to right
+    let mut widths: Vec<usize> = natural.iter().map(|&w| w.min(low)).collect();
+    let mut spare = available - widths.iter().sum::<usize>();
+    for (width, &natural) in widths.iter_mut().zip(natural) {
+        if spare == 0 {
+            break;
+        }
+        if natural > *width {
+            *width += 1;
+            spare -= 1;
+        }
+    }
+    widths
+}
+
+/// Colors already formatted cell text by the type of its value. Every line is
+/// colored separately so wrapped cells keep their color inside the table.
+/// `colored` itself turns this into a no-op for non-TTY output and NO_COLOR.
+fn colorize_value(text: &str, value: &Option<CqlValue>) -> String {
+    let paint = |line: &str| -> ColoredString {
+        match value {
+            None => line.bright_black(),
+            Some(CqlValue::Boolean(true)) => line.green(),
+            Some(CqlValue::Boolean(false)) => line.red(),
+            Some(CqlValue::Int(_))
+            | Some(CqlValue::BigInt(_))
+            | Some(CqlValue::SmallInt(_))
+            | Some(CqlValue::TinyInt(_))
+            | Some(CqlValue::Float(_))
+            | Some(CqlValue::Double(_))
+            | Some(CqlValue::Decimal(_))
+            | Some(CqlValue::Varint(_))
+            | Some(CqlValue::Counter(_)) => line.yellow(),
+            Some(CqlValue::Uuid(_))
+            | Some(CqlValue::Timeuuid(_))
+            | Some(CqlValue::Timestamp(_))
+            | Some(CqlValue::Date(_))
+            | Some(CqlValue::Time(_)) => line.cyan(),
+            Some(_) => line.normal(),
+        }
+    };
+
+    text.split('\n')
+        .map(|line| paint(line).to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn format_as_json(result: &QueryResult, options: &FormatOptions) -> CqlResult<String> {
     let rows = match result.rows {
         Some(ref rows) => rows,
         None => {
@@ -148,30 +938,229 @@ fn format_as_json(result: &QueryResult) -> CqlResult<String> {
         }
     };
 
-    let col_specs = &result.col_specs;
-
-    let mut json_rows = Vec::new();
-    
-    for row in rows {
-        let mut json_row = serde_json::Map::new();
-        for (i, col) in row.columns.iter().enumerate() {
-            let col_name = &col_specs[i].name;
-            let value = cql_value_to_json(col);
-            json_row.insert(col_name.clone(), value);
-        }
-        json_rows.push(JsonValue::Object(json_row));
-    }
+    let json_rows: Vec<JsonValue> = rows.iter()
+        .map(|row| json_row(&result.col_specs, row, options))
+        .collect();
 
     let result_json = json!({
         "rows": json_rows,
         "count": rows.len()
     });
 
-    Ok(serde_json::to_string_pretty(&result_json)
-        .map_err(|e| CqlError::QueryError(format!("JSON serialization error: {}", e)))?)
+    serde_json::to_string_pretty(&result_json)
+        .map_err(|e| CqlError::QueryError(format!("JSON serialization error: {}", e)))
+}
+
+/// Same document as `format_as_json`, as YAML: collections and UDTs become
+/// nested sequences and mappings, NULL becomes `null`
+fn format_as_yaml(result: &QueryResult, options: &FormatOptions) -> CqlResult<String> {
+    let rows: &[CqlRow] = result.rows.as_deref().unwrap_or(&[]);
+    let yaml_rows: Vec<JsonValue> = rows.iter()
+        .map(|row| json_row(&result.col_specs, row, options))
+        .collect();
+
+    to_yaml(&json!({
+        "rows": yaml_rows,
+        "count": rows.len()
+    }))
+}
+
+fn to_yaml(value: &JsonValue) -> CqlResult<String> {
+    serde_yaml::to_string(value)
+        .map(|yaml| yaml.trim_end().to_string())
+        .map_err(|e| CqlError::QueryError(format!("YAML serialization error: {}", e)))
+}
+
+pub fn json_row(col_specs: &[ColumnSpec], row: &CqlRow, options: &FormatOptions) -> JsonValue {
+    let mut json_row = serde_json::Map::new();
+    for (i, col) in row.columns.iter().enumerate() {
+        let col_name = &col_specs[i].name;
+        let value = cql_value_to_json(col, options);
+        json_row.insert(col_name.clone(), value);
+    }
+    JsonValue::Object(json_row)
+}
+
+fn format_as_cql_json(result: &QueryResult) -> String {
+    result.rows.as_deref()
+        .unwrap_or(&[])
+        .iter()
+        .map(|row| cql_json_row(&result.col_specs, row))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Renders a row the way Cassandra renders the `[json]` column of
+/// `SELECT JSON`: case-sensitive column names stay double-quoted, numbers
+/// (including varint and decimal) are exact JSON numbers and everything else
+/// uses the string encodings Cassandra accepts back in `INSERT JSON`.
+fn cql_json_row(col_specs: &[ColumnSpec], row: &CqlRow) -> String {
+    let fields: Vec<String> = col_specs.iter()
+        .zip(&row.columns)
+        .map(|(spec, value)| format!("{}: {}", json_string(&cql_json_column_name(&spec.name)), cql_json_value(value)))
+        .collect();
+    format!("{{{}}}", fields.join(", "))
+}
+
+fn cql_json_column_name(name: &str) -> String {
+    let mut chars = name.chars();
+    let unquoted = chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if unquoted {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+fn json_string(s: &str) -> String {
+    JsonValue::String(s.to_string()).to_string()
+}
+
+fn cql_json_value(value: &Option<CqlValue>) -> String {
+    let Some(value) = value else {
+        return "null".to_string();
+    };
+
+    let join = |values: Vec<String>| values.join(", ");
+    match value {
+        CqlValue::Ascii(s) | CqlValue::Text(s) => json_string(s),
+        CqlValue::Boolean(b) => b.to_string(),
+        CqlValue::TinyInt(i) => i.to_string(),
+        CqlValue::SmallInt(i) => i.to_string(),
+        CqlValue::Int(i) => i.to_string(),
+        CqlValue::BigInt(i) => i.to_string(),
+        CqlValue::Counter(c) => c.0.to_string(),
+        CqlValue::Varint(v) => format_varint(v),
+        CqlValue::Decimal(d) => format_decimal(d),
+        CqlValue::Float(f) => cql_json_float(f64::from(*f), f.to_string()),
+        CqlValue::Double(f) => cql_json_float(*f, f.to_string()),
+        CqlValue::Uuid(u) => json_string(&u.to_string()),
+        CqlValue::Timeuuid(u) => json_string(&u.to_string()),
+        CqlValue::Inet(ip) => json_string(&ip.to_string()),
+        CqlValue::Blob(bytes) => json_string(&format_blob(bytes)),
+        CqlValue::Date(d) => json_string(&format_date(d)),
+        CqlValue::Time(t) => {
+            let time: Result<chrono::NaiveTime, _> = (*t).try_into();
+            match time {
+                Ok(time) => json_string(&time.format("%H:%M:%S%.9f").to_string()),
+                Err(_) => t.0.to_string(),
+            }
+        }
+        CqlValue::Timestamp(ts) => {
+            let timestamp: Result<chrono::DateTime<chrono::Utc>, _> = (*ts).try_into();
+            match timestamp {
+                Ok(timestamp) => json_string(&timestamp.format("%Y-%m-%d %H:%M:%S%.3fZ").to_string()),
+                Err(_) => ts.0.to_string(),
+            }
+        }
+        CqlValue::Duration(d) => json_string(&format_duration(d)),
+        CqlValue::Empty => "null".to_string(),
+        CqlValue::List(values) | CqlValue::Set(values) => {
+            format!("[{}]", join(values.iter().map(|v| cql_json_value(&Some(v.clone()))).collect()))
+        }
+        CqlValue::Tuple(values) => {
+            format!("[{}]", join(values.iter().map(cql_json_value).collect()))
+        }
+        CqlValue::Map(entries) => {
+            // Map keys must be JSON strings, so non-string keys are quoted
+            // in their JSON form, e.g. {"1": ...} or {"[1, 2]": ...}
+            format!("{{{}}}", join(entries.iter().map(|(k, v)| {
+                let key = cql_json_value(&Some(k.clone()));
+                let key = if key.starts_with('"') { key } else { json_string(&key) };
+                format!("{}: {}", key, cql_json_value(&Some(v.clone())))
+            }).collect()))
+        }
+        CqlValue::UserDefinedType { fields, .. } => {
+            format!("{{{}}}", join(fields.iter().map(|(name, v)| {
+                format!("{}: {}", json_string(&cql_json_column_name(name)), cql_json_value(v))
+            }).collect()))
+        }
+    }
+}
+
+/// NaN and infinities have no JSON number form; they are written as the
+/// strings Cassandra accepts for them in `INSERT JSON`
+fn cql_json_float(value: f64, display: String) -> String {
+    if value.is_nan() {
+        json_string("NaN")
+    } else if value.is_infinite() {
+        json_string(if value > 0.0 { "Infinity" } else { "-Infinity" })
+    } else {
+        display
+    }
+}
+
+/// Styles of `--output-format html`, inline so the file can be mailed or opened as is
+const HTML_STYLE: &str = "body { font-family: monospace; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; vertical-align: top; white-space: pre-wrap; }
+th { background: #e8e8e8; }
+tbody tr:nth-child(even) { background: #f5f5f5; }
+td.null { color: #999; }
+p.footer { color: #666; }";
+
+fn format_as_html(result: &QueryResult, options: &FormatOptions) -> String {
+    let rows: &[CqlRow] = result.rows.as_deref().unwrap_or(&[]);
+    let mut output = html_header(&result.col_specs);
+    output.push_str(&html_rows(rows, options));
+    output.push_str(&html_footer(rows.len(), options));
+    output
+}
+
+/// Everything up to the first row: doctype, styles and the column names
+fn html_header(col_specs: &[ColumnSpec]) -> String {
+    let mut output = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>cqlrs result</title>\n<style>\n{}\n</style>\n</head>\n<body>\n<table>\n<thead>\n<tr>",
+        HTML_STYLE
+    );
+    for spec in col_specs {
+        output.push_str(&format!("<th>{}</th>", escape_html(&spec.name)));
+    }
+    output.push_str("</tr>\n</thead>\n<tbody>\n");
+    output
+}
+
+fn html_rows(rows: &[CqlRow], options: &FormatOptions) -> String {
+    let mut output = String::new();
+    for row in rows {
+        output.push_str("<tr>");
+        for value in &row.columns {
+            match value {
+                None => output.push_str("<td class=\"null\">NULL</td>"),
+                Some(_) => output.push_str(&format!("<td>{}</td>", escape_html(&format_cql_value(value, options)))),
+            }
+        }
+        output.push_str("</tr>\n");
+    }
+    output
+}
+
+fn html_footer(rows: usize, options: &FormatOptions) -> String {
+    let mut output = "</tbody>\n</table>\n".to_string();
+    if !options.no_footer {
+        output.push_str(&format!("<p class=\"footer\">{} row(s)</p>\n", rows));
+    }
+    output.push_str("</body>\n</html>");
+    output
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
 }
 
-fn format_as_csv(result: &QueryResult) -> CqlResult<String> {
+fn format_as_csv(result: &QueryResult, options: &FormatOptions) -> CqlResult<String> {
     let rows = match result.rows {
         Some(ref rows) => rows,
         None => {
@@ -179,91 +1168,589 @@ fn format_as_csv(result: &QueryResult) -> CqlResult<String> {
         }
     };
 
-    let col_specs = &result.col_specs;
+    let mut output = csv_header(&result.col_specs, options);
+    output.push_str(&csv_rows(rows, options));
 
+    Ok(output)
+}
+
+fn csv_line_ending(options: &FormatOptions) -> &'static str {
+    // Excel only detects UTF-8 reliably with a BOM and expects CRLF line endings
+    if options.csv_excel { "\r\n" } else { "\n" }
+}
+
+fn csv_header(col_specs: &[ColumnSpec], options: &FormatOptions) -> String {
     let mut output = String::new();
+    if options.csv_excel {
+        output.push('\u{feff}');
+    }
 
     let headers: Vec<String> = col_specs.iter()
         .map(|spec| spec.name.clone())
         .collect();
     output.push_str(&headers.join(","));
-    output.push('\n');
+    output.push_str(csv_line_ending(options));
+    output
+}
 
+fn csv_rows(rows: &[CqlRow], options: &FormatOptions) -> String {
+    let mut output = String::new();
     for row in rows {
         let values: Vec<String> = row.columns.iter()
-            .map(|col| escape_csv_value(&format_cql_value(col)))
+            .map(|col| escape_csv_value(&format_cql_value(col, options)))
             .collect();
         output.push_str(&values.join(","));
-        output.push('\n');
+        output.push_str(csv_line_ending(options));
     }
+    output
+}
 
-    Ok(output)
+/// Formats a result that is fetched page by page. Headers are emitted with
+/// the first page and the row count (or JSON closing) by `finish`, so the
+/// concatenated output matches what `format_result` would produce for CSV,
+/// JSON and HTML. Tables are rendered per page with the header on the first one,
+/// or on every page with `repeat_headers`.
+pub struct PagedFormatter {
+    format: OutputFormat,
+    pages: usize,
+    rows: usize,
 }
 
-fn format_cql_value(value: &Option<CqlValue>) -> String {
+impl PagedFormatter {
+    pub fn new(format: OutputFormat) -> Self {
+        Self { format, pages: 0, rows: 0 }
+    }
+
+    /// Rows formatted so far
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Pages formatted so far
+    pub fn pages(&self) -> usize {
+        self.pages
+    }
+
+    pub fn page(&mut self, result: &QueryResult, options: &FormatOptions) -> CqlResult<String> {
+        let rows: &[CqlRow] = result.rows.as_deref().unwrap_or(&[]);
+        let first_page = self.pages == 0;
+        let mut output = String::new();
+
+        match &self.format {
+            OutputFormat::Table => {
+                if !rows.is_empty() && !result.col_specs.is_empty() && options.expanded {
+                    output = render_expanded(&result.col_specs, rows, self.rows, options);
+                } else if !rows.is_empty() && !result.col_specs.is_empty() {
+                    let show_header = self.rows == 0 || options.repeat_headers;
+                    let group_by = group_column(&result.col_specs, options)?;
+                    output = render_table(&result.col_specs, rows, self.rows, show_header, group_by, options);
+                }
+            }
+            OutputFormat::Json => {
+                if first_page {
+                    output.push_str("{\n  \"rows\": [");
+                }
+                for (i, row) in rows.iter().enumerate() {
+                    if self.rows + i > 0 {
+                        output.push(',');
+                    }
+                    let pretty = serde_json::to_string_pretty(&json_row(&result.col_specs, row, options))
+                        .map_err(|e| CqlError::QueryError(format!("JSON serialization error: {}", e)))?;
+                    for line in pretty.lines() {
+                        output.push_str("\n    ");
+                        output.push_str(line);
+                    }
+                }
+            }
+            OutputFormat::Yaml => {
+                if self.rows == 0 && !rows.is_empty() {
+                    output.push_str("rows:\n");
+                }
+                let yaml_rows: Vec<JsonValue> = rows.iter()
+                    .map(|row| json_row(&result.col_specs, row, options))
+                    .collect();
+                if !yaml_rows.is_empty() {
+                    output.push_str(&to_yaml(&JsonValue::Array(yaml_rows))?);
+                    output.push('\n');
+                }
+            }
+            OutputFormat::CqlJson => {
+                for row in rows {
+                    output.push_str(&cql_json_row(&result.col_specs, row));
+                    output.push('\n');
+                }
+            }
+            OutputFormat::Csv => {
+                if first_page {
+                    output.push_str(&csv_header(&result.col_specs, options));
+                }
+                output.push_str(&csv_rows(rows, options));
+            }
+            OutputFormat::Template(template) => {
+                output.push_str(&template_rows(&result.col_specs, rows, template, options)?);
+            }
+            OutputFormat::Inserts(table) => {
+                output.push_str(&insert_statements(&result.col_specs, rows, table.as_deref())?);
+            }
+            OutputFormat::Html => {
+                if first_page {
+                    output.push_str(&html_header(&result.col_specs));
+                }
+                output.push_str(&html_rows(rows, options));
+            }
+            OutputFormat::Debug => {
+                if first_page {
+                    output.push_str(&format!("col_specs: {:#?}\n", result.col_specs));
+                }
+                output.push_str(&debug_rows(rows, self.rows));
+            }
+        }
+
+        self.pages += 1;
+        self.rows += rows.len();
+        Ok(output)
+    }
+
+    pub fn finish(self, options: &FormatOptions) -> String {
+        match self.format {
+            OutputFormat::Table if options.no_footer => String::new(),
+            OutputFormat::Table if self.rows == 0 => format!("{}", "Empty result set".yellow()),
+            OutputFormat::Table => format!("\n{} row(s) returned\n", self.rows.to_string().cyan()),
+            OutputFormat::Json => {
+                let close = if self.rows == 0 { "]" } else { "\n  ]" };
+                format!("{},\n  \"count\": {}\n}}", close, self.rows)
+            }
+            OutputFormat::Yaml if self.rows == 0 => "rows: []\ncount: 0".to_string(),
+            OutputFormat::Yaml => format!("count: {}", self.rows),
+            OutputFormat::Html => html_footer(self.rows, options),
+            OutputFormat::CqlJson
+            | OutputFormat::Csv
+            | OutputFormat::Template(_)
+            | OutputFormat::Inserts(_)
+            | OutputFormat::Debug => String::new(),
+        }
+    }
+}
+
+fn format_cql_value(value: &Option<CqlValue>, options: &FormatOptions) -> String {
     match value {
         None => "NULL".to_string(),
         Some(cql_val) => match cql_val {
             CqlValue::Ascii(s) | CqlValue::Text(s) => s.clone(),
             CqlValue::Boolean(b) => b.to_string(),
+            CqlValue::TinyInt(i) => i.to_string(),
+            CqlValue::SmallInt(i) => i.to_string(),
             CqlValue::Int(i) => i.to_string(),
             CqlValue::BigInt(i) => i.to_string(),
-            CqlValue::Float(f) => f.to_string(),
-            CqlValue::Double(f) => f.to_string(),
+            CqlValue::Counter(c) => c.0.to_string(),
+            CqlValue::Float(f) => format_float(*f, options.float_precision),
+            CqlValue::Double(f) => format_float(*f, options.float_precision),
+            // Exact digits, --float-precision does not round decimals
+            CqlValue::Decimal(d) => format_decimal(d),
+            CqlValue::Varint(v) => format_varint(v),
             CqlValue::Uuid(u) => u.to_string(),
             CqlValue::Timeuuid(u) => u.to_string(),
-            CqlValue::Timestamp(ts) => format!("{:?}", ts),
+            CqlValue::Timestamp(ts) if options.epoch_timestamps => ts.0.to_string(),
+            CqlValue::Timestamp(ts) => format_timestamp(ts, "%Y-%m-%d %H:%M:%S%.3f%z", options.timezone),
+            CqlValue::Date(d) => format_date(d),
+            CqlValue::Time(t) => format_time(t),
+            CqlValue::Duration(d) => format_duration(d),
+            CqlValue::Inet(addr) => addr.to_string(),
             CqlValue::List(list) => format!("[{}]", list.iter()
-                .map(|v| format_cql_value(&Some(v.clone())))
+                .map(|v| format_cql_value(&Some(v.clone()), options))
                 .collect::<Vec<_>>()
                 .join(", ")),
             CqlValue::Set(set) => format!("{{{}}}", set.iter()
-                .map(|v| format_cql_value(&Some(v.clone())))
+                .map(|v| format_cql_value(&Some(v.clone()), options))
                 .collect::<Vec<_>>()
                 .join(", ")),
             CqlValue::Map(map) => format!("{{{}}}", map.iter()
                 .map(|(k, v)| format!("{}: {}", 
-                    format_cql_value(&Some(k.clone())), 
-                    format_cql_value(&Some(v.clone()))))
+                    format_cql_value(&Some(k.clone()), options), 
+                    format_cql_value(&Some(v.clone()), options)))
+                .collect::<Vec<_>>()
+                .join(", ")),
+            CqlValue::Tuple(fields) => format!("({})", fields.iter()
+                .map(|v| format_cql_value(v, options))
                 .collect::<Vec<_>>()
                 .join(", ")),
-            _ => format!("{:?}", cql_val),
+            CqlValue::UserDefinedType { fields, .. } => format!("{{{}}}", fields.iter()
+                .map(|(name, v)| format!("{}: {}", name, format_cql_value(v, options)))
+                .collect::<Vec<_>>()
+                .join(", ")),
+            CqlValue::Blob(bytes) => format_blob(bytes),
+            CqlValue::Empty => String::new(),
+            // Every type of the current driver has an arm above; this one
+            // catches those a driver upgrade adds
+            #[allow(unreachable_patterns)]
+            _ => unsupported_value(cql_val),
         }
     }
 }
 
-fn cql_value_to_json(value: &Option<CqlValue>) -> JsonValue {
+/// Placeholder for values of types the formatters do not know yet (added
+/// by a newer driver), instead of the driver's Debug output. Each such type
+/// is reported once per run.
+fn unsupported_value(value: &CqlValue) -> String {
+    static REPORTED: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+
+    let debug = format!("{:?}", value);
+    let variant = debug.split(|c: char| !c.is_alphanumeric()).next().unwrap_or_default();
+    let newly_seen = REPORTED.get_or_init(Default::default)
+        .lock()
+        .map(|mut reported| reported.insert(variant.to_string()))
+        .unwrap_or(false);
+    if newly_seen {
+        warn!("No formatting for CQL values of type {}, showing a placeholder", variant);
+    }
+    format!("<unsupported type: {}>", variant)
+}
+
+pub fn cql_value_to_json(value: &Option<CqlValue>, options: &FormatOptions) -> JsonValue {
     match value {
         None => JsonValue::Null,
         Some(cql_val) => match cql_val {
             CqlValue::Ascii(s) | CqlValue::Text(s) => JsonValue::String(s.clone()),
             CqlValue::Boolean(b) => JsonValue::Bool(*b),
+            CqlValue::TinyInt(i) => json!(*i),
+            CqlValue::SmallInt(i) => json!(*i),
             CqlValue::Int(i) => json!(*i),
             CqlValue::BigInt(i) => json!(*i),
+            CqlValue::Counter(c) => json!(c.0),
             CqlValue::Float(f) => json!(*f),
             CqlValue::Double(f) => json!(*f),
             CqlValue::Uuid(u) => JsonValue::String(u.to_string()),
             CqlValue::Timeuuid(u) => JsonValue::String(u.to_string()),
-            CqlValue::Timestamp(ts) => json!(format!("{:?}", ts)),
+            // ISO-8601, in UTC unless a zone was chosen explicitly
+            CqlValue::Timestamp(ts) if options.epoch_timestamps => json!(ts.0),
+            CqlValue::Timestamp(ts) => JsonValue::String(format_timestamp(ts, "%Y-%m-%dT%H:%M:%S%.3f%:z", options.timezone)),
+            CqlValue::Decimal(d) => JsonValue::String(format_decimal(d)),
+            CqlValue::Varint(v) => {
+                let digits = format_varint(v);
+                match digits.parse::<i64>() {
+                    Ok(n) => json!(n),
+                    Err(_) => JsonValue::String(digits),
+                }
+            }
+            CqlValue::Inet(addr) => JsonValue::String(addr.to_string()),
+            CqlValue::Date(d) => JsonValue::String(format_date(d)),
+            CqlValue::Time(t) => JsonValue::String(format_time(t)),
+            CqlValue::Duration(d) => JsonValue::String(format_duration(d)),
+            CqlValue::Blob(bytes) => JsonValue::String(format_blob(bytes)),
             CqlValue::List(list) => JsonValue::Array(
                 list.iter()
-                    .map(|v| cql_value_to_json(&Some(v.clone())))
+                    .map(|v| cql_value_to_json(&Some(v.clone()), options))
                     .collect()
             ),
             CqlValue::Set(set) => JsonValue::Array(
                 set.iter()
-                    .map(|v| cql_value_to_json(&Some(v.clone())))
+                    .map(|v| cql_value_to_json(&Some(v.clone()), options))
+                    .collect()
+            ),
+            CqlValue::Map(map) => {
+                let string_keyed = map.iter()
+                    .all(|(k, _)| matches!(k, CqlValue::Ascii(_) | CqlValue::Text(_)));
+
+                if string_keyed {
+                    let mut object = serde_json::Map::new();
+                    for (k, v) in map {
+                        if let JsonValue::String(key) = cql_value_to_json(&Some(k.clone()), options) {
+                            object.insert(key, cql_value_to_json(&Some(v.clone()), options));
+                        }
+                    }
+                    JsonValue::Object(object)
+                } else {
+                    JsonValue::Array(
+                        map.iter()
+                            .map(|(k, v)| json!({
+                                "key": cql_value_to_json(&Some(k.clone()), options),
+                                "value": cql_value_to_json(&Some(v.clone()), options)
+                            }))
+                            .collect()
+                    )
+                }
+            }
+            CqlValue::Tuple(fields) => JsonValue::Array(
+                fields.iter()
+                    .map(|value| cql_value_to_json(value, options))
                     .collect()
             ),
-            _ => JsonValue::String(format!("{:?}", cql_val)),
+            CqlValue::UserDefinedType { fields, .. } => {
+                let mut object = serde_json::Map::new();
+                for (name, value) in fields {
+                    object.insert(name.clone(), cql_value_to_json(value, options));
+                }
+                JsonValue::Object(object)
+            }
+            CqlValue::Empty => JsonValue::Null,
+            #[allow(unreachable_patterns)]
+            _ => JsonValue::String(unsupported_value(cql_val)),
         }
     }
 }
 
 fn escape_csv_value(value: &str) -> String {
-    if value.contains(',') || value.contains('"') || value.contains('\n') {
+    if value.contains(',') || value.contains('"') || value.contains('\n') || value.contains('\r') {
         format!("\"{}\"", value.replace('"', "\"\""))
     } else {
         value.to_string()
     }
 }
+
+fn format_float<F: std::fmt::Display>(value: F, precision: Option<usize>) -> String {
+    match precision {
+        Some(places) => format!("{:.*}", places, value),
+        None => value.to_string(),
+    }
+}
+
+fn format_decimal(value: &CqlDecimal) -> String {
+    bigdecimal::BigDecimal::from(value.clone()).to_string()
+}
+
+fn format_varint(value: &CqlVarint) -> String {
+    num_bigint::BigInt::from(value.clone()).to_string()
+}
+
+/// `value` formatted with chrono's `format` in `zone` (UTC for `None`, as
+/// `Z` when the format ends in `%:z`). Timestamps outside chrono's range are
+/// shown as milliseconds since the epoch.
+fn format_timestamp(value: &CqlTimestamp, format: &str, zone: Option<DisplayTimeZone>) -> String {
+    let timestamp: Result<chrono::DateTime<chrono::Utc>, _> = (*value).try_into();
+    match (timestamp, zone) {
+        (Ok(timestamp), Some(zone)) => zone.format(timestamp, format),
+        (Ok(timestamp), None) => match format.strip_suffix("%:z") {
+            Some(format) => format!("{}Z", timestamp.format(format)),
+            None => timestamp.format(format).to_string(),
+        },
+        (Err(_), _) => value.0.to_string(),
+    }
+}
+
+fn format_date(value: &CqlDate) -> String {
+    let date: Result<chrono::NaiveDate, _> = (*value).try_into();
+    match date {
+        Ok(date) => date.to_string(),
+        // Outside chrono's range: show days relative to the unix epoch like cqlsh does
+        Err(_) => (value.0 as i64 - (1i64 << 31)).to_string(),
+    }
+}
+
+/// `HH:MM:SS.fff`, always with milliseconds
+fn format_time(value: &CqlTime) -> String {
+    let time: Result<chrono::NaiveTime, _> = (*value).try_into();
+    match time {
+        Ok(time) => time.format("%H:%M:%S%.3f").to_string(),
+        Err(_) => value.0.to_string(),
+    }
+}
+
+fn format_duration(value: &CqlDuration) -> String {
+    if value.months == 0 && value.days == 0 && value.nanoseconds == 0 {
+        return "0s".to_string();
+    }
+
+    let mut output = String::new();
+    if value.months < 0 || value.days < 0 || value.nanoseconds < 0 {
+        output.push('-');
+    }
+
+    let months = value.months.unsigned_abs();
+    let days = value.days.unsigned_abs();
+    let mut nanos = value.nanoseconds.unsigned_abs();
+
+    let mut push_unit = |amount: u64, unit: &str| {
+        if amount > 0 {
+            output.push_str(&format!("{}{}", amount, unit));
+        }
+    };
+
+    push_unit((months / 12) as u64, "y");
+    push_unit((months % 12) as u64, "mo");
+    push_unit(days as u64, "d");
+
+    for (unit_nanos, unit) in [
+        (3_600_000_000_000u64, "h"),
+        (60_000_000_000, "m"),
+        (1_000_000_000, "s"),
+        (1_000_000, "ms"),
+        (1_000, "us"),
+        (1, "ns"),
+    ] {
+        push_unit(nanos / unit_nanos, unit);
+        nanos %= unit_nanos;
+    }
+
+    output
+}
+
+fn format_blob(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(2 + bytes.len() * 2);
+    hex.push_str("0x");
+    for byte in bytes {
+        hex.push_str(&format!("{:02x}", byte));
+    }
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scylla::frame::response::result::TableSpec;
+
+    fn column(name: &str, typ: ColumnType) -> ColumnSpec {
+        ColumnSpec { table_spec: TableSpec::owned("ks".to_string(), "t".to_string()), name: name.to_string(), typ }
+    }
+
+    fn row(values: Vec<Option<CqlValue>>) -> CqlRow {
+        CqlRow { columns: values }
+    }
+
+    #[test]
+    fn large_blob_column_is_as_wide_as_its_elided_text() {
+        let col_specs = [column("id", ColumnType::Int), column("data", ColumnType::Blob)];
+        let rows = [row(vec![Some(CqlValue::Int(1)), Some(CqlValue::Blob(vec![0xab; 4096]))])];
+        let options = FormatOptions { max_width: Some(200), quiet: true, ..Default::default() };
+
+        let table = render_table(&col_specs, &rows, 0, true, None, &options);
+        let widest = table.lines().map(|line| line.chars().count()).max().unwrap();
+        assert!(table.contains("0x\u{2026} (4096 bytes)"));
+        assert_eq!(widest, "│ id │ 0x… (4096 bytes) │".chars().count());
+    }
+
+    #[test]
+    fn truncation_notice_follows_tables_with_elided_cells_unless_quiet() {
+        let col_specs = [column("data", ColumnType::Blob)];
+        let small = [row(vec![Some(CqlValue::Blob(vec![1, 2]))])];
+        let large = [row(vec![Some(CqlValue::Blob(vec![0xab; 100]))])];
+        let options = FormatOptions { max_width: Some(80), ..Default::default() };
+        let quiet = FormatOptions { quiet: true, ..options.clone() };
+
+        assert!(!render_table(&col_specs, &small, 0, true, None, &options).contains(TRUNCATION_NOTICE));
+        let table = render_table(&col_specs, &large, 0, true, None, &options);
+        assert!(table.trim_end().ends_with(&TRUNCATION_NOTICE.yellow().to_string()));
+        assert!(!render_table(&col_specs, &large, 0, true, None, &quiet).contains(TRUNCATION_NOTICE));
+    }
+
+    #[test]
+    fn fit_column_widths_keeps_natural_widths_that_fit() {
+        assert_eq!(fit_column_widths(&[5, 10, 20], 35, 3), [5, 10, 20]);
+        assert_eq!(fit_column_widths(&[5, 10, 20], 100, 3), [5, 10, 20]);
+    }
+
+    #[test]
+    fn fit_column_widths_cuts_one_wide_column_and_keeps_narrow_ones() {
+        assert_eq!(fit_column_widths(&[4, 200, 6, 8], 60, 3), [4, 42, 6, 8]);
+        // Proportional shrinking would have cut the narrow columns as well
+        assert_eq!(fit_column_widths(&[2, 3, 100], 20, 3), [2, 3, 15]);
+    }
+
+    #[test]
+    fn fit_column_widths_cuts_the_widest_columns_to_the_same_width() {
+        assert_eq!(fit_column_widths(&[10, 80, 60, 5], 75, 3), [10, 30, 30, 5]);
+        // Space left below the common width goes to the first cut columns
+        assert_eq!(fit_column_widths(&[10, 80, 60, 5], 76, 3), [10, 31, 30, 5]);
+    }
+
+    #[test]
+    fn fit_column_widths_never_goes_below_the_minimum() {
+        assert_eq!(fit_column_widths(&[10, 20, 30], 6, 3), [3, 3, 3]);
+        assert_eq!(fit_column_widths(&[2, 20, 30], 6, 3), [2, 3, 3]);
+    }
+
+    fn result(col_specs: Vec<ColumnSpec>, rows: Vec<CqlRow>) -> QueryResult {
+        let mut result = QueryResult::default();
+        result.col_specs = col_specs;
+        result.rows = Some(rows);
+        result
+    }
+
+    fn users(ids: std::ops::Range<i32>) -> QueryResult {
+        result(
+            vec![column("id", ColumnType::Int), column("name", ColumnType::Text)],
+            ids.map(|id| row(vec![Some(CqlValue::Int(id)), Some(CqlValue::Text(format!("user, {}", id)))])).collect(),
+        )
+    }
+
+    #[test]
+    fn csv_excel_starts_with_one_bom_and_uses_crlf() {
+        let excel = FormatOptions { csv_excel: true, ..Default::default() };
+        let csv = format_result(&users(1..3), OutputFormat::Csv, &excel).unwrap();
+        assert_eq!(csv, "\u{feff}id,name\r\n1,\"user, 1\"\r\n2,\"user, 2\"\r\n");
+
+        let plain = format_result(&users(1..3), OutputFormat::Csv, &FormatOptions::default()).unwrap();
+        assert_eq!(plain, "id,name\n1,\"user, 1\"\n2,\"user, 2\"\n");
+    }
+
+    #[test]
+    fn csv_excel_bom_is_written_once_across_pages() {
+        let excel = FormatOptions { csv_excel: true, ..Default::default() };
+        let mut paged = PagedFormatter::new(OutputFormat::Csv);
+        let mut output = String::new();
+        for page in [users(1..3), users(3..5), users(5..6)] {
+            output.push_str(&paged.page(&page, &excel).unwrap());
+        }
+        output.push_str(&paged.finish(&excel));
+
+        assert!(output.starts_with('\u{feff}'));
+        assert_eq!(output.matches('\u{feff}').count(), 1);
+        assert_eq!(output, format_result(&users(1..6), OutputFormat::Csv, &excel).unwrap());
+    }
+
+    #[test]
+    fn time_values_are_shown_with_milliseconds() {
+        assert_eq!(format_time(&CqlTime(8 * 3_600_000_000_000 + 30 * 60_000_000_000)), "08:30:00.000");
+        assert_eq!(format_time(&CqlTime(45_296_123_456_789)), "12:34:56.123");
+    }
+
+    #[test]
+    fn smallint_tinyint_and_duration_values_are_formatted_plainly() {
+        let duration = CqlDuration { months: 14, days: 3, nanoseconds: 3_723_000_000_500 };
+        let values = result(
+            vec![
+                column("small", ColumnType::SmallInt),
+                column("tiny", ColumnType::TinyInt),
+                column("took", ColumnType::Duration),
+            ],
+            vec![
+                row(vec![Some(CqlValue::SmallInt(-32768)), Some(CqlValue::TinyInt(127)), Some(CqlValue::Duration(duration))]),
+                row(vec![
+                    Some(CqlValue::SmallInt(5)),
+                    Some(CqlValue::TinyInt(-1)),
+                    Some(CqlValue::Duration(CqlDuration { months: 0, days: -2, nanoseconds: 0 })),
+                ]),
+            ],
+        );
+        let options = FormatOptions::default();
+
+        let csv = format_result(&values, OutputFormat::Csv, &options).unwrap();
+        assert_eq!(csv, "small,tiny,took\n-32768,127,1y2mo3d1h2m3s500ns\n5,-1,-2d\n");
+
+        let rows: Vec<JsonValue> = values.rows.as_deref().unwrap().iter()
+            .map(|row| json_row(&values.col_specs, row, &options))
+            .collect();
+        assert_eq!(rows, [
+            json!({"small": -32768, "tiny": 127, "took": "1y2mo3d1h2m3s500ns"}),
+            json!({"small": 5, "tiny": -1, "took": "-2d"}),
+        ]);
+    }
+
+    #[test]
+    fn text_with_replacement_characters_is_formatted_in_every_format() {
+        let lossy = String::from_utf8_lossy(&[b'a', 0xff, 0xfe, b'b']).into_owned();
+        assert_eq!(lossy, "a\u{fffd}\u{fffd}b");
+        let values = result(
+            vec![column("name", ColumnType::Text), column("code", ColumnType::Ascii)],
+            vec![row(vec![Some(CqlValue::Text(lossy.clone())), Some(CqlValue::Ascii("\u{fffd}".to_string()))])],
+        );
+        let options = FormatOptions { max_width: Some(80), ..Default::default() };
+
+        let csv = format_result(&values, OutputFormat::Csv, &options).unwrap();
+        assert_eq!(csv, "name,code\na\u{fffd}\u{fffd}b,\u{fffd}\n");
+        let json = format_result(&values, OutputFormat::Json, &options).unwrap();
+        let parsed: JsonValue = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["rows"][0]["name"], json!(lossy));
+        let table = format_result(&values, OutputFormat::Table, &options).unwrap();
+        assert!(table.contains("a\u{fffd}\u{fffd}b"));
+    }
+}
@@ -1,17 +1,28 @@
 mod cli;
+mod config;
 mod connection;
 mod executor;
 mod formatter;
 mod repl;
 mod error;
+mod files;
+mod statement;
+mod params;
+mod browse;
+mod diff;
+mod output;
+mod result;
+mod serve;
+mod stats;
+mod proxy;
+mod sqlite;
+mod schema;
 
-use anyhow::Result;
 use clap::Parser;
 use cli::Cli;
-use tracing_subscriber;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
     tracing_subscriber::fmt()
         .with_env_filter(
             tracing_subscriber::EnvFilter::try_from_default_env()
@@ -19,7 +30,18 @@ async fn main() -> Result<()> {
         )
         .init();
 
-    let cli = Cli::parse();
+    // Usage errors count as generic failures, clap's own exit code 2 would
+    // collide with the connection failure code
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            std::process::exit(if e.use_stderr() { error::EXIT_GENERIC } else { error::EXIT_SUCCESS });
+        }
+    };
     
-    cli.execute().await
+    if let Err(e) = cli.execute().await {
+        eprintln!("Error: {:?}", e);
+        std::process::exit(error::exit_code(&e));
+    }
 }
@@ -0,0 +1,119 @@
+use encoding_rs::{EncoderResult, Encoding, UTF_8};
+use std::borrow::Cow;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use crate::error::{CqlError, CqlResult};
+
+/// Encoding of everything written through `print`/`println`, set once by
+/// `--output-encoding`. Unset means UTF-8.
+static ENCODING: OnceLock<&'static Encoding> = OnceLock::new();
+
+/// `--limit-output-bytes`: at most this many bytes go through
+/// `print`/`println`. Unset means unlimited.
+static LIMIT: OnceLock<u64> = OnceLock::new();
+
+/// Bytes passed to `print`/`println` so far, including those cut off
+static WRITTEN: AtomicU64 = AtomicU64::new(0);
+
+/// Selects the output encoding by its WHATWG label (`latin1`, `shift_jis`,
+/// `windows-1251`, ...)
+pub fn set_encoding(label: &str) -> CqlResult<()> {
+    let encoding = Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| CqlError::ConfigError(format!("Unknown output encoding '{}'", label)))?;
+    // UTF-16 labels decode only; encoding_rs writes UTF-8 for them
+    if encoding.output_encoding() != encoding {
+        return Err(CqlError::ConfigError(format!("Cannot write output as {}", encoding.name())));
+    }
+    let _ = ENCODING.set(encoding);
+    Ok(())
+}
+
+pub fn set_limit(bytes: u64) {
+    let _ = LIMIT.set(bytes);
+}
+
+fn encoding() -> &'static Encoding {
+    ENCODING.get().copied().unwrap_or(UTF_8)
+}
+
+/// `text` in the output encoding. Characters the encoding lacks are replaced:
+/// box-drawing characters by their ASCII look-alikes (so tables stay
+/// readable), anything else by `?`.
+pub fn encode(text: &str) -> Cow<'_, [u8]> {
+    let encoding = encoding();
+    if encoding == UTF_8 {
+        return Cow::Borrowed(text.as_bytes());
+    }
+
+    let mut encoder = encoding.new_encoder();
+    let mut output = Vec::with_capacity(text.len());
+    let mut remaining = text;
+    let mut buffer = [0u8; 4096];
+    loop {
+        let (result, read, written) = encoder.encode_from_utf8_without_replacement(remaining, &mut buffer, true);
+        output.extend_from_slice(&buffer[..written]);
+        remaining = &remaining[read..];
+        match result {
+            EncoderResult::InputEmpty => break,
+            EncoderResult::OutputFull => {}
+            EncoderResult::Unmappable(c) => output.push(ascii_fallback(c)),
+        }
+    }
+    Cow::Owned(output)
+}
+
+fn ascii_fallback(c: char) -> u8 {
+    match c {
+        '─' | '━' | '═' => b'-',
+        '│' | '┃' | '║' => b'|',
+        '┌' | '┐' | '└' | '┘' | '├' | '┤' | '┬' | '┴' | '┼'
+        | '╔' | '╗' | '╚' | '╝' | '╠' | '╣' | '╦' | '╩' | '╬' => b'+',
+        '→' => b'>',
+        _ => b'?',
+    }
+}
+
+/// Writes `bytes` as long as the output limit allows. The write that
+/// reaches the limit is cut off there and followed by a notice, everything
+/// after it is dropped.
+fn write_limited(out: &mut impl Write, bytes: &[u8]) {
+    let Some(&limit) = LIMIT.get() else {
+        let _ = out.write_all(bytes);
+        return;
+    };
+
+    let written = WRITTEN.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+    let remaining = limit.saturating_sub(written);
+    if remaining == 0 {
+        return;
+    }
+    if bytes.len() as u64 <= remaining {
+        let _ = out.write_all(bytes);
+        return;
+    }
+
+    // Do not end in the middle of a UTF-8 character
+    let mut cut = remaining as usize;
+    if encoding() == UTF_8 {
+        while cut > 0 && bytes[cut] & 0xC0 == 0x80 {
+            cut -= 1;
+        }
+    }
+    let _ = out.write_all(&bytes[..cut]);
+    let _ = out.write_all(format!("\n(output truncated at {} bytes)\n", limit).as_bytes());
+}
+
+/// Like `print!("{}", text)`, in the output encoding
+pub fn print(text: &str) {
+    let mut stdout = std::io::stdout().lock();
+    write_limited(&mut stdout, &encode(text));
+    let _ = stdout.flush();
+}
+
+/// Like `println!("{}", text)`, in the output encoding
+pub fn println(text: &str) {
+    let mut stdout = std::io::stdout().lock();
+    write_limited(&mut stdout, &encode(text));
+    write_limited(&mut stdout, b"\n");
+}
@@ -0,0 +1,477 @@
+use scylla::frame::response::result::{ColumnType, CqlValue};
+use scylla::frame::value::{CqlDate, CqlDecimal, CqlDuration, CqlTime, CqlTimestamp, CqlVarint};
+use crate::error::{CqlError, CqlResult};
+use crate::formatter::cql_type_name;
+
+/// Turns a `--param` value into the value bound to a `?` marker.
+///
+/// The value is parsed as a CQL literal of `expected`, the type the server
+/// reported for the marker, unless it starts with an explicit type such as
+/// `list<int>:[1, 2, 3]`. Collections use CQL literal syntax: `[...]` for
+/// lists, `{...}` for sets, `{k: v, ...}` for maps and UDTs and `(...)` for
+/// tuples, with text inside them in single quotes. A bare `null` binds NULL.
+pub fn parse_param(raw: &str, expected: &ColumnType, index: usize) -> CqlResult<Option<CqlValue>> {
+    let (typ, literal) = match split_explicit_type(raw) {
+        Some((typ, literal)) => (typ, literal),
+        None => (expected.clone(), raw.trim()),
+    };
+
+    let invalid = |message: String| {
+        CqlError::InvalidQuery(format!(
+            "Invalid value for parameter {} ({}): {}",
+            index + 1,
+            cql_type_name(&typ),
+            message
+        ))
+    };
+
+    if literal.eq_ignore_ascii_case("null") {
+        return Ok(None);
+    }
+
+    if is_collection(&typ) {
+        let mut parser = LiteralParser::new(literal);
+        let value = parser.value(&typ).map_err(invalid)?;
+        parser.skip_whitespace();
+        if let Some(c) = parser.peek() {
+            return Err(invalid(format!("unexpected '{}' at position {}", c, parser.pos + 1)));
+        }
+        Ok(Some(value))
+    } else {
+        // Scalars at the top level may be given without quotes
+        let literal = unquote(literal).unwrap_or_else(|| literal.to_string());
+        parse_scalar(&literal, &typ).map(Some).map_err(invalid)
+    }
+}
+
+/// `list<int>:[1,2]` -> (`list<int>`, `[1,2]`). Only splits if the part before
+/// the first `:` is a valid type, so timestamps and maps are left alone.
+fn split_explicit_type(raw: &str) -> Option<(ColumnType, &str)> {
+    let (typ, literal) = raw.split_once(':')?;
+    Some((parse_type(typ.trim())?, literal.trim()))
+}
+
+/// Parses a CQL type name such as `int`, `frozen<set<text>>` or `map<text, int>`
+pub fn parse_type(name: &str) -> Option<ColumnType> {
+    let name = name.trim();
+    let (base, args) = match name.find('<') {
+        Some(open) if name.ends_with('>') => (&name[..open], Some(&name[open + 1..name.len() - 1])),
+        Some(_) => return None,
+        None => (name, None),
+    };
+
+    let args = match args {
+        Some(args) => Some(split_type_args(args)?),
+        None => None,
+    };
+
+    let typ = match (base.trim().to_lowercase().as_str(), args.as_deref()) {
+        ("ascii", None) => ColumnType::Ascii,
+        ("bigint", None) => ColumnType::BigInt,
+        ("blob", None) => ColumnType::Blob,
+        ("boolean", None) => ColumnType::Boolean,
+        ("counter", None) => ColumnType::Counter,
+        ("date", None) => ColumnType::Date,
+        ("decimal", None) => ColumnType::Decimal,
+        ("double", None) => ColumnType::Double,
+        ("duration", None) => ColumnType::Duration,
+        ("float", None) => ColumnType::Float,
+        ("inet", None) => ColumnType::Inet,
+        ("int", None) => ColumnType::Int,
+        ("smallint", None) => ColumnType::SmallInt,
+        ("text" | "varchar", None) => ColumnType::Text,
+        ("time", None) => ColumnType::Time,
+        ("timestamp", None) => ColumnType::Timestamp,
+        ("timeuuid", None) => ColumnType::Timeuuid,
+        ("tinyint", None) => ColumnType::TinyInt,
+        ("uuid", None) => ColumnType::Uuid,
+        ("varint", None) => ColumnType::Varint,
+        ("frozen", Some([inner])) => parse_type(inner)?,
+        ("list", Some([element])) => ColumnType::List(Box::new(parse_type(element)?)),
+        ("set", Some([element])) => ColumnType::Set(Box::new(parse_type(element)?)),
+        ("map", Some([key, value])) => ColumnType::Map(Box::new(parse_type(key)?), Box::new(parse_type(value)?)),
+        ("tuple", Some(elements)) => ColumnType::Tuple(elements.iter().map(|e| parse_type(e)).collect::<Option<_>>()?),
+        _ => return None,
+    };
+    Some(typ)
+}
+
+/// Splits `text, map<int, int>` on the commas that are not nested in `<>`
+fn split_type_args(args: &str) -> Option<Vec<&str>> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth = depth.checked_sub(1)?,
+            ',' if depth == 0 => {
+                parts.push(&args[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&args[start..]);
+    Some(parts)
+}
+
+fn is_collection(typ: &ColumnType) -> bool {
+    matches!(
+        typ,
+        ColumnType::List(_) | ColumnType::Set(_) | ColumnType::Map(_, _) | ColumnType::Tuple(_) | ColumnType::UserDefinedType { .. }
+    )
+}
+
+/// `'it''s'` -> `it's`; `None` if `s` is not a single quoted string
+fn unquote(s: &str) -> Option<String> {
+    let inner = s.strip_prefix('\'')?.strip_suffix('\'')?;
+    let mut chars = inner.chars().peekable();
+    let mut unquoted = String::with_capacity(inner.len());
+    while let Some(c) = chars.next() {
+        if c == '\'' {
+            // Only doubled quotes may appear inside
+            if chars.next() != Some('\'') {
+                return None;
+            }
+        }
+        unquoted.push(c);
+    }
+    Some(unquoted)
+}
+
+struct LiteralParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl LiteralParser {
+    fn new(input: &str) -> Self {
+        Self { chars: input.chars().collect(), pos: 0 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => Err(format!("expected '{}' but found '{}' at position {}", expected, c, self.pos + 1)),
+            None => Err(format!("expected '{}' but the value ended", expected)),
+        }
+    }
+
+    /// Consumes `c` if it is the next non-whitespace character
+    fn accept(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Parses `open item, item, ... close`, calling `item` for each element
+    fn sequence<T>(
+        &mut self,
+        open: char,
+        close: char,
+        mut item: impl FnMut(&mut Self) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        self.expect(open)?;
+        let mut items = Vec::new();
+        if self.accept(close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.accept(close) {
+                return Ok(items);
+            }
+            if self.peek().is_none() {
+                return Err(format!("expected '{}' but the value ended", close));
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn value(&mut self, typ: &ColumnType) -> Result<CqlValue, String> {
+        match typ {
+            ColumnType::List(element) => {
+                Ok(CqlValue::List(self.sequence('[', ']', |p| p.value(element))?))
+            }
+            ColumnType::Set(element) => {
+                Ok(CqlValue::Set(self.sequence('{', '}', |p| p.value(element))?))
+            }
+            ColumnType::Map(key_type, value_type) => {
+                let entries = self.sequence('{', '}', |p| {
+                    let key = p.value(key_type)?;
+                    p.expect(':')?;
+                    Ok((key, p.value(value_type)?))
+                })?;
+                Ok(CqlValue::Map(entries))
+            }
+            ColumnType::Tuple(types) => {
+                let mut types = types.iter();
+                let values = self.sequence('(', ')', |p| {
+                    let typ = types.next().ok_or("too many tuple elements")?;
+                    p.nullable(typ)
+                })?;
+                Ok(CqlValue::Tuple(values))
+            }
+            ColumnType::UserDefinedType { type_name, keyspace, field_types } => {
+                let given = self.sequence('{', '}', |p| {
+                    p.skip_whitespace();
+                    let name = p.token()?;
+                    let typ = field_types.iter()
+                        .find(|(field, _)| *field == name)
+                        .map(|(_, typ)| typ)
+                        .ok_or_else(|| format!("{} has no field '{}'", type_name, name))?;
+                    p.expect(':')?;
+                    Ok((name, p.nullable(typ)?))
+                })?;
+                // Fields that are not given are NULL
+                let fields = field_types.iter()
+                    .map(|(field, _)| {
+                        let value = given.iter().find(|(name, _)| name == field).and_then(|(_, v)| v.clone());
+                        (field.clone(), value)
+                    })
+                    .collect();
+                Ok(CqlValue::UserDefinedType {
+                    keyspace: keyspace.clone(),
+                    type_name: type_name.clone(),
+                    fields,
+                })
+            }
+            _ => {
+                self.skip_whitespace();
+                let token = if self.peek() == Some('\'') { self.quoted()? } else { self.token()? };
+                parse_scalar(&token, typ)
+            }
+        }
+    }
+
+    /// Tuple elements and UDT fields may be `null`, collection elements may not
+    fn nullable(&mut self, typ: &ColumnType) -> Result<Option<CqlValue>, String> {
+        self.skip_whitespace();
+        let rest: String = self.chars[self.pos..].iter().take(4).collect();
+        let after = self.chars.get(self.pos + 4).copied();
+        if rest.eq_ignore_ascii_case("null") && !after.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            self.pos += 4;
+            return Ok(None);
+        }
+        self.value(typ).map(Some)
+    }
+
+    fn quoted(&mut self) -> Result<String, String> {
+        let start = self.pos + 1;
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                Some('\'') if self.chars.get(self.pos + 1) == Some(&'\'') => {
+                    text.push('\'');
+                    self.pos += 2;
+                }
+                Some('\'') => {
+                    self.pos += 1;
+                    return Ok(text);
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+                None => return Err(format!("unterminated string starting at position {}", start)),
+            }
+        }
+    }
+
+    /// An unquoted value: everything up to the next delimiter
+    fn token(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !c.is_whitespace() && !",:[]{}()".contains(c)) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(match self.peek() {
+                Some(c) => format!("expected a value but found '{}' at position {}", c, self.pos + 1),
+                None => "expected a value but the value ended".to_string(),
+            });
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+}
+
+/// Parses an unquoted scalar of type `typ`. Reads back everything the
+/// formatter writes for scalars, which is what makes CSV exports importable.
+pub fn parse_scalar(literal: &str, typ: &ColumnType) -> Result<CqlValue, String> {
+    fn number<T: std::str::FromStr>(literal: &str, name: &str) -> Result<T, String> {
+        literal.parse::<T>().map_err(|_| format!("'{}' is not a valid {}", literal, name))
+    }
+
+    let value = match typ {
+        ColumnType::Ascii => CqlValue::Ascii(literal.to_string()),
+        ColumnType::Text => CqlValue::Text(literal.to_string()),
+        ColumnType::Boolean => match literal.to_lowercase().as_str() {
+            "true" => CqlValue::Boolean(true),
+            "false" => CqlValue::Boolean(false),
+            _ => return Err(format!("'{}' is not a boolean (true/false)", literal)),
+        },
+        ColumnType::TinyInt => CqlValue::TinyInt(number(literal, "tinyint")?),
+        ColumnType::SmallInt => CqlValue::SmallInt(number(literal, "smallint")?),
+        ColumnType::Int => CqlValue::Int(number(literal, "int")?),
+        ColumnType::BigInt => CqlValue::BigInt(number(literal, "bigint")?),
+        ColumnType::Counter => CqlValue::Counter(scylla::frame::value::Counter(number(literal, "counter")?)),
+        ColumnType::Float => CqlValue::Float(number(literal, "float")?),
+        ColumnType::Double => CqlValue::Double(number(literal, "double")?),
+        ColumnType::Varint => CqlValue::Varint(CqlVarint::from(number::<num_bigint::BigInt>(literal, "varint")?)),
+        ColumnType::Decimal => {
+            let decimal = number::<bigdecimal::BigDecimal>(literal, "decimal")?;
+            CqlValue::Decimal(CqlDecimal::try_from(decimal).map_err(|_| format!("'{}' has too many decimal places", literal))?)
+        }
+        ColumnType::Uuid => CqlValue::Uuid(number(literal, "uuid")?),
+        ColumnType::Timeuuid => CqlValue::Timeuuid(number::<uuid::Uuid>(literal, "timeuuid")?.into()),
+        ColumnType::Inet => CqlValue::Inet(number(literal, "inet address")?),
+        ColumnType::Blob => {
+            let hex = literal.strip_prefix("0x")
+                .or_else(|| literal.strip_prefix("0X"))
+                .ok_or_else(|| format!("'{}' is not a blob literal (0x...)", literal))?;
+            if hex.len() % 2 != 0 {
+                return Err(format!("'{}' has an odd number of hex digits", literal));
+            }
+            let bytes = (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| format!("'{}' is not a blob literal (0x...)", literal))?;
+            CqlValue::Blob(bytes)
+        }
+        ColumnType::Date => match literal.parse::<i64>() {
+            // Days relative to the unix epoch, as dates outside chrono's range are shown
+            Ok(days) => CqlValue::Date(CqlDate(
+                days.checked_add(1i64 << 31)
+                    .and_then(|days| u32::try_from(days).ok())
+                    .ok_or_else(|| format!("'{}' is out of range", literal))?,
+            )),
+            Err(_) => {
+                let date = chrono::NaiveDate::parse_from_str(literal, "%Y-%m-%d")
+                    .map_err(|_| format!("'{}' is not a date (YYYY-MM-DD)", literal))?;
+                CqlValue::Date(CqlDate::from(date))
+            }
+        },
+        ColumnType::Time => {
+            let time = chrono::NaiveTime::parse_from_str(literal, "%H:%M:%S%.f")
+                .map_err(|_| format!("'{}' is not a time (HH:MM:SS[.fffffffff])", literal))?;
+            CqlValue::Time(CqlTime::try_from(time).map_err(|_| format!("'{}' is out of range", literal))?)
+        }
+        ColumnType::Timestamp => CqlValue::Timestamp(parse_timestamp(literal)?),
+        ColumnType::Duration => CqlValue::Duration(parse_duration(literal)?),
+        ColumnType::Custom(class) => return Err(format!("custom type '{}' cannot be bound from the command line", class)),
+        ColumnType::List(_) | ColumnType::Set(_) | ColumnType::Map(_, _) | ColumnType::Tuple(_) | ColumnType::UserDefinedType { .. } => {
+            return Err(format!("expected a {} literal", cql_type_name(typ)));
+        }
+    };
+    Ok(value)
+}
+
+/// Milliseconds since the epoch, or a date/time as accepted by Cassandra:
+/// `2024-01-31`, `2024-01-31 12:00:00[.123]` and the ISO 8601 `T` form, with
+/// an optional `Z` or `+hhmm`/`+hh:mm` offset (UTC if none is given)
+fn parse_timestamp(literal: &str) -> Result<CqlTimestamp, String> {
+    if let Ok(millis) = literal.parse::<i64>() {
+        return Ok(CqlTimestamp(millis));
+    }
+
+    let normalized = literal.replacen('T', " ", 1);
+    let normalized = normalized.strip_suffix('Z').map(|s| format!("{}+0000", s)).unwrap_or(normalized);
+    for format in ["%Y-%m-%d %H:%M:%S%.f%z", "%Y-%m-%d %H:%M%z", "%Y-%m-%d %H:%M:%S%.f%:z", "%Y-%m-%d %H:%M%:z"] {
+        if let Ok(timestamp) = chrono::DateTime::parse_from_str(&normalized, format) {
+            return Ok(CqlTimestamp::from(timestamp.with_timezone(&chrono::Utc)));
+        }
+    }
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M"] {
+        if let Ok(timestamp) = chrono::NaiveDateTime::parse_from_str(&normalized, format) {
+            return Ok(CqlTimestamp::from(timestamp.and_utc()));
+        }
+    }
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(&normalized, "%Y-%m-%d") {
+        return Ok(CqlTimestamp::from(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()));
+    }
+
+    Err(format!("'{}' is not a timestamp (milliseconds or YYYY-MM-DD[ HH:MM:SS[.fff]][Z|+hhmm])", literal))
+}
+
+/// A duration in CQL's unit form, e.g. `1h30m`, `-2d` or `1y2mo3w4d5h6m7s8ms9us10ns`
+fn parse_duration(literal: &str) -> Result<CqlDuration, String> {
+    let invalid = || format!("'{}' is not a duration (e.g. 1h30m, 2d, 1y2mo)", literal);
+    let (negative, rest) = match literal.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, literal),
+    };
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+
+    let (mut months, mut days, mut nanos) = (0i64, 0i64, 0i64);
+    let mut chars = rest.char_indices().peekable();
+    while let Some(&(start, _)) = chars.peek() {
+        let mut end = start;
+        while let Some(&(i, c)) = chars.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            end = i + 1;
+            chars.next();
+        }
+        let amount: i64 = rest[start..end].parse().map_err(|_| invalid())?;
+
+        let unit_start = end;
+        let mut unit_end = end;
+        while let Some(&(i, c)) = chars.peek() {
+            if c.is_ascii_digit() {
+                break;
+            }
+            unit_end = i + c.len_utf8();
+            chars.next();
+        }
+
+        let overflow = |value: Option<i64>| value.ok_or_else(invalid);
+        match rest[unit_start..unit_end].to_lowercase().as_str() {
+            "y" => months = overflow(amount.checked_mul(12).and_then(|m| months.checked_add(m)))?,
+            "mo" => months = overflow(months.checked_add(amount))?,
+            "w" => days = overflow(amount.checked_mul(7).and_then(|d| days.checked_add(d)))?,
+            "d" => days = overflow(days.checked_add(amount))?,
+            unit => {
+                let unit_nanos = match unit {
+                    "h" => 3_600_000_000_000,
+                    "m" => 60_000_000_000,
+                    "s" => 1_000_000_000,
+                    "ms" => 1_000_000,
+                    "us" | "µs" => 1_000,
+                    "ns" => 1,
+                    _ => return Err(invalid()),
+                };
+                nanos = overflow(amount.checked_mul(unit_nanos).and_then(|n| nanos.checked_add(n)))?;
+            }
+        }
+    }
+
+    let sign = if negative { -1 } else { 1 };
+    Ok(CqlDuration {
+        months: i32::try_from(months * sign).map_err(|_| invalid())?,
+        days: i32::try_from(days * sign).map_err(|_| invalid())?,
+        nanoseconds: nanos * sign,
+    })
+}
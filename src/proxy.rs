@@ -0,0 +1,362 @@
+use scylla::transport::errors::TranslationError;
+use scylla::transport::topology::UntranslatedPeer;
+use scylla::transport::session::AddressTranslator;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use crate::error::{CqlError, CqlResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyKind {
+    Socks5,
+    /// A proxy that opens tunnels with `CONNECT host:port`
+    Http,
+}
+
+/// The proxy given with `--proxy`
+#[derive(Debug, Clone)]
+pub struct ProxyConfig {
+    pub kind: ProxyKind,
+    /// `host:port` of the proxy itself
+    pub address: String,
+    pub credentials: Option<(String, String)>,
+    /// Whether hostnames are sent to the proxy to resolve (`socks5h://`,
+    /// `http://`) or resolved here first (`socks5://`)
+    pub remote_dns: bool,
+}
+
+impl ProxyConfig {
+    /// Parses `socks5://[user:password@]host:port` (also `socks5h://`) or
+    /// `http://[user:password@]host:port`
+    pub fn parse(url: &str) -> CqlResult<Self> {
+        let invalid = |reason: &str| {
+            CqlError::ConfigError(format!(
+                "Invalid proxy '{}': {} (expected socks5://[user:password@]host:port or http://host:port)",
+                url, reason
+            ))
+        };
+
+        let (scheme, rest) = url.split_once("://").ok_or_else(|| invalid("no scheme"))?;
+        let (kind, remote_dns) = match scheme.to_lowercase().as_str() {
+            "socks5" => (ProxyKind::Socks5, false),
+            "socks5h" => (ProxyKind::Socks5, true),
+            "http" => (ProxyKind::Http, true),
+            _ => return Err(invalid("unsupported scheme")),
+        };
+
+        let rest = rest.trim_end_matches('/');
+        let (credentials, address) = match rest.rsplit_once('@') {
+            Some((credentials, address)) => {
+                let (user, password) = credentials.split_once(':').unwrap_or((credentials, ""));
+                (Some((user.to_string(), password.to_string())), address)
+            }
+            None => (None, rest),
+        };
+        let port = address.rsplit_once(':').map(|(_, port)| port).ok_or_else(|| invalid("no port"))?;
+        port.parse::<u16>().map_err(|_| invalid("invalid port"))?;
+
+        Ok(Self { kind, address: address.to_string(), credentials, remote_dns })
+    }
+
+    /// A TCP stream to `host:port` through the proxy, ready for the CQL protocol
+    async fn connect(&self, host: &str, port: u16) -> std::io::Result<TcpStream> {
+        let mut stream = TcpStream::connect(&self.address).await?;
+        match self.kind {
+            ProxyKind::Socks5 if self.remote_dns || host.parse::<std::net::IpAddr>().is_ok() => {
+                self.socks5_handshake(&mut stream, host, port).await?
+            }
+            ProxyKind::Socks5 => {
+                let resolved = tokio::net::lookup_host((host, port)).await?.next().ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::NotFound, format!("{} did not resolve to an address", host))
+                })?;
+                self.socks5_handshake(&mut stream, &resolved.ip().to_string(), port).await?
+            }
+            ProxyKind::Http => return self.http_connect(stream, host, port).await,
+        }
+        Ok(stream)
+    }
+
+    /// RFC 1928, with username/password authentication (RFC 1929) if
+    /// credentials were given
+    async fn socks5_handshake<S>(&self, stream: &mut S, host: &str, port: u16) -> std::io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let methods: &[u8] = if self.credentials.is_some() { &[0x00, 0x02] } else { &[0x00] };
+        let mut greeting = vec![0x05, methods.len() as u8];
+        greeting.extend_from_slice(methods);
+        stream.write_all(&greeting).await?;
+
+        let mut choice = [0u8; 2];
+        stream.read_exact(&mut choice).await?;
+        match (choice, &self.credentials) {
+            ([0x05, 0x00], _) => {}
+            ([0x05, 0x02], Some((user, password))) => {
+                let mut request = vec![0x01, user.len() as u8];
+                request.extend_from_slice(user.as_bytes());
+                request.push(password.len() as u8);
+                request.extend_from_slice(password.as_bytes());
+                stream.write_all(&request).await?;
+
+                let mut status = [0u8; 2];
+                stream.read_exact(&mut status).await?;
+                if status[1] != 0x00 {
+                    return Err(proxy_error("SOCKS5 proxy rejected the credentials"));
+                }
+            }
+            _ => return Err(proxy_error("SOCKS5 proxy accepts none of the offered authentication methods")),
+        }
+
+        // Hostnames left at this point are resolved by the proxy
+        let mut request = vec![0x05, 0x01, 0x00];
+        match host.parse::<std::net::IpAddr>() {
+            Ok(std::net::IpAddr::V4(ip)) => {
+                request.push(0x01);
+                request.extend_from_slice(&ip.octets());
+            }
+            Ok(std::net::IpAddr::V6(ip)) => {
+                request.push(0x04);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                request.push(0x03);
+                request.push(host.len() as u8);
+                request.extend_from_slice(host.as_bytes());
+            }
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await?;
+
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply).await?;
+        if reply[1] != 0x00 {
+            return Err(proxy_error(&format!("SOCKS5 proxy could not connect to {}:{} (code {})", host, port, reply[1])));
+        }
+        // Skip the bound address the proxy reports
+        let address_len = match reply[3] {
+            0x01 => 4,
+            0x04 => 16,
+            _ => stream.read_u8().await? as usize,
+        };
+        let mut bound = vec![0u8; address_len + 2];
+        stream.read_exact(&mut bound).await?;
+        Ok(())
+    }
+
+    async fn http_connect(&self, stream: TcpStream, host: &str, port: u16) -> std::io::Result<TcpStream> {
+        let target = match host.parse::<std::net::Ipv6Addr>() {
+            Ok(_) => format!("[{}]:{}", host, port),
+            Err(_) => format!("{}:{}", host, port),
+        };
+        let mut request = format!("CONNECT {} HTTP/1.1\r\nHost: {}\r\n", target, target);
+        if let Some((user, password)) = &self.credentials {
+            request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", base64(format!("{}:{}", user, password).as_bytes())));
+        }
+        request.push_str("\r\n");
+
+        let mut reader = BufReader::new(stream);
+        reader.get_mut().write_all(request.as_bytes()).await?;
+
+        let mut status = String::new();
+        reader.read_line(&mut status).await?;
+        if status.split_whitespace().nth(1) != Some("200") {
+            return Err(proxy_error(&format!("HTTP proxy refused CONNECT {}: {}", target, status.trim())));
+        }
+        // Headers end with an empty line; nothing of the tunnel follows before our first write
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+                break;
+            }
+        }
+        Ok(reader.into_inner())
+    }
+}
+
+fn proxy_error(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::ConnectionRefused, message.to_string())
+}
+
+fn base64(input: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let bytes = [chunk[0], chunk.get(1).copied().unwrap_or(0), chunk.get(2).copied().unwrap_or(0)];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+/// Opens a listener on localhost that forwards every connection to
+/// `host:port` through `proxy`, and returns its address. The driver cannot
+/// use a proxy itself, so it is pointed at these local ends instead.
+///
+/// The listener does not authenticate its clients: while cqlrs runs, any
+/// process on this machine that finds the port can reach the node through
+/// the proxy, using the proxy credentials. Cassandra authentication still
+/// applies on top.
+pub async fn open_tunnel(proxy: Arc<ProxyConfig>, host: String, port: u16) -> std::io::Result<SocketAddr> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+    let local = listener.local_addr()?;
+    info!("Tunneling {} to {}:{} through {}", local, host, port, proxy.address);
+
+    tokio::spawn(async move {
+        while let Ok((mut client, _)) = listener.accept().await {
+            let (proxy, host) = (proxy.clone(), host.clone());
+            tokio::spawn(async move {
+                match proxy.connect(&host, port).await {
+                    Ok(mut upstream) => {
+                        let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+                    }
+                    Err(e) => warn!("Proxy connection to {}:{} failed: {}", host, port, e),
+                }
+            });
+        }
+    });
+
+    Ok(local)
+}
+
+/// Sends the connections to the nodes the driver discovers through the
+/// proxy as well, by translating every node address to a tunnel
+pub struct ProxyTranslator {
+    proxy: Arc<ProxyConfig>,
+    tunnels: Mutex<HashMap<SocketAddr, SocketAddr>>,
+}
+
+impl ProxyTranslator {
+    pub fn new(proxy: Arc<ProxyConfig>) -> Self {
+        Self { proxy, tunnels: Mutex::new(HashMap::new()) }
+    }
+}
+
+#[async_trait::async_trait]
+impl AddressTranslator for ProxyTranslator {
+    async fn translate_address(&self, peer: &UntranslatedPeer) -> Result<SocketAddr, TranslationError> {
+        let address = peer.untranslated_address;
+        let mut tunnels = self.tunnels.lock().await;
+        if let Some(local) = tunnels.get(&address) {
+            return Ok(*local);
+        }
+
+        let local = open_tunnel(self.proxy.clone(), address.ip().to_string(), address.port())
+            .await
+            .map_err(|e| {
+                warn!("Could not open a tunnel to {}: {}", address, e);
+                TranslationError::NoRuleForAddress
+            })?;
+        tunnels.insert(address, local);
+        Ok(local)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn socks5h() -> ProxyConfig {
+        ProxyConfig::parse("socks5h://bastion:1080").unwrap()
+    }
+
+    #[test]
+    fn parse_reads_scheme_credentials_and_address() {
+        let proxy = ProxyConfig::parse("socks5://user:p@ss@bastion.example.com:1080/").unwrap();
+        assert_eq!(proxy.kind, ProxyKind::Socks5);
+        assert_eq!(proxy.address, "bastion.example.com:1080");
+        assert_eq!(proxy.credentials, Some(("user".to_string(), "p@ss".to_string())));
+        assert!(!proxy.remote_dns);
+
+        let proxy = socks5h();
+        assert_eq!(proxy.kind, ProxyKind::Socks5);
+        assert!(proxy.credentials.is_none());
+        assert!(proxy.remote_dns);
+
+        let proxy = ProxyConfig::parse("HTTP://user@[::1]:3128").unwrap();
+        assert_eq!(proxy.kind, ProxyKind::Http);
+        assert_eq!(proxy.address, "[::1]:3128");
+        assert_eq!(proxy.credentials, Some(("user".to_string(), String::new())));
+    }
+
+    #[test]
+    fn parse_rejects_malformed_urls() {
+        for url in ["bastion:1080", "ftp://bastion:1080", "socks5://bastion", "socks5://bastion:port", "http://bastion:70000"] {
+            assert!(ProxyConfig::parse(url).is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn base64_pads_to_whole_groups() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"user:password"), "dXNlcjpwYXNzd29yZA==");
+        assert_eq!(base64(&[0xff, 0xfe, 0xfd]), "//79");
+    }
+
+    /// Runs the SOCKS5 handshake against a fake proxy that answers with
+    /// `replies` and returns what the client sent
+    async fn socks5_exchange(proxy: &ProxyConfig, host: &str, replies: &[u8]) -> (std::io::Result<()>, Vec<u8>) {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        server.write_all(replies).await.unwrap();
+        let result = proxy.socks5_handshake(&mut client, host, 9042).await;
+        drop(client);
+        let mut sent = Vec::new();
+        server.read_to_end(&mut sent).await.unwrap();
+        (result, sent)
+    }
+
+    #[tokio::test]
+    async fn socks5_sends_hostnames_and_skips_the_bound_address() {
+        let replies = [&[0x05, 0x00][..], &[0x05, 0x00, 0x00, 0x03, 4], b"node", &[0x23, 0x52], b"CQL"].concat();
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        server.write_all(&replies).await.unwrap();
+        socks5h().socks5_handshake(&mut client, "cassandra", 9042).await.unwrap();
+
+        // The bound address is consumed, the tunnel data after it is not
+        let mut rest = [0u8; 3];
+        client.read_exact(&mut rest).await.unwrap();
+        assert_eq!(&rest, b"CQL");
+
+        drop(client);
+        let mut sent = Vec::new();
+        server.read_to_end(&mut sent).await.unwrap();
+        assert_eq!(sent, [&[0x05, 0x01, 0x00, 0x05, 0x01, 0x00, 0x03, 9][..], b"cassandra", &[0x23, 0x52]].concat());
+    }
+
+    #[tokio::test]
+    async fn socks5_sends_ip_addresses_and_credentials() {
+        let proxy = ProxyConfig::parse("socks5://user:pw@bastion:1080").unwrap();
+        let replies = [0x05, 0x02, 0x01, 0x00, 0x05, 0x00, 0x00, 0x01, 10, 0, 0, 1, 0x23, 0x52];
+        let (result, sent) = socks5_exchange(&proxy, "10.0.0.7", &replies).await;
+        result.unwrap();
+        assert_eq!(
+            sent,
+            [
+                &[0x05, 0x02, 0x00, 0x02][..],
+                &[0x01, 4], b"user", &[2], b"pw",
+                &[0x05, 0x01, 0x00, 0x01, 10, 0, 0, 7, 0x23, 0x52],
+            ].concat()
+        );
+    }
+
+    #[tokio::test]
+    async fn socks5_reports_refusals() {
+        let (result, _) = socks5_exchange(&socks5h(), "cassandra", &[0x05, 0x00, 0x05, 0x05, 0x00, 0x01]).await;
+        assert!(result.unwrap_err().to_string().contains("code 5"));
+
+        let (result, _) = socks5_exchange(&socks5h(), "cassandra", &[0x05, 0xff]).await;
+        assert!(result.unwrap_err().to_string().contains("authentication methods"));
+    }
+}
@@ -1,19 +1,65 @@
 use rustyline::error::ReadlineError;
 use rustyline::completion::{Completer, Pair};
-use rustyline::hint::Hinter;
+use rustyline::hint::{Hint, Hinter};
 use rustyline::highlight::Highlighter;
 use rustyline::validate::Validator;
 use rustyline::{Context, Helper, Editor};
 use rustyline::history::DefaultHistory;
 use rustyline::Result as RustylineResult;
 use colored::*;
-use std::path::PathBuf;
+use prettytable::{format, Cell, Row, Table};
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::OnceLock;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
-use std::collections::HashMap;
-use std::fs::File;
-use crate::executor::QueryExecutor;
-use crate::error::CqlResult;
-use crate::formatter::{format_result, OutputFormat};
+use std::collections::{BTreeMap, HashMap};
+use crate::files;
+use crate::output;
+use crate::executor::{
+    consistency_name, consistency_names, parse_consistency, parse_serial_consistency, serial_consistency_name,
+    QueryExecutor,
+};
+use crate::error::{CqlError, CqlResult};
+use futures::stream::{self, StreamExt};
+use crate::diff::ResultSnapshot;
+use scylla::frame::response::result::ColumnType;
+use crate::statement::{qualify_describe, split_statements, table_reference};
+use crate::formatter::{cql_literal, format_result, FormatOptions, OutputFormat, DEFAULT_MAX_COL_WIDTH};
+use regex::Regex;
+use scylla::statement::{Consistency, SerialConsistency};
+
+/// Unmeasured runs before `\bench` starts measuring
+const BENCH_WARMUP: usize = 10;
+
+/// Entries `\history` shows without an argument
+const HISTORY_LINES: usize = 20;
+
+/// `\i` scripts including each other deeper than this are assumed to loop
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Topics for `help <topic>`, in the order `help` lists them
+const HELP_TOPICS: &[(&str, &str)] = &[
+    ("commands", "REPL commands (settings, watch, export, import, ...)"),
+    ("formats", "Output formats and display options"),
+    ("describe", "Listing keyspaces, tables and schema"),
+    ("ssl", "SSL/TLS connection options"),
+    ("completion", "Tab completion"),
+    ("cql", "Running CQL statements"),
+];
+
+/// The completer's schema cache as stored by `\dump-completion-cache` and
+/// loaded by `--completion-cache`
+#[derive(Serialize, Deserialize)]
+struct CompletionCache {
+    keyspaces: Vec<String>,
+    tables: Vec<String>,
+    /// Column names per `keyspace.table`
+    #[serde(default)]
+    columns: BTreeMap<String, Vec<String>>,
+}
 
 /// CQL Auto-Completer with schema awareness
 #[derive(Clone)]
@@ -21,7 +67,10 @@ struct CqlCompleter {
     keywords: Vec<String>,
     keyspaces: HashSet<String>,
     tables: HashSet<String>,
-    current_keyspace: Option<String>,
+    /// Column names per `keyspace.table`
+    columns: HashMap<String, Vec<String>>,
+    /// Also offer identifiers that contain the typed word or its letters in order
+    fuzzy: bool,
 }
 
 impl CqlCompleter {
@@ -58,7 +107,8 @@ impl CqlCompleter {
             keywords,
             keyspaces: HashSet::new(),
             tables: HashSet::new(),
-            current_keyspace: None,
+            columns: HashMap::new(),
+            fuzzy: false,
         }
     }
 
@@ -70,12 +120,42 @@ impl CqlCompleter {
         self.tables = tables.into_iter().collect();
     }
 
-    fn set_keyspace(&mut self, keyspace: Option<String>) {
-        self.current_keyspace = keyspace;
+    fn update_columns(&mut self, columns: HashMap<String, Vec<String>>) {
+        self.columns = columns;
+    }
+
+    /// Replaces the columns of one `keyspace.table` after it was altered
+    fn update_table_columns(&mut self, table: String, columns: Vec<String>) {
+        if columns.is_empty() {
+            self.columns.remove(&table);
+        } else {
+            self.columns.insert(table, columns);
+        }
+    }
+
+    fn to_cache(&self) -> CompletionCache {
+        let mut keyspaces: Vec<String> = self.keyspaces.iter().cloned().collect();
+        let mut tables: Vec<String> = self.tables.iter().cloned().collect();
+        keyspaces.sort();
+        tables.sort();
+        let columns = self.columns.iter()
+            .map(|(table, columns)| (table.clone(), columns.clone()))
+            .collect();
+        CompletionCache { keyspaces, tables, columns }
+    }
+
+    fn load_cache(&mut self, cache: CompletionCache) {
+        self.update_keyspaces(cache.keyspaces);
+        self.update_tables(cache.tables);
+        self.update_columns(cache.columns.into_iter().collect());
     }
 
     fn get_completions(&self, line: &str, pos: usize) -> Vec<Pair> {
         let line_up_to_cursor = &line[..pos];
+        if let Some(argument) = describe_argument(line_up_to_cursor) {
+            return self.describe_completions(argument, line_up_to_cursor);
+        }
+
         let last_word = line_up_to_cursor
             .split_whitespace()
             .last()
@@ -87,40 +167,203 @@ impl CqlCompleter {
 
         let last_word_upper = last_word.to_uppercase();
         let line_upper = line_up_to_cursor.to_uppercase();
-        let mut completions = Vec::new();
+        // (score, candidate); keywords are only completed by prefix
+        let mut completions: Vec<(usize, &String)> = Vec::new();
 
         for keyword in &self.keywords {
             if keyword.starts_with(&last_word_upper) {
-                completions.push(Pair {
-                    display: keyword.clone(),
-                    replacement: keyword.clone(),
-                });
+                completions.push((0, keyword));
             }
         }
 
         if line_upper.contains("USE ") || line_upper.contains("KEYSPACE ") {
             for keyspace in &self.keyspaces {
-                if keyspace.to_uppercase().starts_with(&last_word_upper) {
-                    completions.push(Pair {
-                        display: keyspace.clone(),
-                        replacement: keyspace.clone(),
-                    });
+                if let Some(score) = self.match_score(&keyspace.to_uppercase(), &last_word_upper) {
+                    completions.push((score, keyspace));
                 }
             }
         }
 
         if line_upper.contains("FROM ") || line_upper.contains("INTO ") || line_upper.contains("TABLE ") {
             for table in &self.tables {
-                if table.to_uppercase().starts_with(&last_word_upper) {
-                    completions.push(Pair {
-                        display: table.clone(),
-                        replacement: table.clone(),
-                    });
+                if let Some(score) = self.match_score(&table.to_uppercase(), &last_word_upper) {
+                    completions.push((score, table));
                 }
             }
         }
 
-        completions
+        if ["SELECT ", "WHERE ", "SET ", "BY ", "INTO "].iter().any(|kw| line_upper.contains(kw)) {
+            let mut columns: Vec<(usize, &String)> = self.columns.values()
+                .flatten()
+                .filter_map(|column| Some((self.match_score(&column.to_uppercase(), &last_word_upper)?, column)))
+                .collect();
+            columns.sort_by(|a, b| a.1.cmp(b.1));
+            columns.dedup_by(|a, b| a.1 == b.1);
+            completions.extend(columns);
+        }
+
+        // Stable, so without fuzzy matching (all scores 0) the order is unchanged
+        completions.sort_by_key(|(score, _)| *score);
+        completions.into_iter()
+            .map(|(_, candidate)| Pair { display: candidate.clone(), replacement: candidate.clone() })
+            .collect()
+    }
+
+    /// How well `candidate` matches the typed `word`, both in upper case;
+    /// lower is better. A prefix scores 0. With fuzzy matching, substrings
+    /// follow by their position and then candidates that contain the word's
+    /// letters in order (`usr` -> `user_accounts`) by how spread out they are.
+    fn match_score(&self, candidate: &str, word: &str) -> Option<usize> {
+        if candidate.starts_with(word) {
+            return Some(0);
+        }
+        if !self.fuzzy || word.is_empty() {
+            return None;
+        }
+        if let Some(position) = candidate.find(word) {
+            return Some(1 + position);
+        }
+
+        let mut chars = candidate.char_indices();
+        let mut first = None;
+        let mut last = 0;
+        for wanted in word.chars() {
+            let (index, _) = chars.find(|(_, c)| *c == wanted)?;
+            first.get_or_insert(index);
+            last = index;
+        }
+        Some(candidate.len() + 1 + last - first.unwrap_or(0))
+    }
+
+    /// Candidates for the word being typed after `describe`/`desc`/`\dt`
+    fn describe_completions(&self, argument: DescribeArgument, line: &str) -> Vec<Pair> {
+        let prefix = if line.ends_with(char::is_whitespace) {
+            ""
+        } else {
+            line.split_whitespace().last().unwrap_or("")
+        };
+
+        let candidates: Vec<String> = match argument {
+            DescribeArgument::Target => DESCRIBE_TARGETS.iter()
+                .map(|target| target.to_string())
+                .chain(self.tables.iter().cloned())
+                .collect(),
+            DescribeArgument::Keyspace => self.keyspaces.iter().cloned().collect(),
+            DescribeArgument::Table => self.tables.iter().cloned().collect(),
+            DescribeArgument::None => Vec::new(),
+        };
+
+        // Targets follow the case the user types in, like the rest of the line
+        let lowercase = !prefix.is_empty() && prefix.chars().all(|c| !c.is_uppercase());
+        let prefix_upper = prefix.to_uppercase();
+        let mut completions: Vec<(usize, Pair)> = candidates.into_iter()
+            .filter_map(|candidate| {
+                let is_target = DESCRIBE_TARGETS.contains(&candidate.as_str());
+                let score = if is_target {
+                    candidate.starts_with(&prefix_upper).then_some(0)?
+                } else {
+                    self.match_score(&candidate.to_uppercase(), &prefix_upper)?
+                };
+                let replacement = if is_target && lowercase { candidate.to_lowercase() } else { candidate };
+                Some((score, Pair { display: replacement.clone(), replacement }))
+            })
+            .collect();
+        completions.sort_by(|a, b| (a.0, &a.1.display).cmp(&(b.0, &b.1.display)));
+        completions.dedup_by(|a, b| a.1.display == b.1.display);
+        completions.into_iter().map(|(_, pair)| pair).collect()
+    }
+}
+
+/// What `describe`/`desc` accept as their first word; a bare table name works too
+const DESCRIBE_TARGETS: &[&str] = &["CLUSTER", "KEYSPACES", "KEYSPACE", "TABLES", "TABLE", "TYPES", "FUNCTIONS"];
+
+/// The argument expected at the end of a `describe`/`\dt` line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DescribeArgument {
+    Target,
+    Keyspace,
+    Table,
+    None,
+}
+
+/// Detects whether `line` (up to the cursor) is a describe command and what
+/// its word under the cursor should be, `None` for any other statement
+fn describe_argument(line: &str) -> Option<DescribeArgument> {
+    let mut words: Vec<&str> = line.split_whitespace().collect();
+    // The word being typed does not count as complete yet
+    if !line.ends_with(char::is_whitespace) {
+        words.pop();
+    }
+
+    let command = words.first()?;
+    if command.eq_ignore_ascii_case("\\dt") {
+        return Some(if words.len() == 1 { DescribeArgument::Keyspace } else { DescribeArgument::None });
+    }
+    if !command.eq_ignore_ascii_case("describe") && !command.eq_ignore_ascii_case("desc") {
+        return None;
+    }
+
+    Some(match words.len() {
+        1 => DescribeArgument::Target,
+        2 => match words[1].to_uppercase().as_str() {
+            "KEYSPACE" => DescribeArgument::Keyspace,
+            "TABLE" => DescribeArgument::Table,
+            _ => DescribeArgument::None,
+        },
+        _ => DescribeArgument::None,
+    })
+}
+
+/// The part of the schema a statement changes, for refreshing the completer
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SchemaChange {
+    /// Columns of one `(keyspace, table)`
+    Table(String, String),
+    All,
+}
+
+/// Classifies `statement` by its leading words. `ALTER TABLE` with a table
+/// whose keyspace is known is scoped to that table; other `CREATE`, `ALTER`
+/// and `DROP` statements (keyspaces, tables, types, views, ...) and `USE`
+/// refresh everything.
+fn schema_change(statement: &str, current_keyspace: Option<String>) -> Option<SchemaChange> {
+    let words: Vec<String> = statement.split_whitespace()
+        .take(3)
+        .map(|word| word.trim_end_matches(';').to_uppercase())
+        .collect();
+    let first = words.first()?.as_str();
+
+    if first == "ALTER" && matches!(words.get(1).map(String::as_str), Some("TABLE" | "COLUMNFAMILY")) {
+        let name = statement.split_whitespace().nth(2)?;
+        let unquote = |part: &str| {
+            if part.starts_with('"') {
+                part.trim_matches('"').to_string()
+            } else {
+                part.to_lowercase()
+            }
+        };
+        let table = match name.split_once('.') {
+            Some((keyspace, table)) => Some((unquote(keyspace), unquote(table))),
+            None => current_keyspace.map(|keyspace| (keyspace, unquote(name))),
+        };
+        if let Some((keyspace, table)) = table {
+            return Some(SchemaChange::Table(keyspace, table));
+        }
+    }
+
+    matches!(first, "CREATE" | "ALTER" | "DROP" | "USE").then_some(SchemaChange::All)
+}
+
+/// Grey placeholder for the next argument, never inserted into the line
+struct ArgumentHint(&'static str);
+
+impl Hint for ArgumentHint {
+    fn display(&self) -> &str {
+        self.0
+    }
+
+    fn completion(&self) -> Option<&str> {
+        None
     }
 }
 
@@ -145,92 +388,533 @@ impl Completer for CqlCompleter {
 }
 
 impl Hinter for CqlCompleter {
-    type Hint = String;
+    type Hint = ArgumentHint;
 
-    fn hint(&self, _line: &str, _pos: usize, _ctx: &Context<'_>) -> Option<String> {
-        None
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<ArgumentHint> {
+        if pos < line.len() || !line.ends_with(' ') {
+            return None;
+        }
+
+        match describe_argument(line)? {
+            DescribeArgument::Target => Some(ArgumentHint("<keyspaces|keyspace|tables|table|types|functions|cluster|name>")),
+            DescribeArgument::Keyspace => Some(ArgumentHint("<keyspace>")),
+            DescribeArgument::Table => Some(ArgumentHint("<[keyspace.]table>")),
+            DescribeArgument::None => None,
+        }
     }
 }
 
-impl Highlighter for CqlCompleter {}
+impl Highlighter for CqlCompleter {
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(hint.bright_black().to_string())
+    }
+}
 
 impl Validator for CqlCompleter {}
 
 impl Helper for CqlCompleter {}
 
+/// `\history [n|text]`: the last `n` entries (`HISTORY_LINES` without an
+/// argument) or all entries containing `text`, numbered by their position
+fn print_history(entries: &[&String], args: &str) {
+    let numbered = entries.iter().enumerate().map(|(index, entry)| (index + 1, entry));
+    let shown: Vec<(usize, &&String)> = match args.parse::<usize>() {
+        _ if args.is_empty() => numbered.skip(entries.len().saturating_sub(HISTORY_LINES)).collect(),
+        Ok(count) => numbered.skip(entries.len().saturating_sub(count)).collect(),
+        Err(_) => {
+            let needle = args.to_lowercase();
+            numbered.filter(|(_, entry)| entry.to_lowercase().contains(&needle)).collect()
+        }
+    };
+
+    if shown.is_empty() {
+        println!("{}", "No matching history entries".yellow());
+    }
+    let width = shown.last().map(|(number, _)| number.to_string().len()).unwrap_or(0);
+    for (number, entry) in shown {
+        println!("{}  {}", format!("{:>width$}", number, width = width).bright_black(), entry);
+    }
+}
+
+/// One CSV row of an `import`, ready to send
+struct ImportRow {
+    /// Line in the CSV file, for the error report
+    line: u64,
+    fields: Vec<String>,
+    insert: String,
+}
+
+/// `WITH TTL=<seconds> AND TIMESTAMP=<micros>` of an `import`, sent as
+/// `USING` with every INSERT
+#[derive(Debug, Default)]
+struct ImportOptions {
+    ttl: Option<u32>,
+    timestamp: Option<i64>,
+}
+
+/// The largest TTL the server accepts, 20 years
+const MAX_TTL_SECONDS: u32 = 630_720_000;
+
+impl ImportOptions {
+    /// Parses the text after `WITH`: `NAME=value` pairs joined by `AND`
+    fn parse(options: &str) -> CqlResult<Self> {
+        let invalid = |message: String| CqlError::InvalidQuery(format!("import: {}", message));
+        let separator = Regex::new(r"(?i)\s+and\s+").expect("valid regex");
+        let mut parsed = Self::default();
+
+        for option in separator.split(options.trim()) {
+            let Some((name, value)) = option.split_once('=') else {
+                return Err(invalid(format!("expected NAME=value, got '{}'", option)));
+            };
+            let value = value.trim();
+            match name.trim().to_uppercase().as_str() {
+                "TTL" if parsed.ttl.is_some() => return Err(invalid("TTL is given twice".to_string())),
+                "TTL" => {
+                    let ttl = value.parse::<u32>()
+                        .ok()
+                        .filter(|ttl| *ttl <= MAX_TTL_SECONDS)
+                        .ok_or_else(|| invalid(format!("TTL must be 0 to {} seconds, got '{}'", MAX_TTL_SECONDS, value)))?;
+                    parsed.ttl = Some(ttl);
+                }
+                "TIMESTAMP" if parsed.timestamp.is_some() => return Err(invalid("TIMESTAMP is given twice".to_string())),
+                "TIMESTAMP" => {
+                    let timestamp = value.parse::<i64>()
+                        .map_err(|_| invalid(format!("TIMESTAMP must be microseconds since the epoch, got '{}'", value)))?;
+                    parsed.timestamp = Some(timestamp);
+                }
+                other => return Err(invalid(format!("unknown option '{}' (expected TTL or TIMESTAMP)", other))),
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// ` USING ...` for the INSERTs, empty without options
+    fn using_clause(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(ttl) = self.ttl {
+            parts.push(format!("TTL {}", ttl));
+        }
+        if let Some(timestamp) = self.timestamp {
+            parts.push(format!("TIMESTAMP {}", timestamp));
+        }
+        if parts.is_empty() {
+            String::new()
+        } else {
+            format!(" USING {}", parts.join(" AND "))
+        }
+    }
+}
+
+/// The table an `import` loads into, with what each CSV row is checked
+/// against and how it is inserted
+struct ImportTarget<'a> {
+    /// `keyspace.table`
+    table: String,
+    header_names: &'a [String],
+    /// CQL type by column name
+    column_types: &'a HashMap<String, String>,
+    /// ` USING ...` from the `WITH` options, or empty
+    using: String,
+}
+
+impl ImportTarget<'_> {
+    /// The INSERT for one CSV record, or why it cannot be sent
+    fn row(&self, record: csv::Result<csv::StringRecord>) -> Result<ImportRow, ImportFailure> {
+        let record = record.map_err(|e| {
+            let line = e.position().map(|p| p.line()).unwrap_or(0);
+            ImportFailure { line, fields: Vec::new(), error: format!("CSV record read failed: {}", e) }
+        })?;
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+        let fields: Vec<String> = record.iter().map(|value| value.to_string()).collect();
+        let failed = |error: String| ImportFailure { line, fields: fields.clone(), error };
+
+        if record.len() != self.header_names.len() {
+            return Err(failed(format!(
+                "CSV row has {} values but {} columns are expected",
+                record.len(),
+                self.header_names.len()
+            )));
+        }
+
+        let non_ascii = self.header_names.iter().zip(record.iter()).find(|(header, value)| {
+            self.column_types.get(*header).is_some_and(|t| t.eq_ignore_ascii_case("ascii")) && !value.is_ascii()
+        });
+        if let Some((header, value)) = non_ascii {
+            return Err(failed(format!("value '{}' for ascii column '{}' contains non-ASCII characters", value, header)));
+        }
+
+        let values = self.header_names
+            .iter()
+            .zip(record.iter())
+            .map(|(header, value)| {
+                let cql_type = self.column_types.get(header)
+                    .map(|s| s.as_str())
+                    .unwrap_or("text");
+                Repl::csv_value_to_cql_literal(value, cql_type)
+                    .map_err(|e| format!("column '{}': {}", header, e))
+            })
+            .collect::<Result<Vec<String>, String>>()
+            .map_err(failed)?;
+
+        let insert = format!(
+            "INSERT INTO {} ({}) VALUES ({}){};",
+            self.table,
+            self.header_names.join(", "),
+            values.join(", "),
+            self.using
+        );
+        Ok(ImportRow { line, fields, insert })
+    }
+}
+
+/// A CSV row `import` could not load, and why
+struct ImportFailure {
+    line: u64,
+    fields: Vec<String>,
+    error: String,
+}
+
+/// What the REPL commands can change, as it was when the REPL started, so
+/// `\reset` can go back to it
+struct SessionToggles {
+    output_format: String,
+    format_options: FormatOptions,
+    consistency: Consistency,
+    serial_consistency: SerialConsistency,
+    page_timeout: Option<std::time::Duration>,
+    allow_filtering: bool,
+}
+
 pub struct Repl {
     executor: QueryExecutor,
     output_format: String,
+    format_options: FormatOptions,
     completer: CqlCompleter,
+    wide_table_threshold: Option<usize>,
+    last_query: Option<String>,
+    completion_cache: Option<PathBuf>,
+    /// Result of the previous `\diff` run per statement
+    diff_snapshots: HashMap<String, ResultSnapshot>,
+    import_batch_size: usize,
+    import_max_batches_inflight: usize,
+    /// Directories of the scripts being run by `\i`, innermost last
+    script_dirs: Vec<PathBuf>,
+    /// How long loading the schema for completion may take; `None` waits
+    schema_refresh_timeout: Option<std::time::Duration>,
+    startup_toggles: Option<SessionToggles>,
+    /// Opened by the first `\clip` and kept for the session: on X11 and
+    /// Wayland the copied text is only served while the handle lives
+    clipboard: Option<arboard::Clipboard>,
 }
 
 impl Repl {
-    pub fn new(executor: QueryExecutor, output_format: String) -> Self {
+    pub fn new(executor: QueryExecutor, output_format: String, format_options: FormatOptions) -> Self {
         Self {
             executor,
             output_format,
+            format_options,
             completer: CqlCompleter::new(),
+            wide_table_threshold: None,
+            last_query: None,
+            completion_cache: None,
+            diff_snapshots: HashMap::new(),
+            import_batch_size: 1,
+            import_max_batches_inflight: 1,
+            script_dirs: Vec::new(),
+            schema_refresh_timeout: None,
+            startup_toggles: None,
+            clipboard: None,
         }
     }
 
-    async fn refresh_schema(&mut self) -> CqlResult<()> {
-        match self.executor.execute("SELECT keyspace_name FROM system_schema.keyspaces").await {
-            Ok(result) => {
-                if let Some(rows) = result.rows {
-                    let keyspaces: Vec<String> = rows.iter()
-                        .filter_map(|row| {
-                            row.columns.first()
-                                .and_then(|col| {
-                                    if let Some(scylla::frame::response::result::CqlValue::Text(name)) = col {
-                                        Some(name.clone())
-                                    } else {
-                                        None
-                                    }
-                                })
-                        })
-                        .collect();
-                    self.completer.update_keyspaces(keyspaces);
-                }
+    fn toggles(&self) -> SessionToggles {
+        SessionToggles {
+            output_format: self.output_format.clone(),
+            format_options: self.format_options.clone(),
+            consistency: self.executor.consistency(),
+            serial_consistency: self.executor.serial_consistency(),
+            page_timeout: self.executor.page_timeout(),
+            allow_filtering: self.executor.allow_filtering(),
+        }
+    }
+
+    /// `\reset`: restores the output format, row numbers, expanded display, row filter,
+    /// consistency levels, page timeout and ALLOW FILTERING to what they
+    /// were at startup, keeping the connection and keyspace
+    fn reset_toggles(&mut self) {
+        let Some(startup) = &self.startup_toggles else {
+            return;
+        };
+        self.output_format = startup.output_format.clone();
+        self.format_options = startup.format_options.clone();
+        self.executor.set_consistency(startup.consistency);
+        self.executor.set_serial_consistency(startup.serial_consistency);
+        self.executor.set_page_timeout(startup.page_timeout);
+        self.executor.set_allow_filtering(startup.allow_filtering);
+
+        println!(
+            "Reset to startup settings: output format ({}), row numbers, expanded display, row filter, consistency ({}), serial consistency ({}), page timeout, ALLOW FILTERING.",
+            self.output_format.cyan(),
+            consistency_name(startup.consistency).cyan(),
+            serial_consistency_name(startup.serial_consistency).cyan()
+        );
+    }
+
+    /// Warn before `SELECT *` on tables with more than `threshold` columns
+    pub fn set_wide_table_threshold(&mut self, threshold: Option<usize>) {
+        self.wide_table_threshold = threshold;
+    }
+
+    pub fn set_fuzzy_completion(&mut self, fuzzy: bool) {
+        self.completer.fuzzy = fuzzy;
+    }
+
+    pub fn set_schema_refresh_timeout(&mut self, timeout: Option<std::time::Duration>) {
+        self.schema_refresh_timeout = timeout;
+    }
+
+    /// `path` relative to the script currently run by `\i`, like psql's
+    /// `\ir`, so scripts can include files next to them wherever cqlrs was
+    /// started. Outside of scripts relative paths stay relative to the CWD.
+    fn resolve_path(&self, path: &str) -> PathBuf {
+        let path = Path::new(path);
+        match self.script_dirs.last() {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// `\i <file>`: runs the statements in `path`. Lines of the script may
+    /// themselves be `\i`, `export` or `import` commands, whose paths are
+    /// resolved relative to the script. A failing statement is reported and
+    /// the script goes on, like in the REPL.
+    fn source_file<'a>(&'a mut self, path: &'a str) -> Pin<Box<dyn Future<Output = CqlResult<()>> + 'a>> {
+        Box::pin(async move {
+            if self.script_dirs.len() >= MAX_INCLUDE_DEPTH {
+                return Err(CqlError::InvalidQuery(format!(
+                    "\\i nested more than {} levels deep, is {} including itself?",
+                    MAX_INCLUDE_DEPTH, path
+                )));
+            }
+
+            let path = self.resolve_path(path);
+            let content = files::read_script(&path)
+                .map_err(|e| CqlError::ConfigError(format!("Failed to read {}: {}", path.display(), e)))?;
+            let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+            self.script_dirs.push(dir);
+            let result = self.run_script(&content).await;
+            self.script_dirs.pop();
+            result
+        })
+    }
+
+    async fn run_script(&mut self, content: &str) -> CqlResult<()> {
+        let mut pending = String::new();
+        for line in content.lines() {
+            let trimmed = line.trim();
+            let lowercase = trimmed.to_lowercase();
+            let is_command = trimmed.starts_with("\\i ")
+                || lowercase.starts_with("export ")
+                || lowercase.starts_with("import ");
+            if !is_command {
+                pending.push_str(line);
+                pending.push('\n');
+                continue;
+            }
+
+            self.run_statements(&std::mem::take(&mut pending)).await;
+            let outcome = match trimmed.strip_prefix("\\i ") {
+                Some(include) => self.source_file(include.trim()).await,
+                None if lowercase.starts_with("export ") => self.handle_export_command(trimmed).await,
+                None => self.handle_import_command(trimmed).await,
+            };
+            if let Err(e) = outcome {
+                eprintln!("{} {}", "Error:".red().bold(), e);
             }
-            Err(_) => {} 
         }
+        self.run_statements(&pending).await;
+        Ok(())
+    }
 
-        match self.executor.execute("SELECT keyspace_name, table_name FROM system_schema.tables").await {
-            Ok(result) => {
-                if let Some(rows) = result.rows {
-                    let tables: Vec<String> = rows.iter()
-                        .filter_map(|row| {
-                            if row.columns.len() >= 2 {
-                                if let Some(scylla::frame::response::result::CqlValue::Text(table)) = &row.columns[1] {
-                                    Some(table.clone())
-                                } else {
-                                    None
-                                }
-                            } else {
-                                None
-                            }
-                        })
-                        .collect();
-                    self.completer.update_tables(tables);
+    async fn run_statements(&mut self, script: &str) {
+        for statement in split_statements(script) {
+            match self.executor.execute_and_print(&statement, &self.output_format, &self.format_options).await {
+                Ok(_) => {
+                    self.refresh_after(&statement).await;
                 }
+                Err(e) => eprintln!("{} {}", "Error:".red().bold(), e),
             }
-            Err(_) => {} 
         }
+    }
+
+    /// Rows per unlogged batch and batches sent concurrently by `import`
+    pub fn set_import_batching(&mut self, batch_size: usize, max_inflight: usize) {
+        self.import_batch_size = batch_size.max(1);
+        self.import_max_batches_inflight = max_inflight.max(1);
+    }
+
+    /// Seed completion from a file written by `\dump-completion-cache`
+    /// instead of querying the schema at startup. The cache is replaced by
+    /// live data on `\refresh` and after schema changes.
+    pub fn set_completion_cache(&mut self, path: Option<PathBuf>) {
+        self.completion_cache = path;
+    }
+
+    fn load_completion_cache(&mut self, path: &Path) -> CqlResult<()> {
+        let content = files::read_to_string(path)?;
+        let cache: CompletionCache = serde_json::from_str(&content)
+            .map_err(|e| CqlError::ConfigError(format!("Invalid completion cache {}: {}", path.display(), e)))?;
+        self.completer.load_cache(cache);
+        Ok(())
+    }
+
+    fn dump_completion_cache(&self, path: &str) -> CqlResult<()> {
+        let json = serde_json::to_string_pretty(&self.completer.to_cache())
+            .map_err(|e| CqlError::QueryError(format!("JSON serialization error: {}", e)))?;
+        files::write(path, json)?;
+        println!(
+            "{} {} ({})",
+            "Wrote completion cache to".green(),
+            path.cyan(),
+            format!("{} keyspace(s), {} table(s)", self.completer.keyspaces.len(), self.completer.tables.len()).bright_black()
+        );
+        Ok(())
+    }
 
+    /// Reloads keyspaces, tables and columns for completion. Listing the
+    /// schema of clusters with thousands of tables can take long, so this
+    /// gives up on Ctrl-C or after `--schema-refresh-timeout` and leaves
+    /// completion as it was (keywords only at startup).
+    async fn refresh_schema(&mut self) -> CqlResult<()> {
+        let timeout = self.schema_refresh_timeout;
+        let expired = async {
+            match timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        let cache = tokio::select! {
+            cache = self.load_schema() => cache,
+            _ = tokio::signal::ctrl_c() => {
+                return Err(CqlError::Timeout("schema refresh interrupted".to_string()));
+            }
+            _ = expired => {
+                return Err(CqlError::Timeout(format!(
+                    "schema refresh did not finish within {:.1}s",
+                    timeout.unwrap_or_default().as_secs_f64()
+                )));
+            }
+        };
+        self.completer.load_cache(cache);
         Ok(())
     }
 
+    /// The schema as the completer needs it; parts that fail to load keep
+    /// their current contents
+    async fn load_schema(&self) -> CompletionCache {
+        let mut cache = self.completer.to_cache();
+
+        if let Ok(result) = self.executor.execute("SELECT keyspace_name FROM system_schema.keyspaces").await {
+            if let Ok(rows) = result.rows_typed::<(String,)>() {
+                cache.keyspaces = rows.flatten().map(|(keyspace,)| keyspace).collect();
+            }
+        }
+
+        if let Ok(result) = self.executor.execute("SELECT keyspace_name, table_name FROM system_schema.tables").await {
+            if let Ok(rows) = result.rows_typed::<(String, String)>() {
+                cache.tables = rows.flatten().map(|(_, table)| table).collect();
+            }
+        }
+
+        if let Some(columns) = self.load_columns(None).await {
+            cache.columns = columns.into_iter().collect();
+        }
+        cache
+    }
+
+    /// Reloads the column names of one `(keyspace, table)`, or of all tables
+    async fn refresh_columns(&mut self, table: Option<(String, String)>) {
+        let Some(mut columns) = self.load_columns(table.clone()).await else {
+            return;
+        };
+
+        match table {
+            Some((keyspace, table)) => {
+                let key = format!("{}.{}", keyspace, table);
+                let table_columns = columns.remove(&key).unwrap_or_default();
+                self.completer.update_table_columns(key, table_columns);
+            }
+            None => self.completer.update_columns(columns),
+        }
+    }
+
+    /// Column names per `keyspace.table`, of one table or of all tables
+    async fn load_columns(&self, table: Option<(String, String)>) -> Option<HashMap<String, Vec<String>>> {
+        let query = match &table {
+            Some((keyspace, table)) => format!(
+                "SELECT keyspace_name, table_name, column_name FROM system_schema.columns \
+                WHERE keyspace_name = '{}' AND table_name = '{}'",
+                keyspace.replace('\'', "''"),
+                table.replace('\'', "''")
+            ),
+            None => "SELECT keyspace_name, table_name, column_name FROM system_schema.columns".to_string(),
+        };
+        let result = self.executor.execute(&query).await.ok()?;
+        let rows = result.rows_typed::<(String, String, String)>().ok()?;
+
+        let mut columns: HashMap<String, Vec<String>> = HashMap::new();
+        for (keyspace, table, column) in rows.flatten() {
+            columns.entry(format!("{}.{}", keyspace, table)).or_default().push(column);
+        }
+        Some(columns)
+    }
+
+    /// Refreshes the part of the completion cache `statement` changed, if it
+    /// is a schema statement: only the altered table's columns after
+    /// `ALTER TABLE`, everything after other DDL
+    async fn refresh_after(&mut self, statement: &str) -> bool {
+        match schema_change(statement, self.executor.current_keyspace()) {
+            Some(SchemaChange::Table(keyspace, table)) => {
+                self.refresh_columns(Some((keyspace, table))).await;
+                true
+            }
+            Some(SchemaChange::All) => {
+                let _ = self.refresh_schema().await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn refresh_schema_at_startup(&mut self) {
+        if let Err(e) = self.refresh_schema().await {
+            eprintln!("{} {} - completing keywords only, \\refresh retries", "Warning:".yellow().bold(), e);
+        }
+    }
+
     pub async fn run(&mut self) -> CqlResult<()> {
         println!("{}", "=== CQL Rust Client ===".bright_cyan().bold());
         println!("{}", "Type 'help' for available commands, 'quit' or 'exit' to exit.".bright_black());
         println!("{}", "Auto-completion enabled: Use TAB to complete CQL keywords, keyspaces, and tables.".bright_black());
         println!();
 
-        let _ = self.refresh_schema().await;
+        self.startup_toggles = Some(self.toggles());
+
+        match self.completion_cache.clone() {
+            Some(path) => {
+                if let Err(e) = self.load_completion_cache(&path) {
+                    eprintln!("{} {} - loading the schema instead", "Warning:".yellow().bold(), e);
+                    self.refresh_schema_at_startup().await;
+                }
+            }
+            None => self.refresh_schema_at_startup().await,
+        }
 
         let mut rl = Editor::<CqlCompleter, DefaultHistory>::new()
             .map_err(|e| crate::error::CqlError::IoError(
-                std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
+                std::io::Error::other(e.to_string())
             ))?;
         
         rl.set_helper(Some(self.completer.clone()));
@@ -269,7 +953,7 @@ impl Repl {
                                 break;
                             }
                             "help" => {
-                                self.print_help();
+                                self.print_help(None);
                                 continue;
                             }
                             "clear" => {
@@ -281,6 +965,13 @@ impl Repl {
                         }
                     }
 
+                    if multi_line_query.is_empty() {
+                        if let Some(topic) = line.strip_prefix("help ") {
+                            self.print_help(Some(topic.trim()));
+                            continue;
+                        }
+                    }
+
                     if line.to_lowercase().starts_with("export ") {
                         if let Err(e) = self.handle_export_command(line).await {
                             eprintln!("{} {}", "Error:".red().bold(), e);
@@ -295,13 +986,176 @@ impl Repl {
                         continue;
                     }
 
-                    if line.starts_with("\\format ") {
-                        let new_format = line[8..].trim();
+                    if let Some(new_format) = line.strip_prefix("\\format ") {
+                        let new_format = new_format.trim();
                         self.output_format = new_format.to_string();
                         println!("Output format set to: {}", new_format.cyan());
                         continue;
                     }
 
+                    if line == "\\grep" || line.starts_with("\\grep ") {
+                        self.handle_grep_command(line[5..].trim());
+                        continue;
+                    }
+
+                    if let Some(arg) = line.strip_prefix("\\rownum") {
+                        match arg.trim().to_lowercase().as_str() {
+                            "on" => self.format_options.row_numbers = true,
+                            "off" => self.format_options.row_numbers = false,
+                            "" => self.format_options.row_numbers = !self.format_options.row_numbers,
+                            _ => {
+                                println!("Usage: \\rownum [on|off]");
+                                continue;
+                            }
+                        }
+                        println!("Row numbers: {}", if self.format_options.row_numbers { "on" } else { "off" }.cyan());
+                        continue;
+                    }
+
+                    if line == "\\expand" || line.starts_with("\\expand ") {
+                        match line[7..].trim().to_lowercase().as_str() {
+                            "on" => self.format_options.expanded = true,
+                            "off" => self.format_options.expanded = false,
+                            "" => self.format_options.expanded = !self.format_options.expanded,
+                            _ => {
+                                println!("Usage: \\expand [on|off]");
+                                continue;
+                            }
+                        }
+                        println!("Expanded display: {}", if self.format_options.expanded { "on" } else { "off" }.cyan());
+                        continue;
+                    }
+
+                    if multi_line_query.is_empty() {
+                        let command = line.trim_end_matches(';').to_lowercase();
+                        let consistency_args = ["\\consistency", "consistency"].iter()
+                            .find_map(|prefix| command.strip_prefix(prefix))
+                            .map(|args| (false, args));
+                        let serial_args = ["\\serial-consistency", "serial consistency"].iter()
+                            .find_map(|prefix| command.strip_prefix(prefix))
+                            .map(|args| (true, args));
+                        if let Some((serial, args)) = consistency_args.or(serial_args) {
+                            if args.is_empty() || args.starts_with(char::is_whitespace) {
+                                self.handle_consistency_command(args.trim(), serial);
+                                continue;
+                            }
+                        }
+                    }
+
+                    if line == "\\watch" || line.starts_with("\\watch ") {
+                        if let Err(e) = self.handle_watch_command(line[6..].trim()).await {
+                            eprintln!("{} {}", "Error:".red().bold(), e);
+                        }
+                        continue;
+                    }
+
+                    if line == "\\diff" || line.starts_with("\\diff ") {
+                        if let Err(e) = self.handle_diff_command(line[5..].trim()).await {
+                            eprintln!("{} {}", "Error:".red().bold(), e);
+                        }
+                        continue;
+                    }
+
+                    if line == "\\browse" || line.starts_with("\\browse ") {
+                        let query = Some(line[7..].trim().to_string())
+                            .filter(|q| !q.is_empty())
+                            .or_else(|| self.last_query.clone());
+                        match query {
+                            Some(query) => {
+                                if let Err(e) = self.executor.browse(&query, &self.output_format, &self.format_options).await {
+                                    eprintln!("{} {}", "Error:".red().bold(), e);
+                                }
+                                self.last_query = Some(query);
+                            }
+                            None => println!("{}", "No query to browse. Pass one or run a query first.".yellow()),
+                        }
+                        continue;
+                    }
+
+                    if line == "\\bench" || line.starts_with("\\bench ") {
+                        self.handle_bench_command(line[6..].trim()).await;
+                        continue;
+                    }
+
+                    if line == "\\page-timeout" || line.starts_with("\\page-timeout ") {
+                        self.handle_page_timeout_command(line[13..].trim());
+                        continue;
+                    }
+
+                    if let Some(path) = line.strip_prefix("\\i ") {
+                        if let Err(e) = self.source_file(path.trim()).await {
+                            eprintln!("{} {}", "Error:".red().bold(), e);
+                        }
+                        rl.set_helper(Some(self.completer.clone()));
+                        continue;
+                    }
+
+                    if line == "\\history" || line.starts_with("\\history ") {
+                        let args = line[8..].trim();
+                        if args == "clear" {
+                            let _ = rl.clear_history();
+                            if let Some(ref path) = history_file {
+                                let _ = std::fs::remove_file(path);
+                            }
+                            println!("History cleared.");
+                        } else {
+                            // Without the \history line that was just added
+                            let entries: Vec<&String> = rl.history().iter().collect();
+                            print_history(&entries[..entries.len().saturating_sub(1)], args);
+                        }
+                        continue;
+                    }
+
+                    if line == "\\allow-filtering" || line.starts_with("\\allow-filtering ") {
+                        match line[16..].trim().to_lowercase().as_str() {
+                            "on" => self.executor.set_allow_filtering(true),
+                            "off" => self.executor.set_allow_filtering(false),
+                            "" => {}
+                            _ => {
+                                println!("Usage: \\allow-filtering [on|off]");
+                                continue;
+                            }
+                        }
+                        let state = if self.executor.allow_filtering() { "allowed" } else { "refused" };
+                        println!("Statements with ALLOW FILTERING are {}.", state);
+                        continue;
+                    }
+
+                    if line == "\\uuid-time" || line.starts_with("\\uuid-time ") {
+                        self.handle_uuid_time_command(line[10..].trim());
+                        continue;
+                    }
+
+                    if line == "\\clip" {
+                        self.copy_last_output();
+                        continue;
+                    }
+
+                    if line == "\\settings" {
+                        self.print_settings();
+                        continue;
+                    }
+
+                    if line == "\\reset" {
+                        self.reset_toggles();
+                        continue;
+                    }
+
+                    if line == "\\connect" || line.starts_with("\\connect ") {
+                        match self.handle_connect_command(line[8..].trim()).await {
+                            Ok(true) => {
+                                let _ = self.refresh_schema().await;
+                                rl.set_helper(Some(self.completer.clone()));
+                            }
+                            Ok(false) => {}
+                            Err(e) => {
+                                eprintln!("{} {}", "Error:".red().bold(), e);
+                                println!("{}", "Still connected to the previous session.".yellow());
+                            }
+                        }
+                        continue;
+                    }
+
                     if line == "\\refresh" {
                         println!("{}", "Refreshing schema...".cyan());
                         match self.refresh_schema().await {
@@ -316,6 +1170,31 @@ impl Repl {
                         continue;
                     }
 
+                    if let Some(path) = line.strip_prefix("\\dump-completion-cache") {
+                        let path = path.trim();
+                        if path.is_empty() {
+                            println!("Usage: \\dump-completion-cache <file.json>");
+                        } else if let Err(e) = self.dump_completion_cache(path) {
+                            eprintln!("{} {}", "Error:".red().bold(), e);
+                        }
+                        continue;
+                    }
+
+                    if let Some(query) = line.strip_prefix("\\describe-result") {
+                        let query = query.trim();
+                        if query.is_empty() {
+                            println!("Usage: \\describe-result <query>");
+                        } else if let Err(e) = self.executor.describe_result(query, &self.output_format).await {
+                            eprintln!("{} {}", "Error:".red().bold(), e);
+                        }
+                        continue;
+                    }
+
+                    if line == "\\generate-ddl" || line.starts_with("\\generate-ddl ") {
+                        self.handle_generate_ddl_command(line["\\generate-ddl".len()..].trim()).await;
+                        continue;
+                    }
+
                     if line.starts_with("\\d") || line.to_lowercase().starts_with("describe ") {
                         self.handle_describe_command(line).await;
                         continue;
@@ -329,11 +1208,11 @@ impl Repl {
                     }
 
                     if multi_line_query.ends_with(';') {
-                        match self.executor.execute_and_print(&multi_line_query, &self.output_format).await {
+                        self.last_query = Some(multi_line_query.clone());
+                        self.warn_if_wide_select(&multi_line_query).await;
+                        match self.executor.execute_and_print(&multi_line_query, &self.output_format, &self.format_options).await {
                             Ok(_) => {
-                                let query_upper = multi_line_query.to_uppercase();
-                                if query_upper.contains("CREATE ") || query_upper.contains("DROP ") || query_upper.contains("USE ") {
-                                    let _ = self.refresh_schema().await;
+                                if self.refresh_after(&multi_line_query).await {
                                     rl.set_helper(Some(self.completer.clone()));
                                 }
                             }
@@ -366,35 +1245,493 @@ impl Repl {
         Ok(())
     }
 
-    fn print_help(&self) {
-        println!("{}", "=== Available Commands ===".bright_cyan().bold());
-        println!("  {}  - Exit the REPL", "quit, exit".green());
-        println!("  {}        - Show this help message", "help".green());
-        println!("  {}       - Clear the screen", "clear".green());
-        println!("  {}  - Change output format (table, json, csv)", "\\format <fmt>".green());
-        println!("  {}   - List all keyspaces", "\\dk".green());
-        println!("  {} - List tables in keyspace", "\\dt [keyspace]".green());
-        println!("  {}   - Refresh schema cache", "\\refresh".green());
-        println!("  {} - Export table data to CSV", "export <table> [file.csv]".green());
-        println!("  {} - Import table data from CSV", "import <table> [file.csv]".green());
-        println!();
-        println!("{}", "=== Auto-Completion ===".bright_cyan().bold());
-        println!("  Press {} to auto-complete:", "TAB".yellow().bold());
-        println!("  - CQL keywords (SELECT, INSERT, CREATE, etc.)");
-        println!("  - Keyspace names (after USE, CREATE KEYSPACE, etc.)");
-        println!("  - Table names (after FROM, INTO, TABLE, etc.)");
-        println!();
-        println!("{}", "=== CQL Commands ===".bright_cyan().bold());
-        println!("  Execute any CQL query ending with {}.", ";".yellow());
-        println!("  Multi-line queries are supported.");
-        println!();
-        println!("{}", "Examples:".bright_black());
-        println!("  SELECT * FROM system.local;");
-        println!("  USE my_keyspace;");
-        println!("  DESCRIBE KEYSPACES;");
+    /// Prints the topic list for a bare `help`, or the focused help for
+    /// `help <topic>`. New features add a line to their topic (or a new
+    /// entry in `HELP_TOPICS`) instead of growing one large block.
+    fn print_help(&self, topic: Option<&str>) {
+        let Some(topic) = topic else {
+            println!("{}", "=== Help ===".bright_cyan().bold());
+            println!("  Type {} to show help for a topic:", "help <topic>".green());
+            for (name, summary) in HELP_TOPICS {
+                println!("  {:<12} {}", name.green(), summary);
+            }
+            println!();
+            println!("  Execute any CQL query ending with {}. {} leaves the REPL.", ";".yellow(), "quit".green());
+            return;
+        };
+
+        let topic = topic.to_lowercase();
+        match topic.as_str() {
+            "commands" => {
+                println!("{}", "=== Available Commands ===".bright_cyan().bold());
+                println!("  {}  - Exit the REPL", "quit, exit".green());
+                println!("  {} - Show help, optionally for a topic", "help [topic]".green());
+                println!("  {}       - Clear the screen", "clear".green());
+                println!("  {}   - Refresh schema cache", "\\refresh".green());
+                println!("  {} - Show or set the consistency level", "\\consistency [level]".green());
+                println!("  {} - Show or set the serial consistency level", "\\serial-consistency [level]".green());
+                println!("  {} - Switch to another host, keeping credentials and SSL settings", "\\connect <host[:port]> [keyspace]".green());
+                println!("  {}  - Show the current session settings", "\\settings".green());
+                println!("  {}     - Restore format, row filter, consistency, page timeout and ALLOW FILTERING to their startup values", "\\reset".green());
+                println!("  {} - Re-run a query (default: the last one) every N seconds until Ctrl-C", "\\watch <secs> [query]".green());
+                println!("  {} - Show rows added/removed/changed since the last \\diff of the same query", "\\diff [query]".green());
+                println!("  {} - Show or set the time limit per result page of a SELECT", "\\page-timeout [secs|off]".green());
+                println!("  {} - Show the last n history entries, those containing text, or clear it", "\\history [n|text|clear]".green());
+                println!("  {} - Show or set whether statements with ALLOW FILTERING may run", "\\allow-filtering [on|off]".green());
+                println!("  {} - Run a query N times and show latency statistics", "\\bench <n> <query>".green());
+                println!("  {} - Run the statements in a file; paths inside it are relative to the file", "\\i <file>".green());
+                println!("  {} - Check a UUID and show the time embedded in a TimeUUID", "\\uuid-time <uuid>".green());
+                println!("  {} - Export table data to CSV", "export <table> [file.csv]".green());
+                println!("  {} - Import table data from CSV", "import <table> [file.csv]".green());
+                println!("  {} - Give the imported rows a TTL and/or write time", "import ... WITH TTL=<secs> AND TIMESTAMP=<micros>".green());
+            }
+            "formats" => {
+                println!("{}", "=== Output Formats ===".bright_cyan().bold());
+                println!("  {}  - Change output format", "\\format <fmt>".green());
+                println!("    {}     - Boxed table (default)", "table".yellow());
+                println!("    {}      - Pretty-printed {{\"rows\": [...], \"count\": N}}", "json".yellow());
+                println!("    {}      - The same document as YAML (rows: ..., count: N)", "yaml".yellow());
+                println!("    {}  - One object per row, encoded like SELECT JSON", "cql-json".yellow());
+                println!("    {}       - Comma separated values with header", "csv".yellow());
+                println!("    {} - One line per row, e.g. template:{{id}} -> {{name}}", "template:<t>".yellow());
+                println!("    {} - One INSERT statement per row (inserts:<ks.table> for another table)", "inserts".yellow());
+                println!("    {}      - Self-contained HTML page with a striped table", "html".yellow());
+                println!("  {} - Number table rows (no argument toggles)", "\\rownum [on|off]".green());
+                println!("  {} - Show each row as a block of column | value lines (no argument toggles)", "\\expand [on|off]".green());
+                println!("  {} - Only show rows matching a regex (no argument or 'off' clears)", "\\grep [regex]".green());
+                println!("  {}      - Copy the last result to the clipboard", "\\clip".green());
+                println!("  {} - Scroll through a result full-screen (default: the last query)", "\\browse [query]".green());
+                println!();
+                println!("  Start options: --colorize-values, --float-precision N, --csv-excel, --no-color");
+            }
+            "describe" => {
+                println!("{}", "=== Schema Inspection ===".bright_cyan().bold());
+                println!("  {}   - List all keyspaces", "\\dk".green());
+                println!("  {} - List tables, optionally of one keyspace", "\\dt [keyspace]".green());
+                println!("  {} - Run a DESCRIBE statement on the server", "describe <...>".green());
+                println!("  {} - Show the columns a query returns without running it", "\\describe-result <query>".green());
+                println!("  {} - Propose a CREATE TABLE for the columns of a query (default: the last one)", "\\generate-ddl <table> [query]".green());
+                println!();
+                println!("{}", "Examples:".bright_black());
+                println!("  DESCRIBE KEYSPACES;");
+                println!("  DESCRIBE TABLE my_keyspace.users;");
+            }
+            "ssl" => {
+                let config = self.executor.connection().config();
+                println!("{}", "=== SSL/TLS ===".bright_cyan().bold());
+                println!("  TLS is configured when cqlrs starts:");
+                println!("  {}              - Connect with SSL/TLS", "--ssl".green());
+                println!("  {} - Trust this CA certificate", "--ssl-ca-cert <file>".green());
+                println!("  {} - Client certificate and key for mutual TLS", "--ssl-client-cert/--ssl-client-key".green());
+                println!("  {}       - Verify server certificates", "--ssl-verify".green());
+                println!("  {}         - SSL/TLS without verification (development only)", "--insecure".green());
+                println!("  PEM content can also be passed in CQLRS_SSL_CA, CQLRS_SSL_CERT and CQLRS_SSL_KEY.");
+                println!();
+                println!(
+                    "  This session: {}",
+                    if config.ssl_enabled {
+                        if config.ssl_verify { "SSL/TLS, certificates verified" } else { "SSL/TLS, certificates NOT verified" }
+                    } else {
+                        "plain text"
+                    }.cyan()
+                );
+            }
+            "completion" => {
+                println!("{}", "=== Auto-Completion ===".bright_cyan().bold());
+                println!("  Press {} to auto-complete:", "TAB".yellow().bold());
+                println!("  - CQL keywords (SELECT, INSERT, CREATE, etc.)");
+                println!("  - Keyspace names (after USE, CREATE KEYSPACE, etc.)");
+                println!("  - Table names (after FROM, INTO, TABLE, etc.)");
+                println!("  - Column names (after SELECT, WHERE, SET, BY, INTO)");
+                println!("  The cache is updated after CREATE, ALTER and DROP statements run in this session.");
+                println!("  - DESCRIBE targets, then keyspace or table names (a grey hint shows what is expected)");
+                println!("  Use {} after schema changes made by other clients.", "\\refresh".green());
+                println!("  {} - Save the cached schema as JSON (load it with --completion-cache)", "\\dump-completion-cache <file>".green());
+            }
+            "cql" => {
+                println!("{}", "=== CQL Commands ===".bright_cyan().bold());
+                println!("  Execute any CQL query ending with {}.", ";".yellow());
+                println!("  Multi-line queries are supported.");
+                println!();
+                println!("{}", "Examples:".bright_black());
+                println!("  SELECT * FROM system.local;");
+                println!("  USE my_keyspace;");
+                println!("  DESCRIBE KEYSPACES;");
+            }
+            _ => {
+                println!("{} '{}'", "Unknown help topic".yellow(), topic);
+                println!(
+                    "Available topics: {}",
+                    HELP_TOPICS.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")
+                );
+            }
+        }
         println!();
     }
 
+    /// Switches to another host, reusing credentials and SSL settings.
+    /// Returns whether the session was replaced.
+    async fn handle_connect_command(&mut self, args: &str) -> CqlResult<bool> {
+        let mut parts = args.split_whitespace();
+        let Some(target) = parts.next() else {
+            println!("Usage: \\connect <host[:port]> [keyspace]");
+            return Ok(false);
+        };
+
+        let mut config = self.executor.connection().config().clone();
+        let (host, port) = match target.rsplit_once(':') {
+            Some((host, port)) if !host.contains(':') => {
+                let port = port.parse::<u16>()
+                    .map_err(|_| CqlError::ConfigError(format!("Invalid port '{}'", port)))?;
+                (host, port)
+            }
+            _ => (target, config.port),
+        };
+        config.hosts = vec![host.to_string()];
+        config.port = port;
+        // --connect-retries is meant for startup, a typo here should fail fast
+        config.connect_retries = 0;
+        config.keyspace = parts.next().map(|ks| ks.trim_end_matches(';').to_string());
+
+        println!("Connecting to {}:{}...", host.cyan(), port);
+        self.executor.reconnect(config).await?;
+        println!("{}", "Connected.".green());
+        if let Some(warning) = self.executor.connection().degraded_warning() {
+            eprintln!("{} {}", "Warning:".yellow().bold(), warning);
+        }
+        if let Some(keyspace) = self.executor.current_keyspace() {
+            println!("Now using keyspace: {}", keyspace);
+        }
+        Ok(true)
+    }
+
+    /// `\page-timeout [seconds|off]`: print or set the per-page timeout
+    fn handle_page_timeout_command(&mut self, args: &str) {
+        match args.to_lowercase().as_str() {
+            "" => {}
+            "off" => self.executor.set_page_timeout(None),
+            seconds => match seconds.parse::<f64>().ok().and_then(|s| std::time::Duration::try_from_secs_f64(s).ok()) {
+                Some(timeout) if !timeout.is_zero() => self.executor.set_page_timeout(Some(timeout)),
+                _ => {
+                    println!("Usage: \\page-timeout [seconds|off]");
+                    return;
+                }
+            },
+        }
+
+        match self.executor.page_timeout() {
+            Some(timeout) => println!("Page timeout is {:.1}s.", timeout.as_secs_f64()),
+            None => println!("Page timeout is off (driver default)."),
+        }
+    }
+
+    /// `\uuid-time <uuid>`: checks that the argument is a UUID and prints the
+    /// creation time of TimeUUIDs (version 1) as ISO-8601, without asking
+    /// the cluster
+    fn handle_uuid_time_command(&self, args: &str) {
+        let text = args.trim_end_matches(';').trim().trim_matches('\'');
+        if text.is_empty() {
+            println!("Usage: \\uuid-time <uuid>");
+            return;
+        }
+
+        let uuid = match uuid::Uuid::parse_str(text) {
+            Ok(uuid) => uuid,
+            Err(e) => {
+                eprintln!("{} '{}' is not a valid UUID: {}", "Error:".red().bold(), text, e);
+                return;
+            }
+        };
+
+        let version = uuid.get_version_num();
+        let timestamp = uuid.get_timestamp()
+            .map(|timestamp| timestamp.to_unix())
+            .and_then(|(seconds, nanos)| chrono::DateTime::from_timestamp(seconds as i64, nanos));
+        match timestamp {
+            Some(time) if version == 1 => {
+                println!("Valid TimeUUID (version 1)");
+                println!("Timestamp: {}", time.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true).cyan());
+            }
+            Some(time) => {
+                println!("Valid UUID (version {}), not a TimeUUID", version);
+                println!("Embedded time: {}", time.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true).cyan());
+            }
+            None => println!("Valid UUID (version {}), not a TimeUUID: it carries no timestamp", version),
+        }
+    }
+
+    /// `\consistency [level]` and `\serial-consistency [level]`: print the
+    /// current level without an argument, like cqlsh, or set it
+    fn handle_consistency_command(&mut self, level: &str, serial: bool) {
+        let (label, current) = if serial {
+            ("serial consistency", serial_consistency_name(self.executor.serial_consistency()))
+        } else {
+            ("consistency", consistency_name(self.executor.consistency()))
+        };
+
+        if level.is_empty() {
+            println!("Current {} level is {}.", label, current.cyan());
+            return;
+        }
+
+        let name = if serial {
+            parse_serial_consistency(level).map(|consistency| {
+                self.executor.set_serial_consistency(consistency);
+                serial_consistency_name(consistency)
+            })
+        } else {
+            parse_consistency(level).map(|consistency| {
+                self.executor.set_consistency(consistency);
+                consistency_name(consistency)
+            })
+        };
+
+        match name {
+            Some(name) => println!("{} level set to {}.", if serial { "Serial consistency" } else { "Consistency" }, name.cyan()),
+            None => {
+                let valid = if serial {
+                    vec!["SERIAL", "LOCAL_SERIAL"]
+                } else {
+                    consistency_names()
+                };
+                println!("{} '{}'. Valid levels: {}", "Unknown consistency level".yellow(), level, valid.join(", "));
+            }
+        }
+    }
+
+    async fn handle_bench_command(&self, args: &str) {
+        let parsed = args.split_once(char::is_whitespace)
+            .and_then(|(n, query)| Some((n.parse::<usize>().ok()?, query.trim())))
+            .filter(|(n, query)| *n > 0 && !query.is_empty());
+        let Some((iterations, query)) = parsed else {
+            println!("Usage: \\bench <n> <query>");
+            return;
+        };
+
+        println!("Running {} iteration(s) after {} warm-up run(s)...", iterations.to_string().cyan(), BENCH_WARMUP);
+        match self.executor.benchmark(query, iterations, BENCH_WARMUP).await {
+            Ok(stats) => output::print(&stats.render()),
+            Err(e) => eprintln!("{} {}", "Error:".red().bold(), e),
+        }
+    }
+
+    async fn handle_watch_command(&mut self, args: &str) -> CqlResult<()> {
+        let (interval, query) = match args.split_once(char::is_whitespace) {
+            Some((interval, query)) => (interval, Some(query.trim().to_string())),
+            None => (args, None),
+        };
+
+        let interval = interval.parse::<f64>()
+            .ok()
+            .and_then(|secs| std::time::Duration::try_from_secs_f64(secs).ok())
+            .filter(|d| !d.is_zero());
+        let Some(interval) = interval else {
+            println!("Usage: \\watch <seconds> [query]");
+            return Ok(());
+        };
+
+        let Some(query) = query.filter(|q| !q.is_empty()).or_else(|| self.last_query.clone()) else {
+            println!("{}", "No query to watch. Pass one or run a query first.".yellow());
+            return Ok(());
+        };
+
+        let statements = split_statements(&query);
+        self.last_query = Some(query);
+        self.executor.watch(&statements, interval, &self.output_format, &self.format_options).await
+    }
+
+    /// `\generate-ddl <table> [query]`: proposes a CREATE TABLE for the
+    /// result columns of `query`, by default the last query
+    async fn handle_generate_ddl_command(&self, args: &str) {
+        let (table, query) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        let query = Some(query.trim().to_string()).filter(|q| !q.is_empty()).or_else(|| self.last_query.clone());
+        let (false, Some(query)) = (table.is_empty(), query) else {
+            println!("Usage: \\generate-ddl <table> [query]");
+            return;
+        };
+
+        if let Err(e) = self.executor.generate_ddl(table, &query).await {
+            eprintln!("{} {}", "Error:".red().bold(), e);
+        }
+    }
+
+    /// `\diff [query]`: the first run stores the result, each further run
+    /// of the same statement prints what changed since the run before
+    async fn handle_diff_command(&mut self, args: &str) -> CqlResult<()> {
+        let Some(query) = Some(args.to_string()).filter(|q| !q.is_empty()).or_else(|| self.last_query.clone()) else {
+            println!("Usage: \\diff <query>");
+            return Ok(());
+        };
+
+        let snapshot = self.executor.diff_snapshot(&query, &self.format_options).await?;
+        let key = crate::executor::normalize_statement(&query, true);
+        match self.diff_snapshots.get(&key) {
+            Some(previous) => output::print(&snapshot.diff(previous)),
+            None => println!(
+                "{}",
+                format!("Stored {} row(s), run \\diff again to see what changed", snapshot.row_count()).cyan()
+            ),
+        }
+
+        self.diff_snapshots.insert(key, snapshot);
+        self.last_query = Some(query);
+        Ok(())
+    }
+
+    async fn warn_if_wide_select(&self, query: &str) {
+        let Some(threshold) = self.wide_table_threshold else {
+            return;
+        };
+
+        static SELECT_STAR: OnceLock<Regex> = OnceLock::new();
+        let select_star = SELECT_STAR.get_or_init(|| {
+            Regex::new(r#"(?i)^\s*select\s+(?:json\s+)?\*\s+from\s+((?:"(?:[^"]|"")*"|\w+)(?:\s*\.\s*(?:"(?:[^"]|"")*"|\w+))?)"#)
+                .unwrap()
+        });
+        let Some((keyspace, table)) = select_star.captures(query).and_then(|captures| table_reference(&captures[1])) else {
+            return;
+        };
+        let Some(keyspace) = keyspace.or_else(|| self.executor.current_keyspace()) else {
+            return;
+        };
+
+        let columns = match self.executor.query_schema(
+            "SELECT column_name FROM system_schema.columns WHERE keyspace_name = ? AND table_name = ?",
+            (&keyspace, &table),
+        ).await {
+            Ok(result) => result.rows.map(|rows| rows.len()).unwrap_or(0),
+            Err(_) => return,
+        };
+
+        if columns > threshold {
+            eprintln!(
+                "{} {}.{} has {} columns; the table output may be hard to read. Consider selecting explicit columns or \\format json.",
+                "Warning:".yellow().bold(),
+                keyspace,
+                table,
+                columns
+            );
+        }
+    }
+
+    /// Copies the last printed result, without colors, to the system clipboard
+    fn copy_last_output(&mut self) {
+        let Some(output) = self.executor.last_output() else {
+            println!("{}", "No result to copy. Run a query first (results fetched page by page are not kept).".yellow());
+            return;
+        };
+
+        let ansi = Regex::new(r"\x1b\[[0-9;]*m").expect("valid regex");
+        let text = ansi.replace_all(output, "").into_owned();
+        let lines = text.lines().count();
+
+        let clipboard = match self.clipboard.take().map_or_else(arboard::Clipboard::new, Ok) {
+            Ok(clipboard) => self.clipboard.insert(clipboard),
+            Err(e) => {
+                eprintln!("{} Clipboard is not available: {}", "Warning:".yellow().bold(), e);
+                return;
+            }
+        };
+        match clipboard.set_text(text) {
+            Ok(()) => println!("Copied {} line(s) to the clipboard", lines.to_string().cyan()),
+            Err(e) => eprintln!("{} Clipboard is not available: {}", "Warning:".yellow().bold(), e),
+        }
+    }
+
+    fn print_settings(&self) {
+        let config = self.executor.connection().config();
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" }.to_string();
+        let paging = match self.executor.auto_page_threshold() {
+            0 => "off".to_string(),
+            n => format!("above {} rows", n),
+        };
+
+        let settings = vec![
+            ("Output format", self.output_format.clone()),
+            ("Keyspace", self.executor.current_keyspace().unwrap_or_else(|| "-".to_string())),
+            ("Consistency", consistency_name(self.executor.consistency()).to_string()),
+            ("Serial consistency", serial_consistency_name(self.executor.serial_consistency()).to_string()),
+            ("Schema consistency", self.executor.schema_consistency()
+                .map(|consistency| consistency_name(consistency).to_string())
+                .unwrap_or_else(|| "same as data".to_string())),
+            ("Auto paging", paging),
+            ("Page timeout", match self.executor.page_timeout() {
+                Some(timeout) => format!(
+                    "{:.1}s, {} retry(s)",
+                    timeout.as_secs_f64(),
+                    self.executor.page_retries()
+                ),
+                None => "driver default".to_string(),
+            }),
+            ("ALLOW FILTERING", if self.executor.allow_filtering() { "allowed" } else { "refused" }.to_string()),
+            ("Slow query threshold", self.executor.slow_query_threshold()
+                .map(|threshold| format!("{} ms", threshold.as_millis()))
+                .unwrap_or_else(|| "off".to_string())),
+            ("Repeat headers per page", on_off(self.format_options.repeat_headers)),
+            ("Row filter (grep)", self.format_options.grep.as_ref()
+                .map(|r| r.as_str().to_string())
+                .unwrap_or_else(|| "off".to_string())),
+            ("Colorize values", on_off(self.format_options.colorize_values)),
+            ("Row numbers", on_off(self.format_options.row_numbers)),
+            ("Expanded display", on_off(self.format_options.expanded)),
+            ("Table width", match self.format_options.max_width {
+                Some(0) => "unlimited".to_string(),
+                Some(n) => format!("{} characters", n),
+                None => "terminal width".to_string(),
+            }),
+            ("Column width", match self.format_options.max_col_width {
+                Some(0) => "unlimited".to_string(),
+                Some(n) => format!("{} characters", n),
+                None => format!("{} characters", DEFAULT_MAX_COL_WIDTH),
+            }),
+            ("Float precision", self.format_options.float_precision
+                .map(|n| format!("{} decimal places", n))
+                .unwrap_or_else(|| "full".to_string())),
+            ("Wide table warning", self.wide_table_threshold
+                .map(|n| format!("above {} columns", n))
+                .unwrap_or_else(|| "off".to_string())),
+            ("CSV for Excel", on_off(self.format_options.csv_excel)),
+            ("Strip trailing ';'", on_off(self.executor.strip_trailing_semicolon())),
+            ("Hosts", format!("{} (port {})", config.hosts.join(", "), config.port)),
+            ("Username", config.username.clone().unwrap_or_else(|| "-".to_string())),
+            ("Authentication", config.auth_provider.to_string()),
+            ("SSL/TLS", if config.ssl_enabled {
+                format!("on (verify certificates: {})", on_off(config.ssl_verify))
+            } else {
+                "off".to_string()
+            }),
+        ];
+
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_BOX_CHARS);
+        table.set_titles(Row::new(vec![
+            Cell::new("Setting").style_spec("b"),
+            Cell::new("Value").style_spec("b"),
+        ]));
+        for (name, value) in settings {
+            table.add_row(Row::new(vec![Cell::new(name), Cell::new(&value)]));
+        }
+        output::print(&table.to_string());
+    }
+
+    fn handle_grep_command(&mut self, pattern: &str) {
+        if pattern.is_empty() || pattern.eq_ignore_ascii_case("off") {
+            self.format_options.grep = None;
+            println!("{}", "Row filter cleared".cyan());
+            return;
+        }
+
+        match Regex::new(pattern) {
+            Ok(regex) => {
+                self.format_options.grep = Some(regex);
+                println!("Row filter set to: {}", pattern.cyan());
+            }
+            Err(e) => {
+                eprintln!("{} Invalid regex: {}", "Error:".red().bold(), e);
+            }
+        }
+    }
+
     async fn handle_describe_command(&mut self, command: &str) {
         let query = if command == "\\dk" {
             "SELECT keyspace_name FROM system_schema.keyspaces;".to_string()
@@ -406,10 +1743,14 @@ impl Repl {
                 "SELECT keyspace_name, table_name FROM system_schema.tables;".to_string()
             }
         } else {
-            command.to_string() + ";"
+            let command = command.trim_end_matches(';');
+            match self.executor.current_keyspace() {
+                Some(keyspace) => qualify_describe(command, &keyspace) + ";",
+                None => command.to_string() + ";",
+            }
         };
 
-        match self.executor.execute_and_print(&query, &self.output_format).await {
+        match self.executor.execute_and_print(&query, &self.output_format, &self.format_options).await {
             Ok(_) => {}
             Err(e) => {
                 eprintln!("{} {}", "Error:".red().bold(), e);
@@ -431,13 +1772,14 @@ impl Repl {
         } else {
             format!("{}_{}.csv", keyspace, table_name)
         };
+        let file_name = self.resolve_path(&file_name).to_string_lossy().into_owned();
 
         let query = format!("SELECT * FROM {}.{};", keyspace, table_name);
         let result = self.executor.execute(&query).await?;
         let row_count = result.rows.as_ref().map(|rows| rows.len()).unwrap_or(0);
-        let csv_output = format_result(&result, OutputFormat::Csv)?;
+        let csv_output = format_result(&result, OutputFormat::Csv, &self.format_options)?;
 
-        std::fs::write(&file_name, csv_output)?;
+        files::write(&file_name, output::encode(&csv_output))?;
         println!(
             "{} {} ({})",
             "Exported table to".green(),
@@ -449,9 +1791,15 @@ impl Repl {
     }
 
     async fn handle_import_command(&mut self, command: &str) -> CqlResult<()> {
+        let command = command.trim().trim_end_matches(';');
+        let with = Regex::new(r"(?i)\s+with\s+").expect("valid regex");
+        let (command, options) = match with.find(command) {
+            Some(found) => (&command[..found.start()], ImportOptions::parse(&command[found.end()..])?),
+            None => (command, ImportOptions::default()),
+        };
         let parts: Vec<&str> = command.split_whitespace().collect();
-        if parts.len() < 2 {
-            println!("Usage: import <table_name> [file.csv]");
+        if parts.len() < 2 || parts.len() > 3 {
+            println!("Usage: import <table_name> [file.csv] [WITH TTL=<seconds> AND TIMESTAMP=<micros>]");
             return Ok(());
         }
 
@@ -462,16 +1810,25 @@ impl Repl {
         } else {
             format!("{}_{}.csv", keyspace, table_name)
         };
+        let file_name = self.resolve_path(&file_name).to_string_lossy().into_owned();
 
         let column_types = self.get_column_types(&keyspace, &table_name).await?;
         if column_types.is_empty() {
             println!("{}", "No column metadata found for table.".yellow());
             return Ok(());
         }
+        if options.ttl.is_some() && column_types.values().any(|t| t.eq_ignore_ascii_case("counter")) {
+            return Err(CqlError::InvalidQuery(format!(
+                "import: {}.{} has counter columns, which cannot have a TTL",
+                keyspace, table_name
+            )));
+        }
 
-        let file = File::open(&file_name)?;
+        let file = files::open_reader(&file_name)?;
+        // Rows with the wrong number of fields are reported below, with their content
         let mut reader = csv::ReaderBuilder::new()
             .has_headers(true)
+            .flexible(true)
             .from_reader(file);
 
         let headers = reader.headers()
@@ -493,42 +1850,44 @@ impl Repl {
             }
         }
 
-        let mut imported = 0usize;
-
-        for record in reader.records() {
-            let record = record
-                .map_err(|e| crate::error::CqlError::QueryError(format!("CSV record read failed: {}", e)))?;
+        let target = ImportTarget {
+            table: format!("{}.{}", keyspace, table_name),
+            header_names: &header_names,
+            column_types: &column_types,
+            using: options.using_clause(),
+        };
 
-            if record.len() != header_names.len() {
-                return Err(crate::error::CqlError::InvalidQuery(format!(
-                    "CSV row has {} values but {} columns are expected",
-                    record.len(),
-                    header_names.len()
-                )));
+        // Rows are read one batch at a time, as the next batch can be sent,
+        // so only the batches in flight are held in memory. Rows that cannot
+        // be sent (malformed CSV, invalid values) are collected with the rows
+        // the server rejects instead of aborting.
+        let rejected = std::cell::RefCell::new(Vec::new());
+        let batch_size = self.import_batch_size.max(1);
+        let mut records = reader.records();
+        let batches = std::iter::from_fn(|| {
+            let mut batch = Vec::with_capacity(batch_size);
+            for record in records.by_ref() {
+                match target.row(record) {
+                    Ok(row) => batch.push(row),
+                    Err(failure) => rejected.borrow_mut().push(failure),
+                }
+                if batch.len() == batch_size {
+                    break;
+                }
             }
-
-            let values: Vec<String> = header_names
-                .iter()
-                .zip(record.iter())
-                .map(|(header, value)| {
-                    let cql_type = column_types.get(header)
-                        .map(|s| s.as_str())
-                        .unwrap_or("text");
-                    Self::csv_value_to_cql_literal(value, cql_type)
-                })
-                .collect();
-
-            let insert = format!(
-                "INSERT INTO {}.{} ({}) VALUES ({});",
-                keyspace,
-                table_name,
-                header_names.join(", "),
-                values.join(", ")
-            );
-
-            self.executor.execute(&insert).await?;
-            imported += 1;
-        }
+            (!batch.is_empty()).then_some(batch)
+        });
+
+        let executor = &self.executor;
+        let (imported, mut failures) = stream::iter(batches)
+            .map(|batch| async move { Self::import_batch(executor, &batch).await })
+            .buffer_unordered(self.import_max_batches_inflight.max(1))
+            .fold((0usize, Vec::new()), |(imported, mut failures), (count, batch_failures)| async move {
+                failures.extend(batch_failures);
+                (imported + count, failures)
+            })
+            .await;
+        failures.extend(rejected.into_inner());
 
         println!(
             "{} {} ({})",
@@ -537,6 +1896,74 @@ impl Repl {
             format!("{} row(s)", imported).bright_black()
         );
 
+        // An errors file of an earlier run would look like it belongs to this one
+        let error_file = format!("{}.errors", file_name);
+        if failures.is_empty() {
+            match std::fs::remove_file(&error_file) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(CqlError::IoError(e)),
+                _ => {}
+            }
+        } else {
+            failures.sort_by_key(|failure| failure.line);
+            Self::write_import_errors(&error_file, &header_names, &failures)?;
+            eprintln!(
+                "{} {} row(s) failed, written with their line numbers to {}",
+                "Warning:".yellow().bold(),
+                failures.len(),
+                error_file.cyan()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Sends `batch` as one unlogged batch (or a plain INSERT for a single
+    /// row). If the batch fails, its rows are retried one by one so only
+    /// the rows that are actually rejected count as failed. Returns the
+    /// number of imported rows and the failed ones.
+    async fn import_batch(executor: &QueryExecutor, batch: &[ImportRow]) -> (usize, Vec<ImportFailure>) {
+        let statement = match batch {
+            [row] => row.insert.clone(),
+            rows => format!(
+                "BEGIN UNLOGGED BATCH\n{}\nAPPLY BATCH;",
+                rows.iter().map(|row| row.insert.as_str()).collect::<Vec<_>>().join("\n")
+            ),
+        };
+        if executor.execute(&statement).await.is_ok() {
+            return (batch.len(), Vec::new());
+        }
+
+        let mut imported = 0;
+        let mut failures = Vec::new();
+        for row in batch {
+            match executor.execute(&row.insert).await {
+                Ok(_) => imported += 1,
+                Err(e) => failures.push(ImportFailure { line: row.line, fields: row.fields.clone(), error: e.to_string() }),
+            }
+        }
+        (imported, failures)
+    }
+
+    /// Writes the rows `import` could not load as CSV: the source line, the
+    /// error and the original fields, so they can be fixed and imported again
+    fn write_import_errors(path: &str, headers: &[String], failures: &[ImportFailure]) -> CqlResult<()> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        let csv_error = |e: csv::Error| CqlError::QueryError(format!("Failed to write {}: {}", path, e));
+
+        let mut header = vec!["line".to_string(), "error".to_string()];
+        header.extend(headers.iter().cloned());
+        writer.write_record(&header).map_err(csv_error)?;
+        for failure in failures {
+            let mut record = vec![failure.line.to_string(), failure.error.clone()];
+            record.extend(failure.fields.iter().cloned());
+            // Malformed rows may have more or fewer fields than the header
+            record.resize(header.len().max(record.len()), String::new());
+            writer.write_record(&record).map_err(csv_error)?;
+        }
+
+        let content = writer.into_inner()
+            .map_err(|e| CqlError::QueryError(format!("Failed to write {}: {}", path, e)))?;
+        files::write(path, content)?;
         Ok(())
     }
 
@@ -610,25 +2037,22 @@ impl Repl {
         Ok(map)
     }
 
-    fn csv_value_to_cql_literal(value: &str, cql_type: &str) -> String {
+    /// Dates, times, timestamps, durations and inet addresses are parsed the
+    /// way `--param` values are and written back as canonical literals, so
+    /// everything `export` writes for them imports unchanged and malformed
+    /// values are reported per row instead of failing the whole batch.
+    fn csv_value_to_cql_literal(value: &str, cql_type: &str) -> Result<String, String> {
         let trimmed = value.trim();
         if trimmed.is_empty() || trimmed.eq_ignore_ascii_case("NULL") {
-            return "NULL".to_string();
+            return Ok("NULL".to_string());
         }
 
-        let lower = cql_type.to_lowercase();
-
-        if lower.starts_with("text")
-            || lower.starts_with("varchar")
-            || lower.starts_with("ascii")
-            || lower.starts_with("inet")
-            || lower.starts_with("date")
-            || lower.starts_with("time")
-            || lower.starts_with("timestamp")
-        {
-            return format!("'{}'", trimmed.replace('\'', "''"));
+        match crate::params::parse_type(cql_type) {
+            Some(ColumnType::Ascii | ColumnType::Text) => Ok(format!("'{}'", trimmed.replace('\'', "''"))),
+            Some(typ @ (ColumnType::Date | ColumnType::Time | ColumnType::Timestamp | ColumnType::Duration | ColumnType::Inet)) => {
+                crate::params::parse_scalar(trimmed, &typ).map(|value| cql_literal(&value))
+            }
+            _ => Ok(trimmed.to_string()),
         }
-
-        trimmed.to_string()
     }
 }
@@ -0,0 +1,44 @@
+use scylla::frame::response::result::{ColumnSpec, CqlValue, Row};
+use scylla::transport::query_result::QueryResult;
+use colored::*;
+use std::time::Duration;
+
+/// The complete result of one statement, independent of any output format:
+/// column metadata, the rows as typed values, server warnings and how long
+/// the statement took. Formatting is a separate step (`into_query_result`
+/// feeds the formatters), so results can also be built by hand.
+#[derive(Debug, Clone)]
+pub struct StructuredResult {
+    pub columns: Vec<ColumnSpec>,
+    /// `None` for statements that return no rows at all (e.g. INSERT)
+    pub rows: Option<Vec<Vec<Option<CqlValue>>>>,
+    pub warnings: Vec<String>,
+    pub elapsed: Duration,
+}
+
+impl StructuredResult {
+    pub fn from_query_result(result: QueryResult, elapsed: Duration) -> Self {
+        Self {
+            columns: result.col_specs,
+            rows: result.rows.map(|rows| rows.into_iter().map(|row| row.columns).collect()),
+            warnings: result.warnings,
+            elapsed,
+        }
+    }
+
+    /// Prints the server's warnings (e.g. about large partitions) to stderr
+    pub fn print_warnings(&self) {
+        for warning in &self.warnings {
+            eprintln!("{} {}", "Server warning:".yellow().bold(), warning);
+        }
+    }
+
+    /// The result in the driver's representation, as the formatters take it
+    pub fn into_query_result(self) -> QueryResult {
+        let mut result = QueryResult::default();
+        result.col_specs = self.columns;
+        result.rows = self.rows.map(|rows| rows.into_iter().map(|columns| Row { columns }).collect());
+        result.warnings = self.warnings;
+        result
+    }
+}
@@ -0,0 +1,162 @@
+use serde::Serialize;
+use serde_json::{Map, Value as JsonValue};
+use std::collections::{BTreeMap, HashMap};
+use crate::error::{CqlError, CqlResult};
+use crate::executor::QueryExecutor;
+use crate::formatter::{json_row, FormatOptions};
+
+/// A keyspace as read from `system_schema`, the document written by
+/// `describe keyspace NAME --json`
+#[derive(Debug, Serialize)]
+pub struct KeyspaceSchema {
+    pub name: String,
+    pub replication: BTreeMap<String, String>,
+    pub durable_writes: bool,
+    pub tables: Vec<TableSchema>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TableSchema {
+    pub keyspace: String,
+    pub name: String,
+    /// Partition key columns first, then clustering columns, both in key
+    /// order, then the remaining columns by name
+    pub columns: Vec<ColumnSchema>,
+    pub partition_key: Vec<String>,
+    pub clustering_key: Vec<ClusteringColumn>,
+    pub indexes: Vec<IndexSchema>,
+    /// The remaining columns of `system_schema.tables` (compaction,
+    /// gc_grace_seconds, ...), which differ between server versions
+    pub options: Map<String, JsonValue>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ColumnSchema {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub cql_type: String,
+    /// partition_key, clustering, static or regular
+    pub kind: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClusteringColumn {
+    pub name: String,
+    /// asc or desc
+    pub order: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IndexSchema {
+    pub name: String,
+    pub kind: String,
+    /// `target` names the indexed column, `class_name` the implementation
+    /// of custom indexes
+    pub options: BTreeMap<String, String>,
+}
+
+/// Reads `keyspace` with all of its tables
+pub async fn load_keyspace(executor: &QueryExecutor, keyspace: &str) -> CqlResult<KeyspaceSchema> {
+    let result = executor.query_schema(
+        "SELECT replication, durable_writes FROM system_schema.keyspaces WHERE keyspace_name = ?",
+        (keyspace,),
+    ).await?;
+    let (replication, durable_writes) = result
+        .maybe_first_row_typed::<(HashMap<String, String>, bool)>()
+        .map_err(|e| schema_error(keyspace, e))?
+        .ok_or_else(|| CqlError::InvalidQuery(format!("Keyspace '{}' does not exist", keyspace)))?;
+
+    Ok(KeyspaceSchema {
+        name: keyspace.to_string(),
+        replication: replication.into_iter().collect(),
+        durable_writes,
+        tables: load_tables(executor, keyspace, None).await?,
+    })
+}
+
+/// Reads one table
+pub async fn load_table(executor: &QueryExecutor, keyspace: &str, table: &str) -> CqlResult<TableSchema> {
+    load_tables(executor, keyspace, Some(table)).await?
+        .pop()
+        .ok_or_else(|| CqlError::InvalidQuery(format!("Table '{}.{}' does not exist", keyspace, table)))
+}
+
+/// The tables of `keyspace` by name, or only `only` of them
+async fn load_tables(executor: &QueryExecutor, keyspace: &str, only: Option<&str>) -> CqlResult<Vec<TableSchema>> {
+    let mut tables: BTreeMap<String, TableSchema> = BTreeMap::new();
+
+    let result = executor.query_schema("SELECT * FROM system_schema.tables WHERE keyspace_name = ?", (keyspace,)).await?;
+    for row in result.rows.iter().flatten() {
+        let JsonValue::Object(mut options) = json_row(&result.col_specs, row, &FormatOptions::default()) else {
+            continue;
+        };
+        options.remove("keyspace_name");
+        let Some(JsonValue::String(name)) = options.remove("table_name") else {
+            continue;
+        };
+        if only.is_some_and(|only| only != name) {
+            continue;
+        }
+        tables.insert(name.clone(), TableSchema {
+            keyspace: keyspace.to_string(),
+            name,
+            columns: Vec::new(),
+            partition_key: Vec::new(),
+            clustering_key: Vec::new(),
+            indexes: Vec::new(),
+            options,
+        });
+    }
+
+    let result = executor.query_schema(
+        "SELECT table_name, column_name, clustering_order, kind, position, type FROM system_schema.columns WHERE keyspace_name = ?",
+        (keyspace,),
+    ).await?;
+    let mut columns: Vec<(String, String, String, String, i32, String)> = result
+        .rows_typed::<(String, String, String, String, i32, String)>()
+        .map_err(|e| schema_error(keyspace, e))?
+        .filter_map(|row| row.ok())
+        .collect();
+    columns.sort_by_key(|(table, name, _, kind, position, _)| {
+        let rank = match kind.as_str() {
+            "partition_key" => 0,
+            "clustering" => 1,
+            _ => 2,
+        };
+        (table.clone(), rank, if rank < 2 { *position } else { 0 }, name.clone())
+    });
+    for (table, name, order, kind, _, cql_type) in columns {
+        let Some(schema) = tables.get_mut(&table) else {
+            continue;
+        };
+        match kind.as_str() {
+            "partition_key" => schema.partition_key.push(name.clone()),
+            "clustering" => schema.clustering_key.push(ClusteringColumn { name: name.clone(), order }),
+            _ => {}
+        }
+        schema.columns.push(ColumnSchema { name, cql_type, kind });
+    }
+
+    let result = executor.query_schema(
+        "SELECT table_name, index_name, kind, options FROM system_schema.indexes WHERE keyspace_name = ?",
+        (keyspace,),
+    ).await?;
+    let indexes = result
+        .rows_typed::<(String, String, String, HashMap<String, String>)>()
+        .map_err(|e| schema_error(keyspace, e))?
+        .filter_map(|row| row.ok());
+    for (table, name, kind, options) in indexes {
+        if let Some(schema) = tables.get_mut(&table) {
+            schema.indexes.push(IndexSchema { name, kind, options: options.into_iter().collect() });
+        }
+    }
+    for schema in tables.values_mut() {
+        schema.indexes.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    Ok(tables.into_values().collect())
+}
+
+fn schema_error(keyspace: &str, error: impl std::fmt::Display) -> CqlError {
+    CqlError::QueryError(format!("Failed to read the schema of {}: {}", keyspace, error))
+}
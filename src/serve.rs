@@ -0,0 +1,94 @@
+use serde::Deserialize;
+use serde_json::{json, Value as JsonValue};
+use std::io::Write;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use crate::error::{CqlError, CqlResult};
+use crate::executor::QueryExecutor;
+use crate::formatter::{cql_type_name, format_result, json_row, FormatOptions, OutputFormat};
+
+/// One line of input to `cqlrs serve`
+#[derive(Deserialize)]
+struct Request {
+    /// Echoed back unchanged so clients can match responses to requests
+    #[serde(default)]
+    id: JsonValue,
+    query: String,
+    /// Any `--output-format` name; `json` returns the rows as JSON values,
+    /// every other format as the formatted text in `output`
+    format: Option<String>,
+}
+
+/// `cqlrs serve`: reads one JSON request per line from stdin, runs it on the
+/// open session and writes one JSON response per line to stdout, until
+/// stdin is closed. Errors are reported in the response and do not end the
+/// loop, so a client can keep the process for its whole lifetime.
+///
+/// Responses are `{"id", "ok": true, "columns", "warnings", "rows", "elapsed_ms"}` (or
+/// `"output"` instead of `"rows"` for non-JSON formats) and
+/// `{"id", "ok": false, "error", "exit_code"}`.
+pub async fn serve(executor: &QueryExecutor, default_format: &str, options: &FormatOptions) -> CqlResult<()> {
+    // Text output ends up in JSON strings, escape sequences have no place there
+    colored::control::set_override(false);
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let id = request.id.clone();
+                match handle(executor, request, default_format, options).await {
+                    Ok(response) => response,
+                    Err(e) => error_response(id, &e),
+                }
+            }
+            Err(e) => error_response(JsonValue::Null, &CqlError::InvalidQuery(format!("Invalid request: {}", e))),
+        };
+
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{}", response)?;
+        stdout.flush()?;
+    }
+
+    Ok(())
+}
+
+async fn handle(executor: &QueryExecutor, request: Request, default_format: &str, options: &FormatOptions) -> CqlResult<JsonValue> {
+    let result = executor.run(&request.query).await?;
+    let elapsed_ms = result.elapsed.as_secs_f64() * 1000.0;
+    let columns: Vec<JsonValue> = result.columns.iter()
+        .map(|spec| json!({ "name": spec.name, "type": cql_type_name(&spec.typ) }))
+        .collect();
+    let warnings = result.warnings.clone();
+    let result = result.into_query_result();
+
+    let mut response = json!({
+        "id": request.id,
+        "ok": true,
+        "columns": columns,
+        "warnings": warnings,
+        "elapsed_ms": elapsed_ms,
+    });
+    let format = OutputFormat::from_name(request.format.as_deref().unwrap_or(default_format));
+    if matches!(format, OutputFormat::Json) {
+        let rows: Vec<JsonValue> = result.rows.iter()
+            .flatten()
+            .map(|row| json_row(&result.col_specs, row, options))
+            .collect();
+        response["rows"] = JsonValue::Array(rows);
+    } else {
+        response["output"] = JsonValue::String(format_result(&result, format, options)?);
+    }
+    Ok(response)
+}
+
+fn error_response(id: JsonValue, error: &CqlError) -> JsonValue {
+    json!({
+        "id": id,
+        "ok": false,
+        "error": error.to_string(),
+        "exit_code": error.exit_code(),
+    })
+}
@@ -0,0 +1,143 @@
+#[cfg(feature = "sqlite")]
+pub use database::SqliteWriter;
+
+/// What a `SqliteWriter` created
+pub struct SqliteExport {
+    pub table: String,
+    pub rows: usize,
+}
+
+/// Stand-in when cqlrs is built without the `sqlite` feature: `create`
+/// fails, so no writer ever exists
+#[cfg(not(feature = "sqlite"))]
+pub struct SqliteWriter(std::convert::Infallible);
+
+#[cfg(not(feature = "sqlite"))]
+impl SqliteWriter {
+    pub fn create(
+        _path: &std::path::Path,
+        _col_specs: &[scylla::frame::response::result::ColumnSpec],
+    ) -> crate::error::CqlResult<Self> {
+        Err(crate::error::CqlError::ConfigError(
+            "cqlrs was built without the 'sqlite' feature, rebuild with --features sqlite for --output-format sqlite".to_string(),
+        ))
+    }
+
+    pub fn insert(&mut self, _row: &scylla::frame::response::result::Row, _options: &crate::formatter::FormatOptions) -> crate::error::CqlResult<()> {
+        match self.0 {}
+    }
+
+    pub fn finish(self) -> crate::error::CqlResult<SqliteExport> {
+        match self.0 {}
+    }
+}
+
+#[cfg(feature = "sqlite")]
+mod database {
+    use rusqlite::types::Value;
+    use rusqlite::{params_from_iter, Connection, OptionalExtension};
+    use scylla::frame::response::result::{ColumnSpec, ColumnType, CqlValue, Row};
+    use serde_json::Value as JsonValue;
+    use std::path::{Path, PathBuf};
+    use super::SqliteExport;
+    use crate::error::{CqlError, CqlResult};
+    use crate::formatter::{cql_value_to_json, FormatOptions};
+
+    /// Writes rows into a new table of a SQLite database, created if needed.
+    /// The table is named after the CQL table the rows come from, with a
+    /// numeric suffix if the database has one of that name already.
+    /// Collections, UDTs and tuples are stored as JSON text, other values as
+    /// in the json output format. Nothing is visible to other readers of the
+    /// database before `finish`.
+    pub struct SqliteWriter {
+        connection: Connection,
+        path: PathBuf,
+        table: String,
+        insert: String,
+        rows: usize,
+    }
+
+    impl SqliteWriter {
+        pub fn create(path: &Path, col_specs: &[ColumnSpec]) -> CqlResult<Self> {
+            let failed = |e: rusqlite::Error| write_error(path, e);
+            let connection = Connection::open(path).map_err(failed)?;
+            connection.execute_batch("BEGIN").map_err(failed)?;
+
+            let base_name = col_specs.first()
+                .map(|spec| spec.table_spec.table_name().to_string())
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| "result".to_string());
+            let mut table = base_name.clone();
+            let mut suffix = 1;
+            while table_exists(&connection, &table).map_err(failed)? {
+                suffix += 1;
+                table = format!("{}_{}", base_name, suffix);
+            }
+
+            let columns: Vec<String> = col_specs.iter()
+                .map(|spec| format!("{} {}", quote(&spec.name), sqlite_type(&spec.typ)))
+                .collect();
+            connection.execute(&format!("CREATE TABLE {} ({})", quote(&table), columns.join(", ")), [])
+                .map_err(failed)?;
+
+            let placeholders = vec!["?"; col_specs.len()].join(", ");
+            let insert = format!("INSERT INTO {} VALUES ({})", quote(&table), placeholders);
+            Ok(Self { connection, path: path.to_path_buf(), table, insert, rows: 0 })
+        }
+
+        pub fn insert(&mut self, row: &Row, options: &FormatOptions) -> CqlResult<()> {
+            self.connection.prepare_cached(&self.insert)
+                .and_then(|mut insert| insert.execute(params_from_iter(row.columns.iter().map(|value| sqlite_value(value, options)))))
+                .map_err(|e| write_error(&self.path, e))?;
+            self.rows += 1;
+            Ok(())
+        }
+
+        pub fn finish(self) -> CqlResult<SqliteExport> {
+            self.connection.execute_batch("COMMIT").map_err(|e| write_error(&self.path, e))?;
+            Ok(SqliteExport { table: self.table, rows: self.rows })
+        }
+    }
+
+    fn write_error(path: &Path, error: rusqlite::Error) -> CqlError {
+        CqlError::IoError(std::io::Error::other(format!("Failed to write {}: {}", path.display(), error)))
+    }
+
+    fn table_exists(connection: &Connection, table: &str) -> rusqlite::Result<bool> {
+        connection
+            .query_row("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1", [table], |_| Ok(()))
+            .optional()
+            .map(|found| found.is_some())
+    }
+
+    fn quote(identifier: &str) -> String {
+        format!("\"{}\"", identifier.replace('"', "\"\""))
+    }
+
+    /// Column affinity; decimals stay text so no digits are lost
+    fn sqlite_type(typ: &ColumnType) -> &'static str {
+        match typ {
+            ColumnType::Boolean | ColumnType::TinyInt | ColumnType::SmallInt | ColumnType::Int
+            | ColumnType::BigInt | ColumnType::Counter | ColumnType::Varint => "INTEGER",
+            ColumnType::Float | ColumnType::Double => "REAL",
+            ColumnType::Blob => "BLOB",
+            _ => "TEXT",
+        }
+    }
+
+    fn sqlite_value(value: &Option<CqlValue>, options: &FormatOptions) -> Value {
+        if let Some(CqlValue::Blob(bytes)) = value {
+            return Value::Blob(bytes.clone());
+        }
+        match cql_value_to_json(value, options) {
+            JsonValue::Null => Value::Null,
+            JsonValue::Bool(b) => Value::Integer(b as i64),
+            JsonValue::Number(n) => match n.as_i64() {
+                Some(i) => Value::Integer(i),
+                None => Value::Real(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            JsonValue::String(s) => Value::Text(s),
+            json @ (JsonValue::Array(_) | JsonValue::Object(_)) => Value::Text(json.to_string()),
+        }
+    }
+}
@@ -0,0 +1,811 @@
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// What ends a statement in `--execute`/`--file` content
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Separator {
+    Char(char),
+    /// Every line is a statement, unless a string or batch spans lines
+    Newline,
+}
+
+impl Default for Separator {
+    fn default() -> Self {
+        Separator::Char(';')
+    }
+}
+
+impl Separator {
+    /// `newline` or a single character. Characters that start strings or
+    /// comments cannot separate statements.
+    pub fn parse(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("newline") || name == "\\n" {
+            return Some(Separator::Newline);
+        }
+        let mut chars = name.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if !c.is_whitespace() && !matches!(c, '\'' | '"' | '$' | '-' | '/') => Some(Separator::Char(c)),
+            _ => None,
+        }
+    }
+
+    fn matches(self, c: char) -> bool {
+        match self {
+            Separator::Char(separator) => c == separator,
+            Separator::Newline => c == '\n',
+        }
+    }
+}
+
+/// Splits a CQL script into individual statements on `;`.
+///
+/// Semicolons inside string literals (`'...'`, `$$...$$`), quoted
+/// identifiers (`"..."`) and comments (`--`, `//`, `/* */`) are ignored, and
+/// comments are dropped from the output. A `BEGIN ... BATCH` is kept together
+/// until its `APPLY BATCH`, since the statements inside it are `;`-separated.
+pub fn split_statements(input: &str) -> Vec<String> {
+    split_statements_by(input, Separator::default())
+}
+
+/// `split_statements` with another separator. Strings, quoted identifiers,
+/// comments and batches are skipped the same way, so a separator inside a
+/// string literal or a multi-line `'...'` does not end the statement. With
+/// `Separator::Newline` a line comment still ends its line's statement.
+pub fn split_statements_by(input: &str, separator: Separator) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut chars = input.chars().peekable();
+    let mut finish = |current: &mut String| {
+        let statement = current.trim();
+        if !statement.is_empty() {
+            statements.push(statement.to_string());
+        }
+        current.clear();
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                current.push(c);
+                while let Some(next) = chars.next() {
+                    current.push(next);
+                    if next == c {
+                        // A doubled quote is an escaped quote, not the end
+                        if chars.peek() == Some(&c) {
+                            current.push(chars.next().unwrap());
+                        } else {
+                            break;
+                        }
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'$') => {
+                current.push_str("$$");
+                chars.next();
+                while let Some(next) = chars.next() {
+                    current.push(next);
+                    if next == '$' && chars.peek() == Some(&'$') {
+                        current.push(chars.next().unwrap());
+                        break;
+                    }
+                }
+            }
+            '-' | '/' if chars.peek() == Some(&c) => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+                if separator == Separator::Newline && !is_open_batch(&current) {
+                    finish(&mut current);
+                } else {
+                    current.push('\n');
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = '\0';
+                for next in chars.by_ref() {
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+                current.push(' ');
+            }
+            c if separator.matches(c) && is_open_batch(&current) => current.push(c),
+            c if separator.matches(c) => finish(&mut current),
+            _ => current.push(c),
+        }
+    }
+
+    finish(&mut current);
+    statements
+}
+
+fn is_open_batch(statement: &str) -> bool {
+    let words: Vec<String> = statement
+        .split_whitespace()
+        .map(|w| w.to_uppercase())
+        .collect();
+
+    if words.first().map(String::as_str) != Some("BEGIN") {
+        return false;
+    }
+
+    let len = words.len();
+    !(len >= 2 && words[len - 2] == "APPLY" && words[len - 1] == "BATCH")
+}
+
+/// Prefixes the table name after `FROM`, `INTO` and `UPDATE` with `keyspace`
+/// unless it is already qualified, e.g. `SELECT * FROM users` becomes
+/// `SELECT * FROM shop.users`.
+///
+/// Only SELECT, INSERT, UPDATE, DELETE and batches are rewritten; DDL
+/// (`CREATE TABLE`, `CREATE MATERIALIZED VIEW ... AS SELECT`, `TRUNCATE`,
+/// ...) and statements like `REVOKE ... FROM role` are returned unchanged.
+/// This is a token scan, not a CQL parser: string literals, quoted
+/// identifiers and comments are skipped, and a column that is itself named
+/// `from`, `into` or `update` (only possible quoted) is left alone.
+pub fn qualify_tables(statement: &str, keyspace: &str) -> String {
+    let is_dml = keywords(statement).first().is_some_and(|first| {
+        ["SELECT", "INSERT", "UPDATE", "DELETE", "BEGIN"].iter().any(|kw| first.eq_ignore_ascii_case(kw))
+    });
+    if !is_dml {
+        return statement.to_string();
+    }
+
+    let chars: Vec<char> = statement.chars().collect();
+    let keyspace = quote_identifier(keyspace);
+    let mut output = String::with_capacity(statement.len() + keyspace.len());
+    let mut expect_table = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+
+        if c.is_whitespace() {
+            output.push(c);
+            i += 1;
+            continue;
+        }
+
+        match c {
+            '\'' | '"' => {
+                i += 1;
+                while i < chars.len() {
+                    if chars[i] == c {
+                        // A doubled quote is an escaped quote, not the end
+                        if chars.get(i + 1) == Some(&c) {
+                            i += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    i += 1;
+                }
+                i = (i + 1).min(chars.len());
+            }
+            '$' if chars.get(i + 1) == Some(&'$') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '$' && chars.get(i + 1) == Some(&'$')) {
+                    i += 1;
+                }
+                i = (i + 2).min(chars.len());
+            }
+            '-' | '/' if chars.get(i + 1) == Some(&c) => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                output.extend(&chars[start..i]);
+                continue;
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i - 1] == '*' && chars[i] == '/') {
+                    i += 1;
+                }
+                i = (i + 1).min(chars.len());
+                output.extend(&chars[start..i]);
+                continue;
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+            }
+            _ => i += 1,
+        }
+
+        let token: String = chars[start..i].iter().collect();
+        let is_identifier = c == '"' || c.is_alphabetic() || c == '_';
+        if expect_table && is_identifier {
+            let next = chars[i..].iter().find(|c| !c.is_whitespace());
+            if next != Some(&'.') {
+                output.push_str(&keyspace);
+                output.push('.');
+            }
+        }
+
+        expect_table = c != '"' && ["FROM", "INTO", "UPDATE"].iter().any(|kw| token.eq_ignore_ascii_case(kw));
+        output.push_str(&token);
+    }
+
+    output
+}
+
+/// `statement` with every string literal (`'...'`, `$$...$$`) replaced by
+/// `'***'`, so it can be logged without the values it carries (passwords,
+/// personal data). Numbers and other unquoted literals are kept.
+pub fn redact_literals(statement: &str) -> String {
+    let chars: Vec<char> = statement.chars().collect();
+    let mut output = String::with_capacity(statement.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\'' => {
+                i += 1;
+                while i < chars.len() {
+                    if chars[i] == '\'' {
+                        if chars.get(i + 1) == Some(&'\'') {
+                            i += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    i += 1;
+                }
+                i += 1;
+                output.push_str("'***'");
+            }
+            '$' if chars.get(i + 1) == Some(&'$') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '$' && chars.get(i + 1) == Some(&'$')) {
+                    i += 1;
+                }
+                i += 2;
+                output.push_str("'***'");
+            }
+            '"' => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    i += 1;
+                }
+                i = (i + 1).min(chars.len());
+                output.extend(&chars[start..i]);
+            }
+            c => {
+                output.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    output
+}
+
+/// Whether `statement` contains the keywords `ALLOW FILTERING`, outside
+/// of string literals, quoted identifiers and comments
+pub fn has_allow_filtering(statement: &str) -> bool {
+    keywords(statement)
+        .windows(2)
+        .any(|pair| pair[0].eq_ignore_ascii_case("allow") && pair[1].eq_ignore_ascii_case("filtering"))
+}
+
+/// The unquoted words of `statement`, in order. String literals (`'...'`,
+/// `$$...$$`), quoted identifiers and comments are skipped, but still
+/// separate the words around them.
+fn keywords(statement: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = statement.char_indices().collect();
+    let at = |i: usize| chars.get(i).map(|&(_, c)| c);
+    let mut words = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (start, c) = chars[i];
+        match c {
+            '\'' | '"' => {
+                i += 1;
+                while i < chars.len() {
+                    if chars[i].1 == c {
+                        if at(i + 1) == Some(c) {
+                            i += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    i += 1;
+                }
+                i += 1;
+            }
+            '$' if at(i + 1) == Some('$') => {
+                i += 2;
+                while i < chars.len() && !(chars[i].1 == '$' && at(i + 1) == Some('$')) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            '-' | '/' if at(i + 1) == Some(c) => {
+                while i < chars.len() && chars[i].1 != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if at(i + 1) == Some('*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i - 1].1 == '*' && chars[i].1 == '/') {
+                    i += 1;
+                }
+                i += 1;
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                while i < chars.len() && (chars[i].1.is_alphanumeric() || chars[i].1 == '_') {
+                    i += 1;
+                }
+                let end = chars.get(i).map(|&(index, _)| index).unwrap_or(statement.len());
+                words.push(&statement[start..end]);
+            }
+            _ => i += 1,
+        }
+    }
+
+    words
+}
+
+/// For an `UPDATE t SET c = c + n WHERE ...` on a counter, the SELECT that
+/// reads the updated counters back, e.g. `SELECT c FROM t WHERE ...`.
+/// Best effort: `None` for every other statement, for assignments that are
+/// not all `c = c ± n`, and for statements with bind markers.
+pub fn counter_readback(statement: &str) -> Option<String> {
+    static UPDATE: OnceLock<Regex> = OnceLock::new();
+    static INCREMENT: OnceLock<Regex> = OnceLock::new();
+    let update = UPDATE.get_or_init(|| {
+        Regex::new(r"(?is)^\s*UPDATE\s+(\S+)\s+(?:USING\s+.+?\s+)?SET\s+(.+?)\s+WHERE\s+(.+?)[\s;]*$").unwrap()
+    });
+    let increment = INCREMENT.get_or_init(|| {
+        Regex::new(r#"(?is)^\s*("?[\w]+"?)\s*=\s*("?[\w]+"?)\s*[+-]\s*\d+\s*$"#).unwrap()
+    });
+
+    if statement.contains('?') {
+        return None;
+    }
+    let captures = update.captures(statement)?;
+    let columns = captures[2].split(',')
+        .map(|assignment| {
+            let assignment = increment.captures(assignment)?;
+            (assignment[1] == assignment[2]).then(|| assignment[1].to_string())
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(format!("SELECT {} FROM {} WHERE {}", columns.join(", "), &captures[1], &captures[3]))
+}
+
+/// Where `--now-column` fills in the current time: the table an INSERT or
+/// UPDATE writes to (as written, possibly `keyspace.table`) and the byte
+/// range of the `:now` given as the column's value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NowPlaceholder {
+    pub table: String,
+    pub range: std::ops::Range<usize>,
+}
+
+/// Finds a `:now` placeholder given as the value of `column` in
+/// `INSERT INTO t (..., column, ...) VALUES (..., :now, ...)` or
+/// `UPDATE t SET column = :now ...`. `:now` anywhere else is left alone.
+pub fn now_placeholder(statement: &str, column: &str) -> Option<NowPlaceholder> {
+    static INSERT: OnceLock<Regex> = OnceLock::new();
+    static UPDATE: OnceLock<Regex> = OnceLock::new();
+    static ASSIGNMENT: OnceLock<Regex> = OnceLock::new();
+    let insert = INSERT.get_or_init(|| {
+        Regex::new(r"(?is)^\s*INSERT\s+INTO\s+([^\s(]+)\s*\(([^)]*)\)\s*VALUES\s*\((.*)\)").unwrap()
+    });
+    let update = UPDATE.get_or_init(|| {
+        Regex::new(r"(?is)^\s*UPDATE\s+(\S+)\s+(?:USING\s+.+?\s+)?SET\s+(.+?)\s+WHERE\s").unwrap()
+    });
+    let assignment = ASSIGNMENT.get_or_init(|| {
+        Regex::new(r#"(?i)^\s*("[^"]+"|\w+)\s*=\s*(:now)\s*$"#).unwrap()
+    });
+    let is_column = |name: &str| match name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+        Some(quoted) => quoted == column,
+        None => name.eq_ignore_ascii_case(column),
+    };
+
+    if let Some(captures) = insert.captures(statement) {
+        let position = captures[2].split(',').position(|name| is_column(name.trim()))?;
+        let values = captures.get(3)?;
+        let value = top_level_items(values.as_str()).into_iter().nth(position)?;
+        let text = &values.as_str()[value.clone()];
+        let trimmed = text.trim_start();
+        if !text.trim().eq_ignore_ascii_case(":now") {
+            return None;
+        }
+        let start = values.start() + value.start + (text.len() - trimmed.len());
+        return Some(NowPlaceholder { table: captures[1].to_string(), range: start..start + ":now".len() });
+    }
+
+    let captures = update.captures(statement)?;
+    let assignments = captures.get(2)?;
+    top_level_items(assignments.as_str()).into_iter().find_map(|item| {
+        let found = assignment.captures(&assignments.as_str()[item.clone()])?;
+        if !is_column(&found[1]) {
+            return None;
+        }
+        let placeholder = found.get(2)?;
+        let start = assignments.start() + item.start + placeholder.start();
+        Some(NowPlaceholder { table: captures[1].to_string(), range: start..start + placeholder.len() })
+    })
+}
+
+/// `DESCRIBE TABLE t` with `t` prefixed by `keyspace`, so it names the
+/// table in the session's keyspace. Qualified names and other statements
+/// are returned unchanged.
+pub fn qualify_describe(statement: &str, keyspace: &str) -> String {
+    static DESCRIBE_TABLE: OnceLock<Regex> = OnceLock::new();
+    let describe_table = DESCRIBE_TABLE.get_or_init(|| {
+        Regex::new(r"(?i)^(\s*DESC(?:RIBE)?\s+(?:TABLE|COLUMNFAMILY)\s+)(\S.*?)(\s*;?\s*)$").unwrap()
+    });
+
+    let Some(captures) = describe_table.captures(statement) else {
+        return statement.to_string();
+    };
+    match table_reference(&captures[2]) {
+        Some((None, table)) => format!(
+            "{}{}.{}{}",
+            &captures[1],
+            quote_identifier(keyspace),
+            quote_identifier(&table),
+            &captures[3]
+        ),
+        _ => statement.to_string(),
+    }
+}
+
+/// The keyspace (if given) and table a `[keyspace.]table` reference names,
+/// as they are stored in `system_schema`: unquoted identifiers are case
+/// insensitive and lowercased, quoted ones are taken as they are. `None`
+/// if `name` is not such a reference.
+pub fn table_reference(name: &str) -> Option<(Option<String>, String)> {
+    let mut parts = Vec::new();
+    let mut rest = name.trim();
+
+    loop {
+        let (part, after) = match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let mut part = String::new();
+                let mut chars = quoted.char_indices().peekable();
+                let mut end = None;
+                while let Some((i, c)) = chars.next() {
+                    if c != '"' {
+                        part.push(c);
+                    } else if chars.next_if(|&(_, next)| next == '"').is_some() {
+                        part.push('"');
+                    } else {
+                        end = Some(i + 1);
+                        break;
+                    }
+                }
+                (part, &quoted[end?..])
+            }
+            None => {
+                let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+                if end == 0 {
+                    return None;
+                }
+                (rest[..end].to_lowercase(), &rest[end..])
+            }
+        };
+        if part.is_empty() {
+            return None;
+        }
+        parts.push(part);
+
+        let after = after.trim_start();
+        match after.strip_prefix('.') {
+            Some(next) => rest = next.trim_start(),
+            None if after.is_empty() => break,
+            None => return None,
+        }
+    }
+
+    match <[String; 2]>::try_from(parts) {
+        Ok([keyspace, table]) => Some((Some(keyspace), table)),
+        Err(mut parts) if parts.len() == 1 => parts.pop().map(|table| (None, table)),
+        Err(_) => None,
+    }
+}
+
+/// Byte ranges of the comma separated items of `list`, not splitting inside
+/// quotes or brackets
+fn top_level_items(list: &str) -> Vec<std::ops::Range<usize>> {
+    let mut items = Vec::new();
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in list.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '(' | '[' | '{') => depth += 1,
+            (None, ')' | ']' | '}') => depth = depth.saturating_sub(1),
+            (None, ',') if depth == 0 => {
+                items.push(start..i);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(start..list.len());
+    items
+}
+
+/// Quotes `name` unless it is a plain lowercase identifier
+pub fn quote_identifier(name: &str) -> String {
+    let plain = name.chars().next().is_some_and(|c| c.is_ascii_lowercase())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if plain {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+/// A problem found by `check_script`, with the 1-based line it starts on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxProblem {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Splits `input` like `split_statements` and reports statements the
+/// splitter cannot end cleanly: unclosed quotes, `$$` strings, comments and
+/// batches, unbalanced brackets and a last statement without `;`. Returns the
+/// number of statements found and the problems. Nothing is sent anywhere, so
+/// this does not catch errors only the server can see.
+pub fn check_script(input: &str) -> (usize, Vec<SyntaxProblem>) {
+    let mut problems = Vec::new();
+    let mut statements = 0;
+    let mut current = String::new();
+    let mut statement_line = None;
+    let mut brackets: Vec<(char, usize)> = Vec::new();
+    let mut line = 1;
+    let mut chars = input.chars().peekable();
+
+    let problem = |line: usize, message: String| SyntaxProblem { line, message };
+
+    while let Some(c) = chars.next() {
+        if !c.is_whitespace() && statement_line.is_none() {
+            statement_line = Some(line);
+        }
+
+        match c {
+            '\n' => {
+                line += 1;
+                current.push(c);
+            }
+            '\'' | '"' => {
+                let start = line;
+                let mut closed = false;
+                current.push(c);
+                while let Some(next) = chars.next() {
+                    current.push(next);
+                    if next == '\n' {
+                        line += 1;
+                    }
+                    if next == c {
+                        if chars.peek() == Some(&c) {
+                            current.push(chars.next().unwrap());
+                        } else {
+                            closed = true;
+                            break;
+                        }
+                    }
+                }
+                if !closed {
+                    let what = if c == '\'' { "string literal" } else { "quoted identifier" };
+                    problems.push(problem(start, format!("Unterminated {} (missing closing {})", what, c)));
+                }
+            }
+            '$' if chars.peek() == Some(&'$') => {
+                let start = line;
+                let mut closed = false;
+                chars.next();
+                current.push_str("$$");
+                while let Some(next) = chars.next() {
+                    current.push(next);
+                    if next == '\n' {
+                        line += 1;
+                    }
+                    if next == '$' && chars.peek() == Some(&'$') {
+                        current.push(chars.next().unwrap());
+                        closed = true;
+                        break;
+                    }
+                }
+                if !closed {
+                    problems.push(problem(start, "Unterminated $$ string".to_string()));
+                }
+            }
+            '-' | '/' if chars.peek() == Some(&c) => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        line += 1;
+                        break;
+                    }
+                }
+                if current.trim().is_empty() {
+                    statement_line = None;
+                }
+                current.push('\n');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                let start = line;
+                let mut closed = false;
+                let mut previous = '\0';
+                chars.next();
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        line += 1;
+                    }
+                    if previous == '*' && next == '/' {
+                        closed = true;
+                        break;
+                    }
+                    previous = next;
+                }
+                if !closed {
+                    problems.push(problem(start, "Unterminated /* comment".to_string()));
+                }
+                if current.trim().is_empty() {
+                    statement_line = None;
+                }
+                current.push(' ');
+            }
+            '(' | '[' | '{' => {
+                brackets.push((c, line));
+                current.push(c);
+            }
+            ')' | ']' | '}' => {
+                let opening = match c {
+                    ')' => '(',
+                    ']' => '[',
+                    _ => '{',
+                };
+                match brackets.pop() {
+                    Some((open, _)) if open == opening => {}
+                    Some((open, open_line)) => problems.push(problem(
+                        line,
+                        format!("'{}' does not match '{}' opened on line {}", c, open, open_line),
+                    )),
+                    None => problems.push(problem(line, format!("'{}' without matching '{}'", c, opening))),
+                }
+                current.push(c);
+            }
+            ';' if is_open_batch(&current) => current.push(c),
+            ';' => {
+                for (open, open_line) in brackets.drain(..) {
+                    problems.push(problem(open_line, format!("'{}' is never closed", open)));
+                }
+                if !current.trim().is_empty() {
+                    statements += 1;
+                }
+                current.clear();
+                statement_line = None;
+            }
+            _ => current.push(c),
+        }
+    }
+
+    if !current.trim().is_empty() {
+        let start = statement_line.unwrap_or(line);
+        statements += 1;
+        for (open, open_line) in brackets.drain(..) {
+            problems.push(problem(open_line, format!("'{}' is never closed", open)));
+        }
+        if is_open_batch(&current) {
+            problems.push(problem(start, "BEGIN BATCH without APPLY BATCH".to_string()));
+        } else {
+            problems.push(problem(start, "Statement is not terminated with ';'".to_string()));
+        }
+    }
+
+    problems.sort_by_key(|problem| problem.line);
+    (statements, problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qualify_tables_prefixes_unqualified_tables_of_dml() {
+        let cases = [
+            ("SELECT * FROM users WHERE id = 1", "SELECT * FROM shop.users WHERE id = 1"),
+            ("select name from users", "select name from shop.users"),
+            ("INSERT INTO users (id) VALUES (1)", "INSERT INTO shop.users (id) VALUES (1)"),
+            ("UPDATE users SET name = 'a' WHERE id = 1", "UPDATE shop.users SET name = 'a' WHERE id = 1"),
+            ("DELETE FROM users WHERE id = 1", "DELETE FROM shop.users WHERE id = 1"),
+            (
+                "BEGIN BATCH INSERT INTO a (id) VALUES (1); UPDATE b SET x = 1 WHERE id = 1; APPLY BATCH",
+                "BEGIN BATCH INSERT INTO shop.a (id) VALUES (1); UPDATE shop.b SET x = 1 WHERE id = 1; APPLY BATCH",
+            ),
+        ];
+        for (statement, expected) in cases {
+            assert_eq!(qualify_tables(statement, "shop"), expected);
+        }
+    }
+
+    #[test]
+    fn qualify_tables_keeps_qualified_names_strings_and_comments() {
+        let cases = [
+            "SELECT * FROM other.users",
+            "SELECT * FROM other . users",
+            "SELECT * FROM \"Other\".users",
+            "INSERT INTO other.users (note) VALUES ('from users')",
+            "SELECT * FROM other.users -- from users",
+        ];
+        for statement in cases {
+            assert_eq!(qualify_tables(statement, "shop"), statement);
+        }
+        assert_eq!(qualify_tables("SELECT * FROM \"Users\"", "shop"), "SELECT * FROM shop.\"Users\"");
+        assert_eq!(qualify_tables("SELECT * FROM users", "Shop"), "SELECT * FROM \"Shop\".users");
+    }
+
+    #[test]
+    fn qualify_tables_leaves_ddl_and_permissions_alone() {
+        let cases = [
+            "REVOKE SELECT ON ks.t FROM some_role",
+            "GRANT SELECT ON TABLE users TO some_role",
+            "CREATE MATERIALIZED VIEW by_name AS SELECT * FROM users WHERE name IS NOT NULL PRIMARY KEY (name, id)",
+            "CREATE TABLE users (id int PRIMARY KEY)",
+            "TRUNCATE users",
+            "LIST ROLES OF some_role",
+        ];
+        for statement in cases {
+            assert_eq!(qualify_tables(statement, "shop"), statement);
+        }
+    }
+
+    #[test]
+    fn qualify_describe_prefixes_unqualified_tables_with_the_active_keyspace() {
+        let cases = [
+            ("DESCRIBE TABLE users", "DESCRIBE TABLE shop.users"),
+            ("desc table users;", "desc table shop.users;"),
+            ("DESCRIBE COLUMNFAMILY users", "DESCRIBE COLUMNFAMILY shop.users"),
+            ("DESCRIBE TABLE Users", "DESCRIBE TABLE shop.users"),
+            ("DESCRIBE TABLE \"Users\"", "DESCRIBE TABLE shop.\"Users\""),
+        ];
+        for (statement, expected) in cases {
+            assert_eq!(qualify_describe(statement, "shop"), expected);
+        }
+        assert_eq!(qualify_describe("DESCRIBE TABLE users", "My Shop"), "DESCRIBE TABLE \"My Shop\".users");
+    }
+
+    #[test]
+    fn qualify_describe_keeps_qualified_tables_and_other_statements() {
+        let cases = [
+            "DESCRIBE TABLE other.users",
+            "DESCRIBE TABLE \"Other\".\"Users\";",
+            "DESCRIBE KEYSPACE shop",
+            "DESCRIBE TABLES",
+            "SELECT * FROM users",
+        ];
+        for statement in cases {
+            assert_eq!(qualify_describe(statement, "shop"), statement);
+        }
+    }
+
+    #[test]
+    fn table_reference_follows_cql_identifier_rules() {
+        let owned = |keyspace: Option<&str>, table: &str| Some((keyspace.map(str::to_string), table.to_string()));
+        assert_eq!(table_reference("users"), owned(None, "users"));
+        assert_eq!(table_reference("Shop.Users"), owned(Some("shop"), "users"));
+        assert_eq!(table_reference("\"Shop\" . \"My \"\"Users\"\"\""), owned(Some("Shop"), "My \"Users\""));
+        assert_eq!(table_reference("a.b.c"), None);
+        assert_eq!(table_reference("users;"), None);
+        assert_eq!(table_reference("\"unclosed"), None);
+        assert_eq!(table_reference(""), None);
+    }
+}
@@ -0,0 +1,142 @@
+use bigdecimal::BigDecimal;
+use prettytable::{format, Cell, Row, Table};
+use scylla::frame::response::result::{ColumnSpec, CqlValue};
+use scylla::transport::query_result::QueryResult;
+
+/// Which results get the `--stats` footer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum StatsMode {
+    #[default]
+    Off,
+    /// Only table output
+    Table,
+    /// Every format; outside of tables the footer goes to stderr
+    Always,
+}
+
+/// Places after the decimal point for averages of exact numbers
+const AVERAGE_SCALE: i64 = 4;
+
+/// Running min/max/sum of one numeric column. Integers, varints and
+/// decimals are summed exactly, floats as `f64`.
+enum Numbers {
+    Exact { min: BigDecimal, max: BigDecimal, sum: BigDecimal },
+    Float { min: f64, max: f64, sum: f64 },
+}
+
+struct ColumnStats {
+    name: String,
+    nulls: usize,
+    count: usize,
+    numbers: Option<Numbers>,
+}
+
+/// Client-side summary of the fetched rows for `--stats`: nulls per column
+/// and min/max/avg/sum of numeric columns. Fed page by page, so paged
+/// results are summarized without keeping their rows.
+pub struct ResultStats {
+    columns: Vec<ColumnStats>,
+    rows: usize,
+}
+
+impl ResultStats {
+    pub fn new(col_specs: &[ColumnSpec]) -> Self {
+        let columns = col_specs.iter()
+            .map(|spec| ColumnStats { name: spec.name.clone(), nulls: 0, count: 0, numbers: None })
+            .collect();
+        Self { columns, rows: 0 }
+    }
+
+    pub fn add(&mut self, result: &QueryResult) {
+        for row in result.rows.iter().flatten() {
+            self.rows += 1;
+            for (column, value) in self.columns.iter_mut().zip(&row.columns) {
+                match value {
+                    None | Some(CqlValue::Empty) => column.nulls += 1,
+                    Some(value) => column.add(value),
+                }
+            }
+        }
+    }
+
+    /// The footer table, or an empty string for results without rows
+    pub fn render(&self) -> String {
+        if self.rows == 0 {
+            return String::new();
+        }
+
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_BOX_CHARS);
+        table.set_titles(Row::new(
+            ["column", "nulls", "min", "max", "avg", "sum"].iter()
+                .map(|title| Cell::new(title).style_spec("b"))
+                .collect(),
+        ));
+
+        for column in &self.columns {
+            let mut cells = vec![Cell::new(&column.name), Cell::new(&column.nulls.to_string()).style_spec("r")];
+            let numbers = match &column.numbers {
+                Some(Numbers::Exact { min, max, sum }) => {
+                    let avg = (sum / BigDecimal::from(column.count as u64)).round(AVERAGE_SCALE).normalized();
+                    [min.normalized().to_string(), max.normalized().to_string(), avg.to_string(), sum.normalized().to_string()]
+                }
+                Some(Numbers::Float { min, max, sum }) => {
+                    [min.to_string(), max.to_string(), (sum / column.count as f64).to_string(), sum.to_string()]
+                }
+                None => Default::default(),
+            };
+            cells.extend(numbers.iter().map(|number| Cell::new(number).style_spec("r")));
+            table.add_row(Row::new(cells));
+        }
+
+        format!("Statistics over {} row(s):\n{}", self.rows, table)
+    }
+}
+
+impl ColumnStats {
+    fn add(&mut self, value: &CqlValue) {
+        let exact = match value {
+            CqlValue::TinyInt(i) => BigDecimal::from(*i),
+            CqlValue::SmallInt(i) => BigDecimal::from(*i),
+            CqlValue::Int(i) => BigDecimal::from(*i),
+            CqlValue::BigInt(i) => BigDecimal::from(*i),
+            CqlValue::Counter(c) => BigDecimal::from(c.0),
+            CqlValue::Varint(v) => BigDecimal::from(num_bigint::BigInt::from(v.clone())),
+            CqlValue::Decimal(d) => BigDecimal::from(d.clone()),
+            CqlValue::Float(f) => return self.add_float(*f as f64),
+            CqlValue::Double(f) => return self.add_float(*f),
+            _ => return,
+        };
+
+        self.count += 1;
+        match &mut self.numbers {
+            Some(Numbers::Exact { min, max, sum }) => {
+                if exact < *min {
+                    *min = exact.clone();
+                }
+                if exact > *max {
+                    *max = exact.clone();
+                }
+                *sum += exact;
+            }
+            _ => self.numbers = Some(Numbers::Exact { min: exact.clone(), max: exact.clone(), sum: exact }),
+        }
+    }
+
+    /// NaN has no place in min/max/sum and is left out
+    fn add_float(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+
+        self.count += 1;
+        match &mut self.numbers {
+            Some(Numbers::Float { min, max, sum }) => {
+                *min = min.min(value);
+                *max = max.max(value);
+                *sum += value;
+            }
+            _ => self.numbers = Some(Numbers::Float { min: value, max: value, sum: value }),
+        }
+    }
+}